@@ -10,8 +10,10 @@ struct TestStruct {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut empty = TestStruct::default();
-    empty.test = true;
+    let empty = TestStruct {
+        test: true,
+        ..Default::default()
+    };
 
     let mut hello_world = TestStruct::default();
     hello_world
@@ -50,7 +52,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ] {
         println!("{}", diff);
 
-        let mut deserializer = serde_json::Deserializer::from_str(&diff);
+        let mut deserializer = serde_json::Deserializer::from_str(diff);
         Apply::apply(&mut deserializer, &mut built)?;
 
         assert_eq!(after, &built);
@@ -1,3 +1,4 @@
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 use serde_diff::{opaque_serde_diff, Apply, Diff, SerdeDiff};
 
@@ -111,7 +112,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Demonstrate applying the diff saved as bincode
     {
         let mut target = target.clone();
-        bincode::config().deserialize_seed(Apply::deserializable(&mut target), &bincode_data)?;
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_seed(Apply::deserializable(&mut target), &bincode_data)?;
 
         println!("diff {:#?} and {:#?}", old, new);
         println!("result {:#?}", target);
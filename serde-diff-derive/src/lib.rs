@@ -35,9 +35,109 @@ mod serde_diff;
 /// }
 /// ```
 ///
+/// Example of a field whose Vecs are diffed as edit scripts using `#[serde_diff(edit_script)]`, so
+/// inserting or removing an element only writes that change instead of every element after it.
+/// ```rust
+/// use serde_diff::SerdeDiff;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff)]
+/// struct MyListStruct {
+///     #[serde_diff(edit_script)]
+///     list: Vec<u32>,
+/// }
+/// ```
+///
+/// Example of a Vec field whose elements are matched by their `id` field using
+/// `#[serde_diff(key = "id")]`. Elements with the same id are diffed with each other wherever they
+/// are in the list, removed and new ids are removed and inserted, and moved elements are reordered.
+/// The key must implement `Hash + Eq` as well as `Serialize + Deserialize`.
+/// ```rust
+/// use serde_diff::SerdeDiff;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize)]
+/// struct Entity {
+///     id: u64,
+///     name: String,
+/// }
+///
+/// #[derive(SerdeDiff)]
+/// struct MyWorldStruct {
+///     #[serde_diff(key = "id")]
+///     entities: Vec<Entity>,
+/// }
+/// ```
+///
+/// Field and variant names in diffs follow serde's `rename`, `rename_all` and `alias` attributes,
+/// so they match the serialized data. `#[serde_diff(rename = "...")]` on a field or variant sets
+/// the name used in diffs instead.
+/// ```rust
+/// use serde_diff::SerdeDiff;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct MyRenamedStruct {
+///     // Written as "maxHealth" and also applied from "max_hp"
+///     #[serde(alias = "max_hp")]
+///     max_health: u32,
+///     // Written as "pos"
+///     #[serde_diff(rename = "pos")]
+///     position: (f32, f32),
+/// }
+/// ```
+///
+/// Fields and variants can be given stable numeric tags with `#[serde_diff(id = N)]`, which
+/// `FieldPathMode::Index` and `FieldPathMode::Tag` write in place of their index or name. Diffs
+/// written with tags keep applying after fields are reordered or added. A tag can't be the same as
+/// another tag or as the index of a field or variant without one.
+/// ```rust
+/// use serde_diff::SerdeDiff;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize)]
+/// struct MyTaggedStruct {
+///     #[serde_diff(id = 10)]
+///     health: u32,
+///     #[serde_diff(id = 11)]
+///     state: MyTaggedEnum,
+/// }
+///
+/// #[derive(SerdeDiff, Serialize, Deserialize)]
+/// enum MyTaggedEnum {
+///     #[serde_diff(id = 1)]
+///     Idle,
+///     #[serde_diff(id = 2)]
+///     Moving { speed: f32 },
+/// }
+/// ```
+///
+/// Example of diffing a type from another crate using `#[serde_diff(remote = "...")]` on a mirror
+/// of its public fields, like serde's remote derive. The mirror gets `diff` and `apply` functions
+/// for the remote type instead of a SerdeDiff impl, and fields of the remote type use them with
+/// `#[serde_diff(with = "...")]`. Any module with such `diff` and `apply` functions can be used
+/// with `with`. Fields left out of the mirror are not diffed, and the mirror itself is never
+/// constructed, so it needs `#[allow(dead_code)]`. Changing the variant of a remote enum replaces
+/// it whole, which requires the remote type to implement `Serialize + Deserialize`.
+/// ```rust
+/// use serde_diff::SerdeDiff;
+/// use serde::{Serialize, Deserialize};
+/// #[allow(dead_code)]
+/// #[derive(SerdeDiff)]
+/// #[serde_diff(remote = "std::ops::Range<u32>")]
+/// struct RangeDef {
+///     start: u32,
+///     end: u32,
+/// }
+///
+/// #[derive(SerdeDiff, Serialize, Deserialize)]
+/// struct MySelection {
+///     #[serde_diff(with = "RangeDef")]
+///     range: std::ops::Range<u32>,
+/// }
+/// ```
+///
 /// Example of diffing a target struct `MySimpleStruct` that is being used for serialization instead
 /// of the struct `MyComplexStruct` itself. Useful for cases where derived data is present at
-/// runtime, but not wanted in the serialized form.
+/// runtime, but not wanted in the serialized form. Both values are cloned and converted on every
+/// diff and apply, so a `remote` mirror is faster for large types.
 /// ```rust
 /// use serde_diff::SerdeDiff;
 /// use serde::{Serialize, Deserialize};
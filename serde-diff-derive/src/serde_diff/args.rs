@@ -12,6 +12,10 @@ pub struct SerdeDiffStructArgs {
     /// If specified, the struct we will convert to before performing diff operations
     #[darling(default)]
     pub target: Option<String>,
+    /// If specified, the foreign type this struct mirrors. Diff and apply functions for the foreign
+    /// type are generated on this struct instead of a SerdeDiff impl.
+    #[darling(default)]
+    pub remote: Option<String>,
 
     pub generics: syn::Generics,
 }
@@ -33,6 +37,26 @@ pub struct SerdeDiffFieldArgs {
     /// If true, simple diff should be generated inline
     #[darling(default)]
     opaque: bool,
+
+    /// If specified, the name used for this field in diffs instead of its serde name
+    #[darling(default)]
+    rename: Option<String>,
+
+    /// If true, Vecs within this field are diffed as edit scripts of insertions and removals
+    #[darling(default)]
+    edit_script: bool,
+
+    /// If specified, the field is a Vec whose elements are matched by this field of theirs
+    #[darling(default)]
+    key: Option<String>,
+
+    /// If specified, the module whose `diff` and `apply` functions are used for this field
+    #[darling(default)]
+    with: Option<String>,
+
+    /// If specified, the tag identifying this field in diffs instead of its index
+    #[darling(default)]
+    id: Option<u16>,
 }
 
 impl SerdeDiffFieldArgs {
@@ -56,4 +80,29 @@ impl SerdeDiffFieldArgs {
     pub fn opaque(&self) -> bool {
         self.opaque
     }
+
+    /// If specified, the name used for this field in diffs instead of its serde name
+    pub fn rename(&self) -> Option<&str> {
+        self.rename.as_ref().map(String::as_str)
+    }
+
+    /// If true, Vecs within this field are diffed as edit scripts of insertions and removals
+    pub fn edit_script(&self) -> bool {
+        self.edit_script
+    }
+
+    /// If specified, the field is a Vec whose elements are matched by this field of theirs
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_ref().map(String::as_str)
+    }
+
+    /// If specified, the module whose `diff` and `apply` functions are used for this field
+    pub fn with(&self) -> Option<&str> {
+        self.with.as_ref().map(String::as_str)
+    }
+
+    /// If specified, the tag identifying this field in diffs instead of its index
+    pub fn id(&self) -> Option<u16> {
+        self.id
+    }
 }
@@ -1,6 +1,7 @@
 extern crate proc_macro;
 
 mod args;
+mod rename;
 
 use quote::{quote, format_ident};
 
@@ -19,13 +20,27 @@ pub fn macro_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     } else {
         None
     };
+    let remote = match struct_args.remote {
+        Some(_) if target_type.is_some() => {
+            let error = darling::Error::custom("`remote` can't be combined with `target`");
+            return proc_macro::TokenStream::from(error.with_span(&input.ident).write_errors());
+        }
+        Some(ref remote) => match syn::parse_str::<syn::Path>(remote) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                let message = format!("invalid remote type {:?}", remote);
+                return proc_macro::TokenStream::from(syn::Error::new(e.span(), message).to_compile_error());
+            }
+        },
+        None => None,
+    };
     match input.data {
         Data::Struct(..) | Data::Enum(..) => {
             if struct_args.opaque {
-                 generate_opaque(&input, struct_args)
+                 generate_opaque(&input, struct_args, remote)
              } else {
                  // Go ahead and generate the code                 
-                 match generate(&input, struct_args, target_type) {
+                 match generate(&input, struct_args, target_type, remote) {
                      Ok(v) => v,
                      Err(v) => v,
                  }
@@ -36,25 +51,37 @@ pub fn macro_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 }
 
 /// Called per field to parse and verify it
-fn parse_field(f: &syn::Field) -> Result<ParsedField, darling::Error> {
+fn parse_field(f: &syn::Field, rules: rename::RenameRules) -> Result<ParsedField, darling::Error> {
     use darling::FromField;
     let field_args = args::SerdeDiffFieldArgs::from_field(&f)?;
-    Ok(ParsedField { field_args })
+    let names = match field_args.ident() {
+        Some(ident) => Some(rename::names(&ident_name(ident), &f.attrs, rules, false, field_args.rename())?),
+        None => None,
+    };
+    Ok(ParsedField { field_args, names })
 }
 
-fn parse_fields(input: &syn::Fields) -> Vec<Result<ParsedField, darling::Error>> {
+fn parse_fields(input: &syn::Fields, rules: rename::RenameRules) -> Vec<Result<ParsedField, darling::Error>> {
     use syn::Fields;
     match input {
-        Fields::Named(ref fields) => fields.named.iter().map(|f| parse_field(&f)).collect(),
-        Fields::Unnamed(ref fields) => fields.unnamed.iter().map(|f| parse_field(&f)).collect(),
+        Fields::Named(ref fields) => fields.named.iter().map(|f| parse_field(&f, rules)).collect(),
+        Fields::Unnamed(ref fields) => fields.unnamed.iter().map(|f| parse_field(&f, rules)).collect(),
         Fields::Unit => vec![],
     }
 }
 
+/// The name serde uses for an identifier, which doesn't include the `r#` of raw identifiers
+fn ident_name(ident: &syn::Ident) -> String {
+    use syn::ext::IdentExt;
+    ident.unraw().to_string()
+}
+
 /// Parsed metadata for a field
 #[derive(Debug)]
 struct ParsedField {
     field_args: args::SerdeDiffFieldArgs,
+    /// Names of the field in diffs, if it has a name
+    names: Option<rename::Names>,
 }
 
 fn generate_fields_diff(
@@ -69,17 +96,24 @@ fn generate_fields_diff(
             continue;
         }
 
-        let ident = pf.field_args.ident().clone();
-        let ident_as_str = quote!(#ident).to_string();
         let ty = pf.field_args.ty();
         let field_idx = field_idx as u16;
         let left = format_ident!("l{}", field_idx);
         let right = format_ident!("r{}", field_idx);
 
-        let push = if let Some(_) = ident {
-            quote!{ctx.push_field(#ident_as_str);}
-        } else {
-            quote!{ctx.push_field_index(#field_idx);}
+        let push = match (&pf.names, pf.field_args.id()) {
+            (Some(names), Some(id)) => {
+                let name = names.tokens();
+                quote!{ctx.push_tagged_field(#name, #id);}
+            }
+            (Some(names), None) => {
+                let name = names.tokens();
+                quote!{ctx.push_field_with_index(#name, #field_idx);}
+            }
+            (None, id) => {
+                let field_idx = id.unwrap_or(field_idx);
+                quote!{ctx.push_field_index(#field_idx);}
+            }
         };
 
         if pf.field_args.opaque() || !matching {
@@ -92,12 +126,46 @@ fn generate_fields_diff(
                 {
                     #push
                     if #cmp {
-                        ctx.save_value(&#right)?;
+                        ctx.save_change(&#left, &#right)?;
                         __changed__ |= true;
                     }
                     ctx.pop_path_element()?;
                 }
             });
+        } else if let Some(with) = pf.field_args.with() {
+            let with = with_path(with);
+            diff_fn_field_handlers.push(quote! {
+                {
+                    {
+                        #push
+                        __changed__ |= #with::diff(#left, ctx, #right)?;
+                        ctx.pop_path_element()?;
+                    }
+                }
+            });
+        } else if let Some(key) = pf.field_args.key() {
+            let accessor = key_accessor(key);
+            diff_fn_field_handlers.push(quote! {
+                {
+                    {
+                        #push
+                        __changed__ |= serde_diff::diff_keyed_list(ctx, &#left[..], &#right[..], #accessor)?;
+                        ctx.pop_path_element()?;
+                    }
+                }
+            });
+        } else if pf.field_args.edit_script() {
+            diff_fn_field_handlers.push(quote! {
+                {
+                    {
+                        #push
+                        let __mode__ = ctx.set_vec_diff_mode(serde_diff::VecDiffMode::EditScript);
+                        __changed__ |= <#ty as serde_diff::SerdeDiff>::diff(&#left, ctx, &#right)?;
+                        ctx.set_vec_diff_mode(__mode__);
+                        ctx.pop_path_element()?;
+                    }
+                }
+            });
         } else {
             diff_fn_field_handlers.push(quote! {
                 {
@@ -115,8 +183,74 @@ fn generate_fields_diff(
     }
 }
 
+/// Adds the names, tags and fingerprints of the fields that are diffed to `__fingerprint__`
+fn generate_fields_fingerprint(parsed_fields: &[ParsedField]) -> proc_macro2::TokenStream {
+    let field_count = parsed_fields.iter().filter(|pf| !pf.field_args.skip()).count() as u64;
+    let mut statements = vec![quote!{__fingerprint__.write_u64(#field_count);}];
+    for (field_idx, pf) in parsed_fields.iter().enumerate() {
+        if pf.field_args.skip() {
+            continue;
+        }
+        let field_idx = field_idx as u64;
+        let name = match &pf.names {
+            Some(names) => {
+                let name = &names.serialize;
+                quote!{__fingerprint__.write_str(#name);}
+            }
+            None => quote!{__fingerprint__.write_u64(#field_idx);},
+        };
+        let id = pf.field_args.id().map(|id| quote!{__fingerprint__.write_str("id").write_u64(#id as u64);});
+        let ty = pf.field_args.ty();
+        // Fields that aren't diffed with the SerdeDiff impl of their type may not have one
+        let nested = if let Some(with) = pf.field_args.with() {
+            let with = with.rsplit("::").next().unwrap_or(with).trim();
+            quote!{serde_diff::Fingerprint::new("with").write_str(#with).finish()}
+        } else if pf.field_args.opaque() || pf.field_args.key().is_some() {
+            quote!{serde_diff::Fingerprint::deserialized::<#ty>()}
+        } else {
+            quote!{<#ty as serde_diff::SerdeDiff>::fingerprint()}
+        };
+        statements.push(quote! {
+            #name
+            #id
+            __fingerprint__.write_u64(#nested);
+        });
+    }
+    quote! {
+        #(#statements)*
+    }
+}
+
+/// Builds a closure borrowing the key of a keyed list element, given a field path such as `id` or
+/// `meta.id`
+fn key_accessor(key: &str) -> proc_macro2::TokenStream {
+    let members: Result<Vec<syn::Member>, syn::Error> = key.split('.').map(syn::parse_str).collect();
+    match members {
+        Ok(members) => quote! { |__item__| &__item__ #(.#members)* },
+        Err(e) => syn::Error::new(e.span(), format!("invalid key field path {:?}", key)).to_compile_error(),
+    }
+}
+
+/// The name a type's fingerprint starts with: the name of the remote type for a mirror, or else
+/// the type's own name. Module paths are left out so that moving a type keeps its fingerprint.
+fn fingerprint_name(struct_args: &args::SerdeDiffStructArgs, remote: Option<&syn::Path>) -> String {
+    match remote.and_then(|remote| remote.segments.last()) {
+        Some(segment) => ident_name(&segment.ident),
+        None => ident_name(&struct_args.ident),
+    }
+}
+
+/// Parses the path of a module given to `#[serde_diff(with = "...")]`
+fn with_path(with: &str) -> proc_macro2::TokenStream {
+    match syn::parse_str::<syn::Path>(with) {
+        Ok(path) => quote! { #path },
+        Err(e) => syn::Error::new(e.span(), format!("invalid with module path {:?}", with)).to_compile_error(),
+    }
+}
 
-fn enum_fields(fields : &syn::Fields, mutable: bool) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+/// Builds the patterns binding the fields of the left and right values. Patterns for remote types
+/// end with `..` so that mirrors can leave out fields they don't diff.
+fn enum_fields(fields : &syn::Fields, mutable: bool, remote: bool) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
     use syn::Fields;
     let field_match = |f: &syn::Field, name, idx| {
         let name = format_ident!("{}{}", name, idx);
@@ -142,7 +276,11 @@ fn enum_fields(fields : &syn::Fields, mutable: bool) -> (proc_macro2::TokenStrea
             Fields::Unit => vec![]
         }
     };
-    let (left, right) = (fields_match(fields, "l"), fields_match(fields, "r"));    
+    let (mut left, mut right) = (fields_match(fields, "l"), fields_match(fields, "r"));    
+    if remote {
+        left.push(quote!{..});
+        right.push(quote!{..});
+    }
     let (left, right) = match fields {
         Fields::Named(_)  => (quote!{{#(#left),*}}, quote!{{#(#right),*}}),
         Fields::Unnamed(_)  => (quote!{(#(#left),*)}, quote!{(#(#right),*)}),
@@ -151,8 +289,8 @@ fn enum_fields(fields : &syn::Fields, mutable: bool) -> (proc_macro2::TokenStrea
     (left, right)
 }
 
-fn ok_fields(fields : &syn::Fields) -> Result<Vec<ParsedField>, proc_macro::TokenStream> {
-    let parsed_fields = parse_fields(fields);
+fn ok_fields(fields : &syn::Fields, rules: rename::RenameRules) -> Result<Vec<ParsedField>, proc_macro::TokenStream> {
+    let parsed_fields = parse_fields(fields, rules);
     // Check all parsed fields for any errors that may have occurred
     let mut ok_fields: Vec<ParsedField> = vec![];
     let mut errors = vec![];
@@ -164,32 +302,62 @@ fn ok_fields(fields : &syn::Fields) -> Result<Vec<ParsedField>, proc_macro::Toke
     }
     // If any error occurred, return them all here
     if !errors.is_empty() {
-       Err(proc_macro::TokenStream::from(darling::Error::multiple(errors).write_errors()))
+       return Err(proc_macro::TokenStream::from(darling::Error::multiple(errors).write_errors()));
+    }
+    let idents: Vec<String> = ok_fields.iter().filter_map(|pf| pf.field_args.ident().as_ref().map(ident_name)).collect();
+    let mut names: Vec<rename::Names> = ok_fields.iter().filter_map(|pf| pf.names.clone()).collect();
+    rename::accept_identifiers(&idents, &mut names);
+    for (pf, names) in ok_fields.iter_mut().filter(|pf| pf.names.is_some()).zip(names) {
+        pf.names = Some(names);
+    }
+    let field_indices = ok_fields.iter().enumerate().filter(|(_, pf)| !pf.field_args.skip()).map(|(idx, pf)| {
+        (pf.field_args.id().unwrap_or(idx as u16) as u32, pf.field_args.ty())
+    });
+    check_unique_indices(field_indices, "field").map_err(|e| proc_macro::TokenStream::from(e.write_errors()))?;
+    Ok(ok_fields)
+}
+
+/// Checks that the `#[serde_diff(id = N)]` tags of fields or variants differ from each other and
+/// from the index of every field or variant without one, since both are written as indices
+fn check_unique_indices<'a, T: quote::ToTokens + 'a>(indices: impl Iterator<Item = (u32, &'a T)>, kind: &str) -> Result<(), darling::Error> {
+    let mut seen = std::collections::HashSet::new();
+    let errors: Vec<darling::Error> = indices
+        .filter(|(idx, _)| !seen.insert(*idx))
+        .map(|(idx, span)| darling::Error::custom(format!("the id or index {} is used by another {}", idx, kind)).with_span(span))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
     } else {
-        Ok(ok_fields)
+        Err(darling::Error::multiple(errors))
     }
 }
 
-fn generate_arms(name: &syn::Ident, variant: Option<&syn::Ident>, fields: &syn::Fields, matching: bool)
+fn generate_arms(name: &proc_macro2::TokenStream, this: &proc_macro2::TokenStream, remote: bool, variant: Option<(&syn::Ident, u32, bool, &rename::Names)>, fields: &syn::Fields, rules: rename::RenameRules, matching: bool)
                  -> Result<(Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>),
                            proc_macro2::TokenStream>
 {
     let mut diff_match_arms = vec![];
     let mut apply_match_arms = vec![];
-    let parsed_fields = ok_fields(&fields)?;
+    let parsed_fields = ok_fields(&fields, rules)?;
     let diffs = generate_fields_diff(
         &parsed_fields,
         matching,
     );                    
-    let (left, right) =  enum_fields(&fields, false);
-    let variant_specifier = if let Some(id) = variant {
+    let (left, right) =  enum_fields(&fields, false, remote);
+    let variant_specifier = if let Some((id, _, _, _)) = variant {
         quote!{ :: #id}
     } else {
         quote!{}
     };
     
-    let variant_as_str = variant.map(|i| i.to_string());
-    let push_variant = variant.map(|_| quote!{ctx.push_variant(#variant_as_str);});
+    let variant_as_str = variant.map(|(_, _, _, names)| names.tokens());
+    let variant_idx = variant.map(|(_, idx, _, _)| idx);
+    let variant_accepted = variant.map(|(_, _, _, names)| &names.accepted[..]).unwrap_or(&[]);
+    let push_variant = variant.map(|(_, _, tagged, _)| if tagged {
+        quote!{ctx.push_tagged_variant(#variant_as_str, #variant_idx);}
+    } else {
+        quote!{ctx.push_variant_with_index(#variant_as_str, #variant_idx);}
+    });
     let pop_variant = variant.map(|_| quote!{ctx.pop_path_element()?;});
     
     let left = if matching {
@@ -212,7 +380,7 @@ fn generate_arms(name: &syn::Ident, variant: Option<&syn::Ident>, fields: &syn::
             quote!{
                 (#left, #name #variant_specifier #right) => {
                     ctx.push_full_variant();
-                    ctx.save_value(other)?;
+                    ctx.save_change(#this, other)?;
                     ctx.pop_path_element()?;
                 }
             }
@@ -220,7 +388,7 @@ fn generate_arms(name: &syn::Ident, variant: Option<&syn::Ident>, fields: &syn::
     }
     
     if matching {
-        let (left, _right) =  enum_fields(fields, true);
+        let (left, _right) =  enum_fields(fields, true, remote);
         let mut apply_fn_field_handlers = vec![];
         for (field_idx, pf) in parsed_fields.iter().enumerate() {
             // Skip fields marked as #[serde_diff(skip)]
@@ -228,30 +396,56 @@ fn generate_arms(name: &syn::Ident, variant: Option<&syn::Ident>, fields: &syn::
                 continue;
             }
 
-            let ident = pf.field_args.ident().clone();
+            let accepted = pf.names.as_ref().map(|names| &names.accepted);
             let ty = pf.field_args.ty();
             let field_idx = field_idx as u16;
 
             let lhs = format_ident!("l{}", field_idx);
+            // Fields are written with their tag in place of their index if they have one
+            let field_idx = pf.field_args.id().unwrap_or(field_idx);
             if pf.field_args.opaque() {
                 apply_fn_field_handlers.push(quote!(
                     serde_diff::DiffPathElementValue::FieldIndex(#field_idx) =>
                         __changed__ |= ctx.read_value(seq, #lhs)?,
                 ));
-                if let Some(ident_as_str) = ident.map(|s| s.to_string()) {
+                if let Some(accepted) = accepted {
                     apply_fn_field_handlers.push(quote!(
-                        serde_diff::DiffPathElementValue::Field(field) if field == #ident_as_str =>
+                        serde_diff::DiffPathElementValue::Field(field) if #(field == #accepted)||* =>
                             __changed__ |= ctx.read_value(seq, #lhs)?,
                     ));
                 }
+            } else if let Some(with) = pf.field_args.with() {
+                let with = with_path(with);
+                apply_fn_field_handlers.push(quote!(
+                    serde_diff::DiffPathElementValue::FieldIndex(#field_idx) =>
+                        __changed__ |= #with::apply(#lhs, seq, ctx)?,
+                ));
+                if let Some(accepted) = accepted {
+                    apply_fn_field_handlers.push(quote!(
+                        serde_diff::DiffPathElementValue::Field(field) if #(field == #accepted)||* =>
+                            __changed__ |= #with::apply(#lhs, seq, ctx)?,
+                    ));
+                }
+            } else if let Some(key) = pf.field_args.key() {
+                let accessor = key_accessor(key);
+                apply_fn_field_handlers.push(quote!(
+                    serde_diff::DiffPathElementValue::FieldIndex(#field_idx) =>
+                        __changed__ |= serde_diff::apply_keyed_list(#lhs, seq, ctx, #accessor)?,
+                ));
+                if let Some(accepted) = accepted {
+                    apply_fn_field_handlers.push(quote!(
+                        serde_diff::DiffPathElementValue::Field(field) if #(field == #accepted)||* =>
+                            __changed__ |= serde_diff::apply_keyed_list(#lhs, seq, ctx, #accessor)?,
+                    ));
+                }
             } else {
                 apply_fn_field_handlers.push(quote!(
                     serde_diff::DiffPathElementValue::FieldIndex(#field_idx) =>
                         __changed__ |= <#ty as serde_diff::SerdeDiff>::apply(#lhs, seq, ctx)?,
                 ));
-                if let Some(ident_as_str) = ident.map(|s| s.to_string()) {
+                if let Some(accepted) = accepted {
                     apply_fn_field_handlers.push(quote!(
-                        serde_diff::DiffPathElementValue::Field(field) if field == #ident_as_str => 
+                        serde_diff::DiffPathElementValue::Field(field) if #(field == #accepted)||* =>
                             __changed__ |= <#ty as serde_diff::SerdeDiff>::apply(#lhs, seq, ctx)?,
                     ));
                 }
@@ -260,7 +454,11 @@ fn generate_arms(name: &syn::Ident, variant: Option<&syn::Ident>, fields: &syn::
 
         if let Some(_) = variant {
             apply_match_arms.push(quote!{
-                ( &mut #name #variant_specifier #left, Some(serde_diff::DiffPathElementValue::EnumVariant(variant))) if variant == #variant_as_str => {
+                ( &mut #name #variant_specifier #left, Some(element)) if match &element {
+                    serde_diff::DiffPathElementValue::EnumVariant(variant) => #(variant == #variant_accepted)||*,
+                    serde_diff::DiffPathElementValue::EnumVariantIndex(idx) => *idx == #variant_idx,
+                    _ => false,
+                } => {
                     while let Some(element) = ctx.next_path_element(seq)? {
                         match element {
                             #(#apply_fn_field_handlers)* 
@@ -290,17 +488,63 @@ fn generate(
     input: &syn::DeriveInput,
     struct_args: args::SerdeDiffStructArgs,
     target_type: Option<syn::Type>,
+    remote: Option<syn::Path>,
 ) -> Result<proc_macro::TokenStream, proc_macro::TokenStream> {
 
     use syn::Data;
+    // Functions generated for a remote type take the value as `this` instead of `self`, and match
+    // on the remote type's fields
+    let (name, this) = match &remote {
+        Some(remote) => (pattern_path(remote), quote!{this}),
+        None => {
+            let ident = &struct_args.ident;
+            (quote!{#ident}, quote!{self})
+        }
+    };
     let mut diff_match_arms = vec![];
     let mut apply_match_arms = vec![];
 
+    let to_tokens = |e: darling::Error| proc_macro::TokenStream::from(e.write_errors());
+    let container = rename::container_attrs(&input.attrs).map_err(to_tokens)?;
+    let type_name = fingerprint_name(&struct_args, remote.as_ref());
+    let fingerprint;
     let has_variants = match &input.data {
         Data::Enum(e) => {
+            let mut variant_names = vec![];
+            let mut field_rules = vec![];
+            let mut variant_ids = vec![];
+            for v in &e.variants {
+                variant_ids.push(rename::serde_diff_id(&v.attrs).map_err(to_tokens)?);
+                let rename = rename::serde_diff_rename(&v.attrs).map_err(to_tokens)?;
+                let names = rename::names(&ident_name(&v.ident), &v.attrs, container.rename_all, true, rename.as_ref().map(String::as_str));
+                variant_names.push(names.map_err(to_tokens)?);
+                let variant_attrs = rename::container_attrs(&v.attrs).map_err(to_tokens)?;
+                field_rules.push(variant_attrs.rename_all.or(container.rename_all_fields));
+            }
+            let idents: Vec<String> = e.variants.iter().map(|v| ident_name(&v.ident)).collect();
+            rename::accept_identifiers(&idents, &mut variant_names);
+            // Variants are written with their tag in place of their index if they have one
+            let variant_indices: Vec<u32> = variant_ids.iter().enumerate().map(|(idx, id)| id.unwrap_or(idx as u32)).collect();
+            check_unique_indices(variant_indices.iter().copied().zip(e.variants.iter().map(|v| &v.ident)), "variant").map_err(to_tokens)?;
+            let mut fingerprint_variants = vec![];
+            for (((v, names), rules), id) in e.variants.iter().zip(&variant_names).zip(&field_rules).zip(&variant_ids) {
+                let variant_name = &names.serialize;
+                let id = id.map(|id| quote!{__fingerprint__.write_str("id").write_u64(#id as u64);});
+                let fields = generate_fields_fingerprint(&ok_fields(&v.fields, *rules)?);
+                fingerprint_variants.push(quote! {
+                    __fingerprint__.write_str(#variant_name);
+                    #id
+                    #fields
+                });
+            }
+            fingerprint = quote! {
+                let mut __fingerprint__ = serde_diff::Fingerprint::new("enum");
+                __fingerprint__.write_str(#type_name);
+                #(#fingerprint_variants)*
+            };
             for matching in &[true, false] {
-                for v in &e.variants {
-                    let (diff, apply) = generate_arms(&struct_args.ident, Some(&v.ident), &v.fields, *matching)?;
+                for (((v, names), rules), (idx, id)) in e.variants.iter().zip(&variant_names).zip(&field_rules).zip(variant_indices.iter().zip(&variant_ids)) {
+                    let (diff, apply) = generate_arms(&name, &this, remote.is_some(), Some((&v.ident, *idx, id.is_some(), names)), &v.fields, *rules, *matching)?;
                     diff_match_arms.extend(diff);
                     apply_match_arms.extend(apply);
                 }
@@ -309,31 +553,47 @@ fn generate(
         }
         Data::Struct(s) => {
             let matching = true;
-            let (diff, apply) = generate_arms(&struct_args.ident, None, &s.fields, matching)?;
+            let (diff, apply) = generate_arms(&name, &this, remote.is_some(), None, &s.fields, container.rename_all, matching)?;
             diff_match_arms.extend(diff);
             apply_match_arms.extend(apply);
+            let fields = generate_fields_fingerprint(&ok_fields(&s.fields, container.rename_all)?);
+            fingerprint = quote! {
+                let mut __fingerprint__ = serde_diff::Fingerprint::new("struct");
+                __fingerprint__.write_str(#type_name);
+                #fields
+            };
             false
         }
         _ => {unreachable!("Unhandled Type in Enum")},
     };
 
-    // Generate the SerdeDiff::diff function for the type
-    let diff_fn = if let Some(ref ty) = target_type {
+    // Generate the body of the SerdeDiff::fingerprint function for the type
+    let fingerprint_body = if let Some(ref ty) = target_type {
         quote! {
-            fn diff<'a, S: serde_diff::_serde::ser::SerializeSeq>(&self, ctx: &mut serde_diff::DiffContext<'a, S>, other: &Self) -> Result<bool, S::Error> {
-                std::convert::Into::<#ty>::into(std::clone::Clone::clone(self))
-                    .diff(ctx, &std::convert::Into::<#ty>::into(std::clone::Clone::clone(other)))
-            }
+            <#ty as serde_diff::SerdeDiff>::fingerprint()
         }
     } else {
         quote! {
-            fn diff<'a, S: serde_diff::_serde::ser::SerializeSeq>(&self, ctx: &mut serde_diff::DiffContext<'a, S>, other: &Self) -> Result<bool, S::Error> {
-                let mut __changed__ = false;
-                match (self, other) {
-                    #(#diff_match_arms)*
-                }
-                Ok(__changed__)
+            serde_diff::Fingerprint::recursive(#type_name, || {
+                #fingerprint
+                __fingerprint__.finish()
+            })
+        }
+    };
+
+    // Generate the body of the SerdeDiff::diff function for the type
+    let diff_body = if let Some(ref ty) = target_type {
+        quote! {
+            std::convert::Into::<#ty>::into(std::clone::Clone::clone(self))
+                .diff(ctx, &std::convert::Into::<#ty>::into(std::clone::Clone::clone(other)))
+        }
+    } else {
+        quote! {
+            let mut __changed__ = false;
+            match (#this, other) {
+                #(#diff_match_arms)*
             }
+            Ok(__changed__)
         }
     };
 
@@ -341,45 +601,87 @@ fn generate(
     // Generate the SerdeDiff::apply function for the type
     //TODO: Consider using something like the phf crate to avoid a string compare across field names,
     // or consider having the user manually tag their data with a number similar to protobuf
-    let apply_fn = if let Some(ref ty) = target_type {
+    let apply_body = if let Some(ref ty) = target_type {
         quote! {
-            fn apply<'de, A>(
-                &mut self,
-                seq: &mut A,
-                ctx: &mut serde_diff::ApplyContext,
-            ) -> Result<bool, <A as serde_diff::_serde::de::SeqAccess<'de>>::Error>
-            where
-                A: serde_diff::_serde::de::SeqAccess<'de>, {
-                    let mut converted = std::convert::Into::<#ty>::into(std::clone::Clone::clone(self));
-                    let result = converted.apply(seq, ctx);
-                    *self = std::convert::From::<#ty>::from(converted);
-                    result
+            let mut converted = std::convert::Into::<#ty>::into(std::clone::Clone::clone(self));
+            let result = converted.apply(seq, ctx);
+            *self = std::convert::From::<#ty>::from(converted);
+            result
+        }
+    } else if has_variants {
+        quote! {
+            let mut __changed__ = false;
+            match (#this, ctx.next_path_element(seq)?) {
+                (this, Some(serde_diff::DiffPathElementValue::FullEnumVariant)) => {
+                    ctx.read_value(seq, this)?;
+                    __changed__ = true;
+                }
+                #(#apply_match_arms)*
+                _ => ctx.skip_value(seq)?,
             }
+            // The variant is written as a path element below the one the enum was
+            // entered with, so the Exit for the enclosing element is still pending
+            ctx.exit_variant(seq)?;
+            Ok(__changed__)
         }
     } else {
-        if has_variants {
-            quote! {
-                fn apply<'de, A>(
-                    &mut self,
+        quote! {
+            let mut __changed__ = false;
+            match (#this) {
+                #(#apply_match_arms)*
+                _ => ctx.skip_value(seq)?,
+            }
+            Ok(__changed__)
+        }
+    };
+
+    Ok(proc_macro::TokenStream::from(generate_impl(&struct_args, remote.as_ref(), diff_body, apply_body, fingerprint_body)))
+}
+
+/// Wraps the bodies of the diff, apply and fingerprint functions in a SerdeDiff impl for the type,
+/// or in associated functions of the mirror struct for a remote type
+fn generate_impl(
+    struct_args: &args::SerdeDiffStructArgs,
+    remote: Option<&syn::Path>,
+    diff_body: proc_macro2::TokenStream,
+    apply_body: proc_macro2::TokenStream,
+    fingerprint_body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let struct_name = &struct_args.ident;
+    let generics = &struct_args.generics.params;
+    let where_clause =  &struct_args.generics.where_clause;
+    if let Some(remote) = remote {
+        quote! {
+            impl <#generics> #struct_name < #generics> #where_clause {
+                /// Diffs two values of the remote type, as `SerdeDiff::diff` would
+                pub fn diff<'a, S: serde_diff::_serde::ser::SerializeSeq>(this: &#remote, ctx: &mut serde_diff::DiffContext<'a, S>, other: &#remote) -> Result<bool, S::Error> {
+                    #diff_body
+                }
+
+                /// Applies a diff to a value of the remote type, as `SerdeDiff::apply` would
+                pub fn apply<'de, A>(
+                    this: &mut #remote,
                     seq: &mut A,
                     ctx: &mut serde_diff::ApplyContext,
                 ) -> Result<bool, <A as serde_diff::_serde::de::SeqAccess<'de>>::Error>
                 where
                     A: serde_diff::_serde::de::SeqAccess<'de>, {
-                    let mut __changed__ = false;
-                    match (self, ctx.next_path_element(seq)?) {
-                        (this, Some(serde_diff::DiffPathElementValue::FullEnumVariant)) => {
-                            ctx.read_value(seq, this)?;
-                            __changed__ = true;
-                        }
-                        #(#apply_match_arms)*
-                        _ => ctx.skip_value(seq)?,
-                    }
-                    Ok(__changed__)
+                    #apply_body
+                }
+
+                /// The fingerprint of the remote type, as `SerdeDiff::fingerprint` would return
+                pub fn fingerprint() -> u64 {
+                    #fingerprint_body
                 }
             }
-        } else {
-            quote! {
+        }
+    } else {
+        quote! {
+            impl <#generics> serde_diff::SerdeDiff for #struct_name < #generics> #where_clause {
+                fn diff<'a, S: serde_diff::_serde::ser::SerializeSeq>(&self, ctx: &mut serde_diff::DiffContext<'a, S>, other: &Self) -> Result<bool, S::Error> {
+                    #diff_body
+                }
+
                 fn apply<'de, A>(
                     &mut self,
                     seq: &mut A,
@@ -387,62 +689,54 @@ fn generate(
                 ) -> Result<bool, <A as serde_diff::_serde::de::SeqAccess<'de>>::Error>
                 where
                     A: serde_diff::_serde::de::SeqAccess<'de>, {
-                    let mut __changed__ = false;
-                    match (self) {
-                        #(#apply_match_arms)*
-                        _ => ctx.skip_value(seq)?,
-                    }
-                    Ok(__changed__)
+                    #apply_body
+                }
+
+                fn fingerprint() -> u64 {
+                    #fingerprint_body
                 }
             }
         }
-    };
+    }
+}
 
-    // Generate the impl block with the diff and apply functions within it
-    let struct_name = &struct_args.ident;
-    let generics = &struct_args.generics.params;
-    let where_clause =  &struct_args.generics.where_clause;
-    let diff_impl = quote! {
-        impl <#generics> serde_diff::SerdeDiff for #struct_name < #generics> #where_clause {
-            #diff_fn
-            #apply_fn
-        }
-    };
-    return Ok(proc_macro::TokenStream::from(quote! {
-        #diff_impl
-    }));
+/// The path of a remote type without the generic arguments of its last segment, which patterns
+/// can't take
+fn pattern_path(remote: &syn::Path) -> proc_macro2::TokenStream {
+    let mut path = remote.clone();
+    if let Some(segment) = path.segments.last_mut() {
+        segment.arguments = syn::PathArguments::None;
+    }
+    quote!{#path}
 }
 
 fn generate_opaque(
     _input: &syn::DeriveInput,
     struct_args: args::SerdeDiffStructArgs,
+    remote: Option<syn::Path>,
 ) -> proc_macro::TokenStream {
-    let struct_name = &struct_args.ident;
-    let diff_impl = quote! {
-        impl serde_diff::SerdeDiff for #struct_name {
-            fn diff<'a, S: serde_diff::_serde::ser::SerializeSeq>(&self, ctx: &mut serde_diff::DiffContext<'a, S>, other: &Self) -> Result<bool, S::Error> {
-                if self != other {
-                    ctx.save_value(other)?;
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            fn apply<'de, A>(
-                &mut self,
-                seq: &mut A,
-                ctx: &mut serde_diff::ApplyContext,
-            ) -> Result<bool, <A as serde_diff::_serde::de::SeqAccess<'de>>::Error>
-            where
-                A: serde_diff::_serde::de::SeqAccess<'de>, {
-                    ctx.read_value(seq, self)
-            }
+    let this = if remote.is_some() { quote!{this} } else { quote!{self} };
+    let diff_body = quote! {
+        if #this != other {
+            ctx.save_change(#this, other)?;
+            Ok(true)
+        } else {
+            Ok(false)
         }
     };
-
-    return proc_macro::TokenStream::from(quote! {
-        #diff_impl
-    });
+    let apply_body = quote! {
+        ctx.read_value(seq, #this)
+    };
+    // Opaque types are read and written whole, so their fingerprint is taken from their
+    // Deserialize impl
+    let ty = match &remote {
+        Some(remote) => quote!{#remote},
+        None => quote!{Self},
+    };
+    let fingerprint_body = quote! {
+        serde_diff::Fingerprint::deserialized::<#ty>()
+    };
+    proc_macro::TokenStream::from(generate_impl(&struct_args, remote.as_ref(), diff_body, apply_body, fingerprint_body))
 }
 
 // Adapted from serde's internal `parse_lit_into_ty` function (with the chain of helper functions directly cargo culted over)
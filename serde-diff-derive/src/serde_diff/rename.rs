@@ -0,0 +1,328 @@
+//! Reads serde's `rename`, `rename_all` and `alias` attributes, so that diff paths use the same
+//! names as the serialized data
+
+use quote::quote;
+
+/// The case conversions supported by `#[serde(rename_all = "...")]`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenameRule {
+    None,
+    LowerCase,
+    UpperCase,
+    PascalCase,
+    CamelCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    KebabCase,
+    ScreamingKebabCase,
+}
+
+impl RenameRule {
+    fn from_str(rule: &str) -> Option<RenameRule> {
+        Some(match rule {
+            "lowercase" => RenameRule::LowerCase,
+            "UPPERCASE" => RenameRule::UpperCase,
+            "PascalCase" => RenameRule::PascalCase,
+            "camelCase" => RenameRule::CamelCase,
+            "snake_case" => RenameRule::SnakeCase,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnakeCase,
+            "kebab-case" => RenameRule::KebabCase,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebabCase,
+            _ => return None,
+        })
+    }
+
+    /// Renames a variant, which is expected to be in PascalCase
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::None | RenameRule::PascalCase => variant.to_owned(),
+            RenameRule::LowerCase => variant.to_ascii_lowercase(),
+            RenameRule::UpperCase => variant.to_ascii_uppercase(),
+            RenameRule::CamelCase => variant[..1].to_ascii_lowercase() + &variant[1..],
+            RenameRule::SnakeCase => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            RenameRule::ScreamingSnakeCase => {
+                RenameRule::SnakeCase.apply_to_variant(variant).to_ascii_uppercase()
+            }
+            RenameRule::KebabCase => RenameRule::SnakeCase.apply_to_variant(variant).replace('_', "-"),
+            RenameRule::ScreamingKebabCase => RenameRule::ScreamingSnakeCase
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+
+    /// Renames a field, which is expected to be in snake_case
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::None | RenameRule::LowerCase | RenameRule::SnakeCase => field.to_owned(),
+            RenameRule::UpperCase | RenameRule::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            RenameRule::PascalCase => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            RenameRule::CamelCase => {
+                let pascal = RenameRule::PascalCase.apply_to_field(field);
+                if pascal.is_empty() {
+                    pascal
+                } else {
+                    pascal[..1].to_ascii_lowercase() + &pascal[1..]
+                }
+            }
+            RenameRule::KebabCase => field.replace('_', "-"),
+            RenameRule::ScreamingKebabCase => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+/// `rename_all` rules for the names a type serializes and deserializes
+#[derive(Debug, Copy, Clone)]
+pub struct RenameRules {
+    pub serialize: RenameRule,
+    pub deserialize: RenameRule,
+}
+
+impl RenameRules {
+    /// Falls back to the other rules where these don't rename
+    pub fn or(self, other: RenameRules) -> RenameRules {
+        let or = |rule, other| if rule == RenameRule::None { other } else { rule };
+        RenameRules {
+            serialize: or(self.serialize, other.serialize),
+            deserialize: or(self.deserialize, other.deserialize),
+        }
+    }
+}
+
+impl Default for RenameRules {
+    fn default() -> Self {
+        RenameRules {
+            serialize: RenameRule::None,
+            deserialize: RenameRule::None,
+        }
+    }
+}
+
+/// The renaming attributes found on a container or variant
+#[derive(Debug, Default)]
+pub struct ContainerAttrs {
+    /// `rename_all`, which applies to the fields of a struct or variant and the variants of an enum
+    pub rename_all: RenameRules,
+    /// `rename_all_fields`, which applies to the fields of every variant of an enum
+    pub rename_all_fields: RenameRules,
+}
+
+/// The names of a field or variant in diff paths
+#[derive(Debug, Clone)]
+pub struct Names {
+    /// The name written into diffs
+    pub serialize: String,
+    /// Every name accepted when applying a diff
+    pub accepted: Vec<String>,
+    /// The name serde serializes with, which differs from `serialize` if it is overridden by
+    /// `#[serde_diff(rename = "...")]`
+    pub serialized: String,
+}
+
+impl Names {
+    /// Code for the name written into diffs. JSON Patches address the serialized value, so the
+    /// diffs they are computed from use the serialized name in place of a `serde_diff` rename.
+    pub fn tokens(&self) -> proc_macro2::TokenStream {
+        let name = &self.serialize;
+        if self.serialized == self.serialize {
+            quote!{#name}
+        } else {
+            let serialized = &self.serialized;
+            quote!{ctx.renamed(#name, #serialized)}
+        }
+    }
+
+    fn accept(&mut self, name: String) {
+        if !self.accepted.contains(&name) {
+            self.accepted.push(name);
+        }
+    }
+}
+
+/// Reads `rename_all` and `rename_all_fields` from the serde attributes of a container or variant
+pub fn container_attrs(attrs: &[syn::Attribute]) -> Result<ContainerAttrs, darling::Error> {
+    let mut container = ContainerAttrs::default();
+    for meta in attr_metas(attrs, "serde") {
+        if meta.path().is_ident("rename_all") {
+            container.rename_all = rename_rules(&meta, container.rename_all)?;
+        } else if meta.path().is_ident("rename_all_fields") {
+            container.rename_all_fields = rename_rules(&meta, container.rename_all_fields)?;
+        }
+    }
+    Ok(container)
+}
+
+/// Works out the names of a field or variant from its serde attributes, the `rename_all` rules of
+/// its container and `#[serde_diff(rename = "...")]`, which takes precedence over serde's names
+pub fn names(
+    ident: &str,
+    attrs: &[syn::Attribute],
+    rules: RenameRules,
+    is_variant: bool,
+    serde_diff_rename: Option<&str>,
+) -> Result<Names, darling::Error> {
+    let apply = |rule: RenameRule| {
+        if is_variant {
+            rule.apply_to_variant(ident)
+        } else {
+            rule.apply_to_field(ident)
+        }
+    };
+    let mut serialize = apply(rules.serialize);
+    let mut deserialize = apply(rules.deserialize);
+    let mut aliases = Vec::new();
+    for meta in attr_metas(attrs, "serde") {
+        if meta.path().is_ident("rename") {
+            let (ser, de) = rename_pair(&meta)?;
+            serialize = ser.unwrap_or(serialize);
+            deserialize = de.unwrap_or(deserialize);
+        } else if meta.path().is_ident("alias") {
+            aliases.push(string_value(&meta)?);
+        }
+    }
+    let serialized = serialize.clone();
+    if let Some(rename) = serde_diff_rename {
+        serialize = rename.to_owned();
+        deserialize = rename.to_owned();
+    }
+    let mut names = Names {
+        serialize: serialize.clone(),
+        accepted: Vec::new(),
+        serialized,
+    };
+    names.accept(serialize);
+    names.accept(deserialize);
+    for alias in aliases {
+        names.accept(alias);
+    }
+    Ok(names)
+}
+
+/// Reads `#[serde_diff(rename = "...")]` from a variant, whose attributes aren't parsed by darling
+pub fn serde_diff_rename(attrs: &[syn::Attribute]) -> Result<Option<String>, darling::Error> {
+    for meta in attr_metas(attrs, "serde_diff") {
+        if meta.path().is_ident("rename") {
+            return string_value(&meta).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Reads `#[serde_diff(id = N)]` from a variant
+pub fn serde_diff_id(attrs: &[syn::Attribute]) -> Result<Option<u32>, darling::Error> {
+    for meta in attr_metas(attrs, "serde_diff") {
+        if meta.path().is_ident("id") {
+            return match &meta {
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    lit: syn::Lit::Int(value),
+                    ..
+                }) => value
+                    .base10_parse()
+                    .map(Some)
+                    .map_err(|e| darling::Error::custom(e).with_span(&meta)),
+                meta => Err(darling::Error::unexpected_type("non-integer").with_span(meta)),
+            };
+        }
+    }
+    Ok(None)
+}
+
+// The items within every `#[namespace(...)]` attribute. Attributes that aren't meta lists are
+// left for serde to report.
+fn attr_metas(attrs: &[syn::Attribute], namespace: &str) -> Vec<syn::Meta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(namespace))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            syn::NestedMeta::Meta(meta) => Some(meta),
+            syn::NestedMeta::Lit(_) => None,
+        })
+        .collect()
+}
+
+fn string_value(meta: &syn::Meta) -> Result<String, darling::Error> {
+    match meta {
+        syn::Meta::NameValue(syn::MetaNameValue {
+            lit: syn::Lit::Str(value),
+            ..
+        }) => Ok(value.value()),
+        meta => Err(darling::Error::unexpected_type("non-string").with_span(meta)),
+    }
+}
+
+// `rename = "..."` or `rename(serialize = "...", deserialize = "...")`
+fn rename_pair(meta: &syn::Meta) -> Result<(Option<String>, Option<String>), darling::Error> {
+    match meta {
+        syn::Meta::List(list) => {
+            let (mut ser, mut de) = (None, None);
+            for nested in &list.nested {
+                if let syn::NestedMeta::Meta(meta) = nested {
+                    if meta.path().is_ident("serialize") {
+                        ser = Some(string_value(meta)?);
+                    } else if meta.path().is_ident("deserialize") {
+                        de = Some(string_value(meta)?);
+                    }
+                }
+            }
+            Ok((ser, de))
+        }
+        meta => {
+            let value = string_value(meta)?;
+            Ok((Some(value.clone()), Some(value)))
+        }
+    }
+}
+
+fn rename_rules(meta: &syn::Meta, rules: RenameRules) -> Result<RenameRules, darling::Error> {
+    let rule = |name: String| {
+        RenameRule::from_str(&name).ok_or_else(|| {
+            darling::Error::unknown_value(&name).with_span(meta)
+        })
+    };
+    let (ser, de) = rename_pair(meta)?;
+    Ok(RenameRules {
+        serialize: ser.map(rule).transpose()?.unwrap_or(rules.serialize),
+        deserialize: de.map(rule).transpose()?.unwrap_or(rules.deserialize),
+    })
+}
+
+/// Diffs written before serde's names were used hold the Rust identifiers, so an identifier is
+/// accepted too unless another field or variant goes by that name
+pub fn accept_identifiers(idents: &[String], names: &mut [Names]) {
+    let taken: Vec<String> = names
+        .iter()
+        .flat_map(|names| names.accepted.iter().cloned())
+        .collect();
+    for (ident, names) in idents.iter().zip(names.iter_mut()) {
+        if !taken.contains(ident) {
+            names.accept(ident.clone());
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use crate::{
     difference::{
-        DeserWrapper, DiffCommandDeserWrapper, DiffCommandIgnoreValue, DiffCommandValue,
-        DiffPathElementValue,
+        DeserWrapper, DiffCommandDeserWrapper, DiffCommandFramedValue, DiffCommandIgnoreValue,
+        DiffCommandValue, DiffPathElementValue,
     },
+    patch::{DisplayPath, PathElement},
+    report::{AppliedChange, ApplyReport, ChangeKind},
     Config, SerdeDiff,
 };
-use serde::{de, Deserialize};
+use serde::{de, Deserialize, Serialize};
 
 /// A deserializable structure that will apply a sequence of diff commands to the target
 ///
@@ -26,6 +28,9 @@ use serde::{de, Deserialize};
 /// ```
 pub struct Apply<'a, T: SerdeDiff> {
     pub(crate) target: &'a mut T,
+    pub(crate) strict: bool,
+    pub(crate) report: Option<&'a mut ApplyReport>,
+    pub(crate) error: Option<&'a mut Option<ApplyError>>,
 }
 
 impl<'a, 'de, T: SerdeDiff> Apply<'a, T> {
@@ -67,15 +72,150 @@ impl<'a, 'de, T: SerdeDiff> de::Visitor<'de> for Apply<'a, T> {
     where
         A: de::SeqAccess<'de>,
     {
-        let mut ctx = ApplyContext {};
-        self.target.apply(&mut seq, &mut ctx)?;
+        let mut ctx = ApplyContext {
+            path: Vec::new(),
+            last_exited: None,
+            strict: self.strict,
+            report: self.report.as_ref().map(|_| ApplyReport::default()),
+            fingerprint: T::fingerprint,
+            framed: false,
+            error: None,
+        };
+        let result = self.target.apply(&mut seq, &mut ctx);
+        if let Some(out) = self.error {
+            *out = ctx.error.take();
+        }
+        result?;
+        if let (Some(out), Some(report)) = (self.report, ctx.report) {
+            *out = report;
+        }
         Ok(())
     }
 }
 
+/// Describes why a sequence of diff commands could not be applied. Most apply functions return
+/// the deserializer's own error type, which carries an `ApplyError` as its message.
+/// `Config::try_apply` returns it as an `ApplyFailure`, so it can be matched on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApplyError {
+    /// A command appeared where it is not valid
+    UnexpectedCommand {
+        /// Name of the command
+        command: &'static str,
+        /// Path at which the command appeared
+        path: Vec<PathElement>,
+    },
+    /// A command that must be followed by a value was followed by something else
+    ExpectedValue {
+        /// Name of the command that requires a value
+        after: &'static str,
+        /// Path at which the value was expected
+        path: Vec<PathElement>,
+    },
+    /// The sequence ended before all elements that were entered had been exited
+    UnexpectedEnd {
+        /// Path that was entered when the sequence ended
+        path: Vec<PathElement>,
+    },
+    /// A command refers to a part of the target that doesn't exist or doesn't match, such as an
+    /// index past the end of a collection. Only reported in strict mode.
+    UnmatchedPath {
+        /// Path that could not be matched
+        path: Vec<PathElement>,
+    },
+    /// The diff starts with the fingerprint of a type whose structure differs from the target's
+    FingerprintMismatch {
+        /// Fingerprint of the target's type
+        expected: u64,
+        /// Fingerprint written in the diff
+        found: u64,
+    },
+}
+
+impl ApplyError {
+    /// Path at which the failure occurred
+    pub fn path(&self) -> &[PathElement] {
+        match self {
+            ApplyError::UnexpectedCommand { path, .. }
+            | ApplyError::ExpectedValue { path, .. }
+            | ApplyError::UnexpectedEnd { path }
+            | ApplyError::UnmatchedPath { path } => path,
+            ApplyError::FingerprintMismatch { .. } => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyError::UnexpectedCommand { command, .. } => {
+                write!(f, "unexpected diff command {}", command)?
+            }
+            ApplyError::ExpectedValue { after, .. } => {
+                write!(f, "expected a value after diff command {}", after)?
+            }
+            ApplyError::UnexpectedEnd { .. } => write!(f, "diff commands ended unexpectedly")?,
+            ApplyError::UnmatchedPath { path } => {
+                return write!(
+                    f,
+                    "diff path {} does not match the target",
+                    DisplayPath(path)
+                )
+            }
+            ApplyError::FingerprintMismatch { expected, found } => {
+                return write!(
+                    f,
+                    "diff was created for a type with fingerprint {:016x}, but the target's \
+                     fingerprint is {:016x}",
+                    found, expected
+                )
+            }
+        }
+        write!(f, " at {}", DisplayPath(self.path()))
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+/// The error returned by `Config::try_apply`. Separates diffs that can't be applied from errors
+/// of the format the diff is read from.
+#[derive(Debug)]
+pub enum ApplyFailure<E> {
+    /// The diff commands are malformed or don't match the target
+    Apply(ApplyError),
+    /// The format failed to read the diff, such as on invalid input or a value of the wrong type
+    Format(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ApplyFailure<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyFailure::Apply(error) => error.fmt(f),
+            ApplyFailure::Format(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for ApplyFailure<E> {}
+
 /// Used during an apply operation for transient data used during the apply
 #[doc(hidden)]
-pub struct ApplyContext {}
+pub struct ApplyContext {
+    /// Elements that have been entered but not yet exited, used to report where errors occur
+    path: Vec<PathElement>,
+    /// The element most recently exited, so removals can be reported with their full path
+    last_exited: Option<PathElement>,
+    /// If true, commands that don't match the target fail instead of being skipped
+    strict: bool,
+    /// Collects the changes made, if a report was requested
+    report: Option<ApplyReport>,
+    /// Computes the fingerprint of the target's type, if the diff starts with one to check
+    fingerprint: fn() -> u64,
+    /// True once the diff has said that its values are framed
+    framed: bool,
+    /// The most recent failure, so `Config::try_apply` can return it
+    error: Option<ApplyError>,
+}
 
 impl ApplyContext {
     /// Returns the next element if it is a path. If it is a Value or Exit, it returns None.
@@ -87,16 +227,42 @@ impl ApplyContext {
         A: de::SeqAccess<'de>,
     {
         use DiffCommandValue::*;
-        let element = match seq.next_element_seed(DiffCommandIgnoreValue {})? {
-            Some(Enter(element)) => Ok(Some(element)),
-            Some(AddKey(_)) | Some(EnterKey(_)) | Some(RemoveKey(_)) => {
-                //self.skip_value(seq);
+        match seq.next_element_seed(DiffCommandIgnoreValue {
+            framed: self.framed,
+        })? {
+            Some(Enter(element)) => {
+                self.path
+                    .push(PathElement::Element(element.clone().into_owned()));
+                Ok(Some(element))
+            }
+            Some(Exit) => {
+                self.exit_element();
                 Ok(None)
             }
-            Some(Value(_)) | Some(Remove(_)) => panic!("unexpected DiffCommand Value or Remove"),
-            Some(Exit) | Some(Nothing) | Some(DeserializedValue) | None => Ok(None),
-        };
-        element
+            Some(Fingerprint(fingerprint)) => {
+                self.check_fingerprint(fingerprint)?;
+                self.next_path_element(seq)
+            }
+            Some(Framed) => {
+                self.framed = true;
+                self.next_path_element(seq)
+            }
+            Some(cmd) => Err(self.unexpected_command(cmd.name())),
+            None => self.end_of_commands().map(|_| None),
+        }
+    }
+    /// Consumes the Exit that closes the path element of an enum variant.
+    pub fn exit_variant<'de, A>(
+        &mut self,
+        seq: &mut A,
+    ) -> Result<(), <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        match self.next_path_element(seq)? {
+            Some(_) => Err(self.unexpected_command("Enter")),
+            None => Ok(()),
+        }
     }
     /// To be called after next_path_element returns a path, but the path is not recognized.
     pub fn skip_value<'de, A>(
@@ -106,6 +272,12 @@ impl ApplyContext {
     where
         A: de::SeqAccess<'de>,
     {
+        if self.strict {
+            return Err(self.unmatched(self.path.clone()));
+        }
+        if let Some(report) = &mut self.report {
+            report.unmatched.push(self.path.clone());
+        }
         self.skip_value_internal(seq, 1)
     }
     fn skip_value_internal<'de, A>(
@@ -117,16 +289,31 @@ impl ApplyContext {
         A: de::SeqAccess<'de>,
     {
         // this tries to skip the value without knowing the type - not possible for some formats..
-        while let Some(cmd) = seq.next_element_seed(DiffCommandIgnoreValue {})? {
+        // unless the diff is framed, in which case every value is skipped as a byte string
+        while let Some(cmd) = seq.next_element_seed(DiffCommandIgnoreValue {
+            framed: self.framed,
+        })? {
             match cmd {
                 DiffCommandValue::Enter(_)
                 | DiffCommandValue::AddKey(_)
-                | DiffCommandValue::EnterKey(_) => depth += 1,
+                | DiffCommandValue::EnterKey(_)
+                | DiffCommandValue::RemoveEntry(_)
+                | DiffCommandValue::InsertAt(_)
+                | DiffCommandValue::RemoveValueAt(_) => depth += 1,
                 DiffCommandValue::Exit => depth -= 1,
-                DiffCommandValue::Value(_) | DiffCommandValue::Remove(_) => depth -= 1, // ignore value, but reduce depth, as it is an implicit Exit
-                DiffCommandValue::RemoveKey(_) => {}
-                DiffCommandValue::Nothing | DiffCommandValue::DeserializedValue => {
-                    panic!("should never serialize cmd Nothing or DeserializedValue")
+                DiffCommandValue::Value(_)
+                | DiffCommandValue::Remove(_)
+                | DiffCommandValue::Replace(..)
+                | DiffCommandValue::RemoveValues(_) => depth -= 1, // ignore value, but reduce depth, as it is an implicit Exit
+                DiffCommandValue::RemoveKey(_)
+                | DiffCommandValue::RemoveAt(_)
+                | DiffCommandValue::Reorder(_) => {}
+                // Fingerprint and Framed are only written before the first command
+                DiffCommandValue::Fingerprint(_)
+                | DiffCommandValue::Framed
+                | DiffCommandValue::Nothing
+                | DiffCommandValue::DeserializedValue => {
+                    return Err(self.unexpected_command(cmd.name()))
                 }
             }
             if depth == 0 {
@@ -134,8 +321,11 @@ impl ApplyContext {
             }
         }
         if depth != 0 {
-            panic!("mismatched DiffCommand::Enter/Exit ")
+            let path = self.path.clone();
+            return Err(self.fail(ApplyError::UnexpectedEnd { path }));
         }
+        // The skipped value was the last element entered
+        self.exit_element();
         Ok(())
     }
     /// Attempts to deserialize a value
@@ -151,38 +341,248 @@ impl ApplyContext {
         // a command if the next element was not a Value
         let cmd = seq.next_element_seed::<DiffCommandDeserWrapper<T>>(DiffCommandDeserWrapper {
             val_wrapper: DeserWrapper { val },
+            framed: self.framed,
         })?;
         match cmd {
-            Some(DiffCommandValue::DeserializedValue) => return Ok(true),
-            Some(DiffCommandValue::Enter(_)) => {
-                self.skip_value_internal(seq, 1)?;
+            Some(DiffCommandValue::DeserializedValue) => {
+                if let Some(report) = &mut self.report {
+                    // A replaced enum variant is reported as a change to the enum itself
+                    let mut path = self.path.clone();
+                    if let Some(PathElement::Element(DiffPathElementValue::FullEnumVariant)) =
+                        path.last()
+                    {
+                        path.pop();
+                    }
+                    report.changes.push(AppliedChange {
+                        path,
+                        kind: ChangeKind::Modified,
+                    });
+                }
+                // A value is an implicit Exit of the element it was written for
+                self.exit_element();
+                return Ok(true);
+            }
+            Some(DiffCommandValue::Enter(element)) => {
+                self.path.push(PathElement::Element(element.into_owned()));
+                self.skip_value(seq)?;
+            }
+            Some(DiffCommandValue::Fingerprint(fingerprint)) => {
+                self.check_fingerprint(fingerprint)?;
+                return self.read_value(seq, val);
             }
-            Some(DiffCommandValue::Exit) => panic!("unexpected Exit command"),
-            _ => {}
+            Some(DiffCommandValue::Framed) => {
+                self.framed = true;
+                return self.read_value(seq, val);
+            }
+            Some(cmd) => return Err(self.unexpected_command(cmd.name())),
+            None => self.end_of_commands()?,
         }
 
         Ok(false)
     }
     /// Returns the next command in the stream. Make sure you know what you're doing!
-    pub fn read_next_command<'de, A, T: for<'c> Deserialize<'c>>(
+    pub fn read_next_command<'de, A, T: Serialize + for<'c> Deserialize<'c>>(
         &mut self,
         seq: &mut A,
     ) -> Result<Option<DiffCommandValue<'de, T>>, <A as de::SeqAccess<'de>>::Error>
     where
         A: de::SeqAccess<'de>,
     {
-        // The visitor for DiffCommandDeserWrapper handles enum cases and returns
-        // a command if the next element was not a Value
-        let cmd = seq.next_element::<DiffCommandValue<'de, T>>()?;
-        Ok(match cmd {
-            cmd @ Some(DiffCommandValue::Remove(_))
-            | cmd @ Some(DiffCommandValue::Value(_))
-            | cmd @ Some(DiffCommandValue::Enter(_))
-            | cmd @ Some(DiffCommandValue::AddKey(_))
-            | cmd @ Some(DiffCommandValue::EnterKey(_))
-            | cmd @ Some(DiffCommandValue::RemoveKey(_))
-            | cmd @ Some(DiffCommandValue::Exit) => cmd,
-            _ => None,
-        })
+        use DiffCommandValue::*;
+        let cmd = if self.framed {
+            seq.next_element_seed(DiffCommandFramedValue {
+                value_type: std::marker::PhantomData,
+            })?
+        } else {
+            seq.next_element::<DiffCommandValue<'de, T>>()?
+        };
+        match &cmd {
+            Some(Enter(element)) => self
+                .path
+                .push(PathElement::Element(element.clone().into_owned())),
+            // AddKey and RemoveEntry are followed by a Value, which exits the key again
+            Some(EnterKey(key)) | Some(AddKey(key)) | Some(RemoveEntry(key)) => {
+                self.path.push(key_element(key)?)
+            }
+            // As are InsertAt and RemoveValueAt for the index
+            Some(InsertAt(idx)) | Some(RemoveValueAt(idx)) => {
+                self.path
+                    .push(PathElement::Element(DiffPathElementValue::CollectionIndex(
+                        *idx,
+                    )))
+            }
+            Some(Value(_))
+            | Some(Replace(..))
+            | Some(Remove(_))
+            | Some(RemoveValues(_))
+            | Some(Exit) => {
+                self.exit_element();
+            }
+            Some(RemoveKey(_)) | Some(RemoveAt(_)) | Some(Reorder(_)) => {}
+            Some(Fingerprint(fingerprint)) => {
+                self.check_fingerprint(*fingerprint)?;
+                return self.read_next_command(seq);
+            }
+            Some(Framed) => {
+                self.framed = true;
+                return self.read_next_command(seq);
+            }
+            Some(internal @ Nothing) | Some(internal @ DeserializedValue) => {
+                return Err(self.unexpected_command(internal.name()))
+            }
+            None => self.end_of_commands()?,
+        }
+        Ok(cmd)
+    }
+    /// Reads the value that must follow the given command, such as AddKey
+    pub fn read_expected_value<'de, A, T: Serialize + for<'c> Deserialize<'c>>(
+        &mut self,
+        seq: &mut A,
+        after: &'static str,
+    ) -> Result<T, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let path = self.path.clone();
+        match self.read_next_command::<A, T>(seq)? {
+            Some(DiffCommandValue::Value(value)) => Ok(value),
+            _ => Err(self.fail(ApplyError::ExpectedValue { after, path })),
+        }
+    }
+    /// Fails unless the fingerprint written in the diff is the fingerprint of the target's type
+    fn check_fingerprint<E: de::Error>(&mut self, found: u64) -> Result<(), E> {
+        let expected = (self.fingerprint)();
+        if found == expected {
+            Ok(())
+        } else {
+            Err(self.fail(ApplyError::FingerprintMismatch { expected, found }))
+        }
+    }
+    /// Path of elements that have been entered but not yet exited
+    pub fn path(&self) -> &[PathElement] {
+        &self.path
+    }
+    /// True if commands that don't match the target should fail instead of being skipped
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+    /// Reports that a map key referred to by the last command doesn't exist in the target. Fails
+    /// in strict mode, otherwise the command is ignored.
+    pub fn unmatched_key<E: de::Error, K: Serialize>(&mut self, key: &K) -> Result<(), E> {
+        if !self.strict && self.report.is_none() {
+            return Ok(());
+        }
+        let mut path = self.path.clone();
+        path.push(key_element(key)?);
+        self.unmatched_path(path)
+    }
+    /// Reports that the command which exited the last element, such as a Remove, doesn't match
+    /// the target. Fails in strict mode, otherwise the command is ignored.
+    pub fn unmatched_exited_element<E: de::Error>(&mut self) -> Result<(), E> {
+        if !self.strict && self.report.is_none() {
+            return Ok(());
+        }
+        let path = self.exited_path(None);
+        self.unmatched_path(path)
+    }
+    /// Reports that the last command, such as a Reorder, doesn't match the collection at the
+    /// current path. Fails in strict mode, otherwise the command is ignored.
+    pub fn unmatched_current<E: de::Error>(&mut self) -> Result<(), E> {
+        if !self.strict && self.report.is_none() {
+            return Ok(());
+        }
+        let path = self.path.clone();
+        self.unmatched_path(path)
     }
+    /// Reports that the given element of the current path, such as an index, doesn't exist in the
+    /// target. Fails in strict mode, otherwise the command is ignored.
+    pub fn unmatched_element<E: de::Error>(&mut self, element: PathElement) -> Result<(), E> {
+        if !self.strict && self.report.is_none() {
+            return Ok(());
+        }
+        let mut path = self.path.clone();
+        path.push(element);
+        self.unmatched_path(path)
+    }
+    /// True if the changes made are being collected into an `ApplyReport`
+    pub fn is_reporting(&self) -> bool {
+        self.report.is_some()
+    }
+    /// Records a change at the current path, followed by the given element
+    pub fn record_change(&mut self, kind: ChangeKind, element: Option<PathElement>) {
+        if let Some(report) = &mut self.report {
+            let mut path = self.path.clone();
+            path.extend(element);
+            report.changes.push(AppliedChange { path, kind });
+        }
+    }
+    /// Records a change to the given key of the map at the current path
+    pub fn record_key_change<E: de::Error, K: Serialize>(
+        &mut self,
+        kind: ChangeKind,
+        key: &K,
+    ) -> Result<(), E> {
+        if self.report.is_some() {
+            let element = key_element(key)?;
+            self.record_change(kind, Some(element));
+        }
+        Ok(())
+    }
+    /// Records a change at the element that was last exited, such as the collection a Remove was
+    /// written for, followed by the given element
+    pub fn record_exited_change(&mut self, kind: ChangeKind, element: Option<PathElement>) {
+        if self.report.is_some() {
+            let path = self.exited_path(element);
+            if let Some(report) = &mut self.report {
+                report.changes.push(AppliedChange { path, kind });
+            }
+        }
+    }
+    fn exited_path(&self, element: Option<PathElement>) -> Vec<PathElement> {
+        let mut path = self.path.clone();
+        path.extend(self.last_exited.clone());
+        path.extend(element);
+        path
+    }
+    fn unmatched_path<E: de::Error>(&mut self, path: Vec<PathElement>) -> Result<(), E> {
+        if self.strict {
+            return Err(self.unmatched(path));
+        }
+        if let Some(report) = &mut self.report {
+            report.unmatched.push(path);
+        }
+        Ok(())
+    }
+    fn unmatched<E: de::Error>(&mut self, path: Vec<PathElement>) -> E {
+        self.fail(ApplyError::UnmatchedPath { path })
+    }
+    fn exit_element(&mut self) {
+        self.last_exited = self.path.pop();
+    }
+    fn unexpected_command<E: de::Error>(&mut self, command: &'static str) -> E {
+        let path = self.path.clone();
+        self.fail(ApplyError::UnexpectedCommand { command, path })
+    }
+    // Running out of commands is only valid once every entered element has been exited
+    fn end_of_commands<E: de::Error>(&mut self) -> Result<(), E> {
+        if self.path.is_empty() {
+            Ok(())
+        } else {
+            let path = self.path.clone();
+            Err(self.fail(ApplyError::UnexpectedEnd { path }))
+        }
+    }
+    // Keeps the failure so it can be returned as an ApplyError, and formats it as the message of
+    // the deserializer's error
+    fn fail<E: de::Error>(&mut self, error: ApplyError) -> E {
+        let message = de::Error::custom(&error);
+        self.error = Some(error);
+        message
+    }
+}
+
+fn key_element<E: de::Error, K: Serialize>(key: &K) -> Result<PathElement, E> {
+    serde_value::to_value(key)
+        .map(PathElement::Key)
+        .map_err(de::Error::custom)
 }
@@ -0,0 +1,614 @@
+use serde::{ser, Serialize, Serializer};
+
+/// An owned copy of a serialized value that keeps everything the serializer was told, such as
+/// struct and variant names, so serializing it again gives the same output as the original value
+pub(crate) enum Content {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Content>),
+    Unit,
+    UnitStruct(&'static str),
+    UnitVariant(&'static str, u32, &'static str),
+    NewtypeStruct(&'static str, Box<Content>),
+    NewtypeVariant(&'static str, u32, &'static str, Box<Content>),
+    Seq(Vec<Content>),
+    Tuple(Vec<Content>),
+    TupleStruct(&'static str, Vec<Content>),
+    TupleVariant(&'static str, u32, &'static str, Vec<Content>),
+    Map(Vec<(Content, Content)>),
+    Struct(&'static str, Vec<(&'static str, Content)>),
+    StructVariant(
+        &'static str,
+        u32,
+        &'static str,
+        Vec<(&'static str, Content)>,
+    ),
+}
+
+impl Serialize for Content {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{
+            SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+            SerializeTupleStruct, SerializeTupleVariant,
+        };
+        match self {
+            Content::Bool(v) => serializer.serialize_bool(*v),
+            Content::I8(v) => serializer.serialize_i8(*v),
+            Content::I16(v) => serializer.serialize_i16(*v),
+            Content::I32(v) => serializer.serialize_i32(*v),
+            Content::I64(v) => serializer.serialize_i64(*v),
+            Content::I128(v) => serializer.serialize_i128(*v),
+            Content::U8(v) => serializer.serialize_u8(*v),
+            Content::U16(v) => serializer.serialize_u16(*v),
+            Content::U32(v) => serializer.serialize_u32(*v),
+            Content::U64(v) => serializer.serialize_u64(*v),
+            Content::U128(v) => serializer.serialize_u128(*v),
+            Content::F32(v) => serializer.serialize_f32(*v),
+            Content::F64(v) => serializer.serialize_f64(*v),
+            Content::Char(v) => serializer.serialize_char(*v),
+            Content::String(v) => serializer.serialize_str(v),
+            Content::Bytes(v) => serializer.serialize_bytes(v),
+            Content::None => serializer.serialize_none(),
+            Content::Some(v) => serializer.serialize_some(&**v),
+            Content::Unit => serializer.serialize_unit(),
+            Content::UnitStruct(name) => serializer.serialize_unit_struct(name),
+            Content::UnitVariant(name, idx, variant) => {
+                serializer.serialize_unit_variant(name, *idx, variant)
+            }
+            Content::NewtypeStruct(name, v) => serializer.serialize_newtype_struct(name, &**v),
+            Content::NewtypeVariant(name, idx, variant, v) => {
+                serializer.serialize_newtype_variant(name, *idx, variant, &**v)
+            }
+            Content::Seq(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Content::Tuple(elements) => {
+                let mut tuple = serializer.serialize_tuple(elements.len())?;
+                for element in elements {
+                    tuple.serialize_element(element)?;
+                }
+                tuple.end()
+            }
+            Content::TupleStruct(name, fields) => {
+                let mut tuple = serializer.serialize_tuple_struct(name, fields.len())?;
+                for field in fields {
+                    tuple.serialize_field(field)?;
+                }
+                tuple.end()
+            }
+            Content::TupleVariant(name, idx, variant, fields) => {
+                let mut tuple =
+                    serializer.serialize_tuple_variant(name, *idx, variant, fields.len())?;
+                for field in fields {
+                    tuple.serialize_field(field)?;
+                }
+                tuple.end()
+            }
+            Content::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Content::Struct(name, fields) => {
+                let mut s = serializer.serialize_struct(name, fields.len())?;
+                for (key, value) in fields {
+                    s.serialize_field(key, value)?;
+                }
+                s.end()
+            }
+            Content::StructVariant(name, idx, variant, fields) => {
+                let mut s =
+                    serializer.serialize_struct_variant(name, *idx, variant, fields.len())?;
+                for (key, value) in fields {
+                    s.serialize_field(key, value)?;
+                }
+                s.end()
+            }
+        }
+    }
+}
+
+/// Error raised by a value being buffered, which is passed on to the real serializer
+#[derive(Debug)]
+pub(crate) struct BufferedSerializerError(pub(crate) String);
+
+impl std::fmt::Display for BufferedSerializerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BufferedSerializerError {}
+
+impl ser::Error for BufferedSerializerError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        BufferedSerializerError(msg.to_string())
+    }
+}
+
+/// A sequence that buffers its elements as `Content`, so they can be written once their number is
+/// known. Used to serialize a diff in a single pass for formats that need the length up front.
+pub(crate) struct BufferedSeq {
+    pub(crate) elements: Vec<Content>,
+    /// Whether the format the elements are written to later is human readable, which some types
+    /// serialize differently for
+    pub(crate) human_readable: bool,
+}
+
+impl ser::SerializeSeq for BufferedSeq {
+    type Ok = Vec<Content>;
+    type Error = BufferedSerializerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let content = value.serialize(ContentSerializer {
+            human_readable: self.human_readable,
+        })?;
+        self.elements.push(content);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.elements)
+    }
+}
+
+/// Serializes a value into `Content`
+struct ContentSerializer {
+    human_readable: bool,
+}
+
+impl ContentSerializer {
+    fn content<T: ?Sized + Serialize>(
+        &self,
+        value: &T,
+    ) -> Result<Content, BufferedSerializerError> {
+        value.serialize(ContentSerializer {
+            human_readable: self.human_readable,
+        })
+    }
+}
+
+impl Serializer for ContentSerializer {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    type SerializeSeq = SerializeElements;
+    type SerializeTuple = SerializeElements;
+    type SerializeTupleStruct = SerializeElements;
+    type SerializeTupleVariant = SerializeElements;
+    type SerializeMap = SerializeEntries;
+    type SerializeStruct = SerializeFields;
+    type SerializeStructVariant = SerializeFields;
+
+    fn serialize_bool(self, v: bool) -> Result<Content, Self::Error> {
+        Ok(Content::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Content, Self::Error> {
+        Ok(Content::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Content, Self::Error> {
+        Ok(Content::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Content, Self::Error> {
+        Ok(Content::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Content, Self::Error> {
+        Ok(Content::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Content, Self::Error> {
+        Ok(Content::I128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Content, Self::Error> {
+        Ok(Content::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Content, Self::Error> {
+        Ok(Content::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Content, Self::Error> {
+        Ok(Content::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Content, Self::Error> {
+        Ok(Content::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Content, Self::Error> {
+        Ok(Content::U128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Content, Self::Error> {
+        Ok(Content::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Content, Self::Error> {
+        Ok(Content::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Content, Self::Error> {
+        Ok(Content::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Content, Self::Error> {
+        Ok(Content::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Content, Self::Error> {
+        Ok(Content::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Content, Self::Error> {
+        Ok(Content::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Content, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::Some(Box::new(self.content(value)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Content, Self::Error> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Content, Self::Error> {
+        Ok(Content::UnitStruct(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Content, Self::Error> {
+        Ok(Content::UnitVariant(name, variant_index, variant))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Content, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::NewtypeStruct(name, Box::new(self.content(value)?)))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Content, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::NewtypeVariant(
+            name,
+            variant_index,
+            variant,
+            Box::new(self.content(value)?),
+        ))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeElements::new(
+            self.human_readable,
+            len.unwrap_or(0),
+            ElementsKind::Seq,
+        ))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(SerializeElements::new(
+            self.human_readable,
+            len,
+            ElementsKind::Tuple,
+        ))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(SerializeElements::new(
+            self.human_readable,
+            len,
+            ElementsKind::TupleStruct(name),
+        ))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeElements::new(
+            self.human_readable,
+            len,
+            ElementsKind::TupleVariant(name, variant_index, variant),
+        ))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeEntries {
+            serializer: self,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(SerializeFields {
+            serializer: self,
+            fields: Vec::with_capacity(len),
+            variant: None,
+            name,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeFields {
+            serializer: self,
+            fields: Vec::with_capacity(len),
+            variant: Some((variant_index, variant)),
+            name,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+}
+
+enum ElementsKind {
+    Seq,
+    Tuple,
+    TupleStruct(&'static str),
+    TupleVariant(&'static str, u32, &'static str),
+}
+
+/// Buffers the elements of a sequence, tuple, tuple struct or tuple variant
+struct SerializeElements {
+    serializer: ContentSerializer,
+    elements: Vec<Content>,
+    kind: ElementsKind,
+}
+
+impl SerializeElements {
+    fn new(human_readable: bool, len: usize, kind: ElementsKind) -> Self {
+        SerializeElements {
+            serializer: ContentSerializer { human_readable },
+            elements: Vec::with_capacity(len),
+            kind,
+        }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BufferedSerializerError> {
+        let content = self.serializer.content(value)?;
+        self.elements.push(content);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Content, BufferedSerializerError> {
+        Ok(match self.kind {
+            ElementsKind::Seq => Content::Seq(self.elements),
+            ElementsKind::Tuple => Content::Tuple(self.elements),
+            ElementsKind::TupleStruct(name) => Content::TupleStruct(name, self.elements),
+            ElementsKind::TupleVariant(name, idx, variant) => {
+                Content::TupleVariant(name, idx, variant, self.elements)
+            }
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeElements {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeElements {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeElements {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeElements {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Buffers the entries of a map
+struct SerializeEntries {
+    serializer: ContentSerializer,
+    entries: Vec<(Content, Content)>,
+    /// Key whose value hasn't been serialized yet
+    key: Option<Content>,
+}
+
+impl ser::SerializeMap for SerializeEntries {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(self.serializer.content(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take().ok_or_else(|| {
+            <BufferedSerializerError as ser::Error>::custom("map value serialized before its key")
+        })?;
+        let value = self.serializer.content(value)?;
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        Ok(Content::Map(self.entries))
+    }
+}
+
+/// Buffers the fields of a struct or struct variant
+struct SerializeFields {
+    serializer: ContentSerializer,
+    fields: Vec<(&'static str, Content)>,
+    name: &'static str,
+    /// Index and name of the variant, if this is a struct variant
+    variant: Option<(u32, &'static str)>,
+}
+
+impl SerializeFields {
+    fn push<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), BufferedSerializerError> {
+        let content = self.serializer.content(value)?;
+        self.fields.push((key, content));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Content, BufferedSerializerError> {
+        Ok(match self.variant {
+            Some((idx, variant)) => Content::StructVariant(self.name, idx, variant, self.fields),
+            None => Content::Struct(self.name, self.fields),
+        })
+    }
+}
+
+impl ser::SerializeStruct for SerializeFields {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeFields {
+    type Ok = Content;
+    type Error = BufferedSerializerError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Content, Self::Error> {
+        self.finish()
+    }
+}
@@ -1,5 +1,11 @@
-use crate::{Apply, Diff, FieldPathMode, SerdeDiff};
-use serde::{de, Serialize, Serializer};
+use crate::{
+    difference::{DiffCommandRef, DiffContext},
+    patch::PatchSerializer,
+    Apply, ApplyFailure, ApplyReport, Diff, DiffPatch, FieldPathMode, JsonMergePatch, JsonPatch,
+    Merge, MergeError, RenderStyle, SerdeDiff, VecDiffMode,
+};
+use serde::{de, ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use serde_value::{DeserializerError, SerializerError};
 use std::cell::Cell;
 
 /// Configures creation of `Apply` and `Diff`
@@ -17,14 +23,29 @@ use std::cell::Cell;
 ///     .with_field_path_mode(FieldPathMode::Index)
 ///     .serializable_diff(&Test { a: 3 }, &Test { a: 5 });
 /// ```
+#[derive(Copy, Clone)]
 pub struct Config {
     field_path_mode: FieldPathMode,
+    invertible: bool,
+    strict_apply: bool,
+    vec_diff_mode: VecDiffMode,
+    fingerprint: bool,
+    buffered: bool,
+    framed: bool,
+    serialized_names: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             field_path_mode: FieldPathMode::Name,
+            invertible: false,
+            strict_apply: false,
+            vec_diff_mode: VecDiffMode::ByIndex,
+            fingerprint: false,
+            buffered: false,
+            framed: false,
+            serialized_names: false,
         }
     }
 }
@@ -41,6 +62,72 @@ impl Config {
         self
     }
 
+    /// Sets whether diffs also record the values they replace and remove. An invertible diff is
+    /// larger, but a `DiffPatch` created from it can be inverted to undo the change.
+    pub fn with_invertible(mut self, invertible: bool) -> Self {
+        self.invertible = invertible;
+        self
+    }
+
+    /// Sets whether applying fails when a diff refers to a part of the target that doesn't exist
+    /// or doesn't match, such as a missing map key or an index past the end of a collection. By
+    /// default such commands are skipped. A strict apply that fails may have already applied
+    /// some of the changes.
+    pub fn with_strict_apply(mut self, strict: bool) -> Self {
+        self.strict_apply = strict;
+        self
+    }
+
+    /// Sets how Vecs are compared. `VecDiffMode::EditScript` produces smaller diffs when elements
+    /// are inserted or removed, but uses commands that older versions of this crate can't apply.
+    /// Fields marked `#[serde_diff(edit_script)]` always use an edit script.
+    pub fn with_vec_diff_mode(mut self, mode: VecDiffMode) -> Self {
+        self.vec_diff_mode = mode;
+        self
+    }
+
+    /// Sets whether diffs start with a fingerprint of the diffed type's structure, which is
+    /// derived from its field and variant names, ids and types. Applying such a diff to a type
+    /// with a different fingerprint fails with `ApplyError::FingerprintMismatch` before anything
+    /// is changed, instead of silently skipping or misapplying commands. Diffs without a
+    /// fingerprint are applied as before.
+    pub fn with_fingerprint(mut self, fingerprint: bool) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Sets whether serializing a `Diff` into a format that needs the length of a sequence up
+    /// front, such as bincode, buffers the diff commands. By default the values are diffed twice,
+    /// once to count the commands and once to write them. Buffering diffs them once, at the cost of
+    /// holding a copy of the commands in memory until they are written. Human-readable formats
+    /// are always written in a single pass without buffering.
+    pub fn with_buffered_serialization(mut self, buffered: bool) -> Self {
+        self.buffered = buffered;
+        self
+    }
+
+    /// Sets whether a serialized `Diff` frames its values: each value is written as a byte string
+    /// holding a self-describing encoding of it. This lets the receiver skip the changes to parts
+    /// of a value it doesn't have in formats that can't skip data of an unknown type, such as
+    /// bincode. Without framing, applying a diff with such changes fails in these formats.
+    /// Framed diffs are larger, and are applied like any other diff, but can't be read into a
+    /// `DiffPatch`. Framed values are always encoded as if the format were human-readable, so
+    /// types that serialize differently depending on `is_human_readable`, such as `IpAddr`, are
+    /// framed in their human-readable form even in binary formats. The value is read back in the
+    /// same form, so this only affects the size of the diff.
+    pub fn with_framed_values(mut self, framed: bool) -> Self {
+        self.framed = framed;
+        self
+    }
+
+    /// Sets whether fields and variants renamed with `#[serde_diff(rename)]` are written with the
+    /// name they are serialized with, so the diff addresses the serialized value. Used to compute
+    /// JSON Patches and JSON Merge Patches.
+    pub(crate) fn with_serialized_names(mut self, serialized_names: bool) -> Self {
+        self.serialized_names = serialized_names;
+        self
+    }
+
     /// Create a serializable Diff, which when serialized will write the differences between the old
     /// and new value into the serializer in the form of a sequence of diff commands
     pub fn serializable_diff<'a, 'b, T: SerdeDiff + 'a + 'b>(
@@ -52,6 +139,11 @@ impl Config {
             old,
             new,
             field_path_mode: self.field_path_mode,
+            invertible: self.invertible,
+            vec_diff_mode: self.vec_diff_mode,
+            fingerprint: self.fingerprint,
+            buffered: self.buffered,
+            framed: self.framed,
             has_changes: Cell::new(false),
         }
     }
@@ -67,10 +159,69 @@ impl Config {
         self.serializable_diff(old, new).serialize(serializer)
     }
 
+    /// Computes the differences between the old and new value into an owned `DiffPatch`, which can
+    /// be stored, applied and serialized later
+    pub fn diff_patch<T: SerdeDiff>(self, old: &T, new: &T) -> Result<DiffPatch, SerializerError> {
+        let mut seq = PatchSerializer {
+            commands: Vec::new(),
+        };
+        if self.fingerprint {
+            seq.serialize_element(&DiffCommandRef::<()>::Fingerprint(T::fingerprint()))?;
+        }
+        {
+            let mut ctx = DiffContext::new(
+                &mut seq,
+                self.field_path_mode,
+                self.invertible,
+                self.vec_diff_mode,
+            )
+            .with_serialized_names(self.serialized_names);
+            old.diff(&mut ctx, new)?;
+        }
+        seq.end()
+    }
+
+    /// Renders the differences between the old and new value as human-readable text, with one
+    /// line per change. See `DiffPatch::render`.
+    pub fn render_diff<T: SerdeDiff>(
+        self,
+        old: &T,
+        new: &T,
+        style: RenderStyle,
+    ) -> Result<String, SerializerError> {
+        self.with_invertible(true)
+            .diff_patch(old, new)?
+            .render(style)
+            .map_err(<SerializerError as serde::ser::Error>::custom)
+    }
+
+    /// Computes the differences between the old and new value as RFC 6902 JSON Patch operations
+    pub fn json_patch<T: SerdeDiff + Serialize>(
+        self,
+        old: &T,
+        new: &T,
+    ) -> Result<JsonPatch, SerializerError> {
+        JsonPatch::compute(self, old, new)
+    }
+
+    /// Computes the differences between the old and new value as an RFC 7386 JSON Merge Patch
+    pub fn json_merge_patch<T: SerdeDiff + Serialize>(
+        self,
+        old: &T,
+        new: &T,
+    ) -> Result<JsonMergePatch, SerializerError> {
+        JsonMergePatch::compute(self, old, new)
+    }
+
     /// Create a deserializable Apply, where the given target will be changed when the resulting
     /// Apply struct is deserialized
     pub fn deserializable_apply<'a, T: SerdeDiff>(self, target: &'a mut T) -> Apply<'a, T> {
-        Apply { target }
+        Apply {
+            target,
+            strict: self.strict_apply,
+            report: None,
+            error: None,
+        }
     }
 
     /// Applies a sequence of diff commands to the target, as read by the deserializer
@@ -84,4 +235,107 @@ impl Config {
     {
         deserializer.deserialize_seq(self.deserializable_apply(target))
     }
+
+    /// Applies a sequence of diff commands to the target, as read by the deserializer. Unlike
+    /// `apply`, a diff that can't be applied fails with the `ApplyError` describing why, rather
+    /// than with a deserializer error holding its message.
+    pub fn try_apply<'de, D, T: SerdeDiff>(
+        self,
+        deserializer: D,
+        target: &mut T,
+    ) -> Result<(), ApplyFailure<<D as de::Deserializer<'de>>::Error>>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut error = None;
+        deserializer
+            .deserialize_seq(Apply {
+                target,
+                strict: self.strict_apply,
+                report: None,
+                error: Some(&mut error),
+            })
+            .map_err(|format_error| match error {
+                Some(error) => ApplyFailure::Apply(error),
+                None => ApplyFailure::Format(format_error),
+            })
+    }
+
+    /// Applies a sequence of diff commands to the target, as read by the deserializer, and
+    /// reports which parts of the target were changed
+    pub fn apply_with_report<'de, D, T: SerdeDiff>(
+        self,
+        deserializer: D,
+        target: &mut T,
+    ) -> Result<ApplyReport, <D as de::Deserializer<'de>>::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut report = ApplyReport::default();
+        deserializer.deserialize_seq(Apply {
+            target,
+            strict: self.strict_apply,
+            report: Some(&mut report),
+            error: None,
+        })?;
+        Ok(report)
+    }
+
+    /// Applies the diff commands held by a `DiffPatch` to the target
+    pub fn apply_patch<T: SerdeDiff>(
+        self,
+        patch: &DiffPatch,
+        target: &mut T,
+    ) -> Result<(), DeserializerError> {
+        self.apply(patch.deserializer(), target)
+    }
+
+    /// Applies the diff commands held by a `DiffPatch` to the target and reports which parts of
+    /// the target were changed
+    pub fn apply_patch_with_report<T: SerdeDiff>(
+        self,
+        patch: &DiffPatch,
+        target: &mut T,
+    ) -> Result<ApplyReport, DeserializerError> {
+        self.apply_with_report(patch.deserializer(), target)
+    }
+
+    /// Applies RFC 6902 JSON Patch operations to the target. The operations are applied to the
+    /// target's serialized representation and the resulting differences are then applied to the
+    /// target, so only the parts that changed are written.
+    pub fn apply_json_patch<T>(
+        self,
+        patch: &JsonPatch,
+        target: &mut T,
+    ) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        patch.apply_to(self, target)
+    }
+
+    /// Merges an RFC 7386 JSON Merge Patch into the target. Like `apply_json_patch`, only the
+    /// parts of the target that the merge changes are written.
+    pub fn apply_json_merge_patch<T>(
+        self,
+        patch: &JsonMergePatch,
+        target: &mut T,
+    ) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        patch.apply_to(self, target)
+    }
+
+    /// Performs a three-way merge, combining the changes from base to ours and from base to theirs.
+    /// Changes that touch overlapping parts of the value are reported as conflicts, in which case
+    /// our changes are kept.
+    pub fn merge<T: SerdeDiff + Clone>(
+        self,
+        base: &T,
+        ours: &T,
+        theirs: &T,
+    ) -> Result<Merge<T>, MergeError> {
+        Merge::compute(self, base, ours, theirs)
+    }
 }
@@ -5,14 +5,14 @@ pub(crate) struct CountingSerializer {
     pub(crate) num_elements: usize,
 }
 
-/// This is a dummy error type for CountingSerializer. Currently we don't expect the serializer
-/// to fail, so it's empty for now
+/// Error type for CountingSerializer. The serializer itself never fails, but the values it
+/// counts can, and their message is kept so the real serializer can report it
 #[derive(Debug)]
-pub(crate) struct CountingSerializerError;
+pub(crate) struct CountingSerializerError(String);
 
 impl std::fmt::Display for CountingSerializerError {
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unimplemented!()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 impl std::error::Error for CountingSerializerError {
@@ -27,15 +27,15 @@ impl std::error::Error for CountingSerializerError {
     }
 }
 impl ser::Error for CountingSerializerError {
-    fn custom<T>(_msg: T) -> Self
+    fn custom<T>(msg: T) -> Self
     where
         T: std::fmt::Display,
     {
-        CountingSerializerError
+        CountingSerializerError(msg.to_string())
     }
 }
 
-impl<'a> ser::Serializer for &'a mut CountingSerializer {
+impl ser::Serializer for &mut CountingSerializer {
     type Ok = ();
     type Error = CountingSerializerError;
 
@@ -200,7 +200,7 @@ impl<'a> ser::Serializer for &'a mut CountingSerializer {
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut CountingSerializer {
+impl ser::SerializeSeq for &mut CountingSerializer {
     type Ok = ();
     type Error = CountingSerializerError;
 
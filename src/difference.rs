@@ -1,9 +1,16 @@
 use crate::{
-    apply::ApplyContext, counting_serializer::CountingSerializer, Config, ElementStackEntry,
-    FieldPathMode, SerdeDiff,
+    apply::ApplyContext,
+    buffered_serializer::BufferedSeq,
+    counting_serializer::CountingSerializer,
+    edit_script::{diff_edit_script, edit_script, is_unchanged},
+    framing::{FramedInPlace, FramedSeq, FramedValue, Pair, SkipFramed},
+    ChangeKind, Config, ElementStackEntry, FieldPathMode, PathElement, SerdeDiff, VecDiffMode,
 };
 use serde::{de, ser::SerializeSeq, Deserialize, Serialize, Serializer};
-use std::{borrow::Cow, cell::Cell};
+use std::{
+    borrow::{Borrow, Cow},
+    cell::Cell,
+};
 
 /// Used during a diff operation for transient data used during the diff
 #[doc(hidden)]
@@ -19,15 +26,22 @@ pub struct DiffContext<'a, S: SerializeSeq> {
     /// parent_element_stack contains a reference to the parent DiffContext's element_stack.
     parent_element_stack: Option<&'a mut Option<Vec<ElementStackEntry<'a, S>>>>,
     /// Contains the minimum index in the element stack at which this context has pushed elements.
-    /// When the context is dropped, we have to make sure we have dropped all elements
-    /// >= index before we can pass the element stack back to the parent.
+    /// When the context is dropped, we have to make sure we have dropped all elements at or
+    /// above this index before we can pass the element stack back to the parent.
     /// This is to ensure the safety invariant that a sub-context's (a `reborrow`ed context)
     /// pushed elements cannot live longer than the sub-context itself.
     element_stack_start: usize,
     /// Mode for serializing field paths
     field_path_mode: FieldPathMode,
+    /// If true, the values that are replaced or removed are written as well
+    invertible: bool,
+    /// How Vecs are compared
+    vec_diff_mode: VecDiffMode,
     /// Set to true if any change is detected
     has_changes: bool,
+    /// If true, fields and variants renamed with `#[serde_diff(rename)]` are written with the name
+    /// they are serialized with
+    serialized_names: bool,
 }
 
 impl<'a, S: SerializeSeq> Drop for DiffContext<'a, S> {
@@ -45,11 +59,55 @@ impl<'a, S: SerializeSeq> Drop for DiffContext<'a, S> {
 
 #[doc(hidden)]
 impl<'a, S: SerializeSeq> DiffContext<'a, S> {
+    /// Creates a top-level context that writes diff commands into the given sequence
+    pub(crate) fn new(
+        serializer: &'a mut S,
+        field_path_mode: FieldPathMode,
+        invertible: bool,
+        vec_diff_mode: VecDiffMode,
+    ) -> Self {
+        DiffContext {
+            element_stack_start: 0,
+            element_stack: Some(Vec::new()),
+            serializer,
+            implicit_exit_written: false,
+            parent_element_stack: None,
+            field_path_mode,
+            invertible,
+            vec_diff_mode,
+            has_changes: false,
+            serialized_names: false,
+        }
+    }
+
+    /// Writes fields and variants renamed with `#[serde_diff(rename)]` with the name they are
+    /// serialized with, for diffs that are turned into patches of the serialized value
+    pub(crate) fn with_serialized_names(mut self, serialized_names: bool) -> Self {
+        self.serialized_names = serialized_names;
+        self
+    }
+
     /// Mode for serializing field paths
     pub fn field_path_mode(&self) -> FieldPathMode {
         self.field_path_mode
     }
 
+    /// True if replaced and removed values should be written so the diff can be inverted
+    pub fn is_invertible(&self) -> bool {
+        self.invertible
+    }
+
+    /// How Vecs are compared
+    pub fn vec_diff_mode(&self) -> VecDiffMode {
+        self.vec_diff_mode
+    }
+
+    /// Sets how Vecs are compared from now on, returning the previous mode so it can be restored.
+    /// Used by `#[serde_diff(edit_script)]` fields.
+    pub fn set_vec_diff_mode(&mut self, mode: VecDiffMode) -> VecDiffMode {
+        std::mem::replace(&mut self.vec_diff_mode, mode)
+    }
+
     /// True if a change operation has been written
     pub fn has_changes(&self) -> bool {
         self.has_changes
@@ -67,6 +125,17 @@ impl<'a, S: SerializeSeq> DiffContext<'a, S> {
             )));
     }
 
+    /// Chooses the name of a field or variant renamed with `#[serde_diff(rename)]`. Diffs use the
+    /// renamed name, except those that JSON Patches are computed from, which use the name the
+    /// field or variant is serialized with.
+    pub fn renamed(&self, name: &'static str, serialized_name: &'static str) -> &'static str {
+        if self.serialized_names {
+            serialized_name
+        } else {
+            name
+        }
+    }
+
     pub fn push_variant(&mut self, variant_name: &'static str) {
         self.element_stack
             .as_mut()
@@ -97,6 +166,50 @@ impl<'a, S: SerializeSeq> DiffContext<'a, S> {
             ));
     }
 
+    /// Called when we visit a field that has both a name and an index, such as a field of a
+    /// derived struct. Only `FieldPathMode::Index` writes the index.
+    pub fn push_field_with_index(&mut self, field_name: &'static str, field_idx: u16) {
+        match self.field_path_mode {
+            FieldPathMode::Name | FieldPathMode::Tag => self.push_field(field_name),
+            FieldPathMode::Index => self.push_field_index(field_idx),
+        }
+    }
+
+    /// Called when we visit a field tagged with `#[serde_diff(id = N)]`. The tag is written in
+    /// place of the name unless the `FieldPathMode` is `Name`.
+    pub fn push_tagged_field(&mut self, field_name: &'static str, field_tag: u16) {
+        match self.field_path_mode {
+            FieldPathMode::Name => self.push_field(field_name),
+            FieldPathMode::Index | FieldPathMode::Tag => self.push_field_index(field_tag),
+        }
+    }
+
+    /// Called when we visit an enum variant. Only `FieldPathMode::Index` writes the index.
+    pub fn push_variant_with_index(&mut self, variant_name: &'static str, variant_idx: u32) {
+        match self.field_path_mode {
+            FieldPathMode::Name | FieldPathMode::Tag => self.push_variant(variant_name),
+            FieldPathMode::Index => self.push_variant_index(variant_idx),
+        }
+    }
+
+    /// Called when we visit an enum variant tagged with `#[serde_diff(id = N)]`. The tag is
+    /// written in place of the name unless the `FieldPathMode` is `Name`.
+    pub fn push_tagged_variant(&mut self, variant_name: &'static str, variant_tag: u32) {
+        match self.field_path_mode {
+            FieldPathMode::Name => self.push_variant(variant_name),
+            FieldPathMode::Index | FieldPathMode::Tag => self.push_variant_index(variant_tag),
+        }
+    }
+
+    fn push_variant_index(&mut self, variant_idx: u32) {
+        self.element_stack
+            .as_mut()
+            .unwrap()
+            .push(ElementStackEntry::PathElement(
+                DiffPathElementValue::EnumVariantIndex(variant_idx),
+            ));
+    }
+
     /// Called when we visit an element within an indexed collection
     pub fn push_collection_index(&mut self, idx: usize) {
         self.element_stack
@@ -148,6 +261,16 @@ impl<'a, S: SerializeSeq> DiffContext<'a, S> {
         self.save_command(&DiffCommandRef::Value(value), true, true)
     }
 
+    /// Stores a new value for an element that has previously been pushed using push_field or
+    /// similar. When the diff is invertible, the value being replaced is stored as well.
+    pub fn save_change<T: Serialize>(&mut self, old: &T, new: &T) -> Result<(), S::Error> {
+        if self.invertible {
+            self.save_command(&DiffCommandRef::Replace(old, new), true, true)
+        } else {
+            self.save_value(new)
+        }
+    }
+
     /// Stores an arbitrary DiffCommand to be handled by the type.
     /// Any custom sequence of DiffCommands must be followed by Exit.
     pub fn save_command<'b, T: Serialize>(
@@ -164,7 +287,7 @@ impl<'a, S: SerializeSeq> DiffContext<'a, S> {
                     ElementStackEntry::PathElement(element) => self
                         .serializer
                         .serialize_element(&DiffCommandRef::<()>::Enter(element))?,
-                    ElementStackEntry::Closure(closure) => (closure)(&mut self.serializer)?,
+                    ElementStackEntry::Closure(closure) => (closure)(self.serializer)?,
                 };
             }
             self.element_stack_start = 0;
@@ -208,7 +331,10 @@ impl<'a, S: SerializeSeq> DiffContext<'a, S> {
             serializer: &mut *self.serializer,
             implicit_exit_written: self.implicit_exit_written,
             field_path_mode: self.field_path_mode,
+            invertible: self.invertible,
+            vec_diff_mode: self.vec_diff_mode,
             has_changes: false,
+            serialized_names: self.serialized_names,
         }
     }
 }
@@ -232,6 +358,11 @@ pub struct Diff<'a, 'b, T> {
     pub(crate) old: &'a T,
     pub(crate) new: &'b T,
     pub(crate) field_path_mode: FieldPathMode,
+    pub(crate) invertible: bool,
+    pub(crate) vec_diff_mode: VecDiffMode,
+    pub(crate) fingerprint: bool,
+    pub(crate) buffered: bool,
+    pub(crate) framed: bool,
 
     // This is a cell to provide interior mutability
     pub(crate) has_changes: Cell<bool>,
@@ -256,6 +387,37 @@ impl<'a, 'b, T: SerdeDiff + 'a + 'b> Diff<'a, 'b, T> {
     }
 }
 
+impl<'a, 'b, T: SerdeDiff> Diff<'a, 'b, T> {
+    /// Writes the diff commands into the sequence, returning true if a change was detected
+    fn write_commands<S: SerializeSeq>(&self, seq: &mut S) -> Result<bool, S::Error> {
+        if self.fingerprint {
+            // Written first, so a mismatch is found before any change is applied
+            seq.serialize_element(&DiffCommandRef::<()>::Fingerprint(T::fingerprint()))?;
+        }
+        if self.framed {
+            seq.serialize_element(&DiffCommandRef::<()>::Framed)?;
+            self.diff_into(&mut FramedSeq { seq })
+        } else {
+            self.diff_into(seq)
+        }
+    }
+
+    /// Diffs the values, writing the diff commands into the sequence
+    fn diff_into<S: SerializeSeq>(&self, seq: &mut S) -> Result<bool, S::Error> {
+        let mut ctx = DiffContext::new(
+            seq,
+            self.field_path_mode,
+            self.invertible,
+            self.vec_diff_mode,
+        );
+
+        // Do the actual comparison, writing diff commands (see DiffCommandRef, DiffCommandValue)
+        // into the sequence
+        self.old.diff(&mut ctx, self.new)?;
+        Ok(ctx.has_changes)
+    }
+}
+
 impl<'a, 'b, T: SerdeDiff> Serialize for Diff<'a, 'b, T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -267,21 +429,30 @@ impl<'a, 'b, T: SerdeDiff> Serialize for Diff<'a, 'b, T> {
         // This may only be needed for certain serializers like bincode,
         // so we assume that it's only required if the serializer format is not human readable.
         let num_elements = if !serializer.is_human_readable() {
-            let mut serializer = CountingSerializer { num_elements: 0 };
-            let mut seq = serializer.serialize_seq(None).unwrap();
-            {
-                let mut ctx = DiffContext {
-                    element_stack_start: 0,
-                    element_stack: Some(Vec::new()),
-                    serializer: &mut seq,
-                    implicit_exit_written: false,
-                    parent_element_stack: None,
-                    field_path_mode: self.field_path_mode,
-                    has_changes: false,
+            if self.buffered {
+                // Diff once into a buffer instead of diffing again just to count the commands
+                let mut buffer = BufferedSeq {
+                    elements: Vec::new(),
+                    human_readable: false,
                 };
-                self.old.diff(&mut ctx, &self.new).unwrap();
+                let has_changes = self
+                    .write_commands(&mut buffer)
+                    .map_err(<S::Error as serde::ser::Error>::custom)?;
+                self.has_changes.set(has_changes);
+                let commands = buffer.elements;
+                let mut seq = serializer.serialize_seq(Some(commands.len()))?;
+                for command in &commands {
+                    seq.serialize_element(command)?;
+                }
+                return seq.end();
             }
-            seq.end().unwrap();
+            let mut serializer = CountingSerializer { num_elements: 0 };
+            let mut seq = serializer
+                .serialize_seq(None)
+                .map_err(<S::Error as serde::ser::Error>::custom)?;
+            self.write_commands(&mut seq)
+                .map_err(<S::Error as serde::ser::Error>::custom)?;
+            seq.end().map_err(<S::Error as serde::ser::Error>::custom)?;
             Some(serializer.num_elements)
         } else {
             None
@@ -289,25 +460,10 @@ impl<'a, 'b, T: SerdeDiff> Serialize for Diff<'a, 'b, T> {
 
         // Setup the context, starting a sequence on the serializer
         let mut seq = serializer.serialize_seq(num_elements)?;
-        {
-            let mut ctx = DiffContext {
-                element_stack_start: 0,
-                element_stack: Some(Vec::new()),
-                serializer: &mut seq,
-                implicit_exit_written: false,
-                parent_element_stack: None,
-                field_path_mode: self.field_path_mode,
-                has_changes: false,
-            };
-
-            // Do the actual comparison, writing diff commands (see DiffCommandRef, DiffCommandValue)
-            // into the sequence
-            self.old.diff(&mut ctx, &self.new)?;
-            self.has_changes.set(ctx.has_changes);
-        }
+        self.has_changes.set(self.write_commands(&mut seq)?);
 
         // End the sequence on the serializer
-        Ok(seq.end()?)
+        seq.end()
     }
 }
 
@@ -317,6 +473,8 @@ pub(crate) struct DeserWrapper<'a, T> {
 
 pub(crate) struct DiffCommandDeserWrapper<'a, T> {
     pub(crate) val_wrapper: DeserWrapper<'a, T>,
+    /// If true, values are read from their framed form
+    pub(crate) framed: bool,
 }
 
 // This monstrosity is based off the output of the derive macro for DiffCommand.
@@ -332,11 +490,20 @@ pub(crate) enum DiffCommandField {
     EnterKey,
     RemoveKey,
     Exit,
+    Replace,
+    RemoveValues,
+    RemoveEntry,
+    InsertAt,
+    RemoveAt,
+    RemoveValueAt,
+    Reorder,
+    Fingerprint,
+    Framed,
 }
 
 pub(crate) struct DiffCommandFieldVisitor;
 
-const VARIANTS: &'static [&'static str] = &[
+const VARIANTS: &[&str] = &[
     "Enter",
     "Value",
     "Remove",
@@ -344,6 +511,15 @@ const VARIANTS: &'static [&'static str] = &[
     "EnterKey",
     "RemoveKey",
     "Exit",
+    "Replace",
+    "RemoveValues",
+    "RemoveEntry",
+    "InsertAt",
+    "RemoveAt",
+    "RemoveValueAt",
+    "Reorder",
+    "Fingerprint",
+    "Framed",
 ];
 
 impl<'de> de::Visitor<'de> for DiffCommandFieldVisitor {
@@ -363,9 +539,18 @@ impl<'de> de::Visitor<'de> for DiffCommandFieldVisitor {
             4u64 => Ok(DiffCommandField::EnterKey),
             5u64 => Ok(DiffCommandField::RemoveKey),
             6u64 => Ok(DiffCommandField::Exit),
+            7u64 => Ok(DiffCommandField::Replace),
+            8u64 => Ok(DiffCommandField::RemoveValues),
+            9u64 => Ok(DiffCommandField::RemoveEntry),
+            10u64 => Ok(DiffCommandField::InsertAt),
+            11u64 => Ok(DiffCommandField::RemoveAt),
+            12u64 => Ok(DiffCommandField::RemoveValueAt),
+            13u64 => Ok(DiffCommandField::Reorder),
+            14u64 => Ok(DiffCommandField::Fingerprint),
+            15u64 => Ok(DiffCommandField::Framed),
             _ => Err(de::Error::invalid_value(
                 de::Unexpected::Unsigned(value),
-                &"variant index 0 <= i < 7",
+                &"variant index 0 <= i < 16",
             )),
         }
     }
@@ -381,6 +566,15 @@ impl<'de> de::Visitor<'de> for DiffCommandFieldVisitor {
             "EnterKey" => Ok(DiffCommandField::EnterKey),
             "RemoveKey" => Ok(DiffCommandField::RemoveKey),
             "Exit" => Ok(DiffCommandField::Exit),
+            "Replace" => Ok(DiffCommandField::Replace),
+            "RemoveValues" => Ok(DiffCommandField::RemoveValues),
+            "RemoveEntry" => Ok(DiffCommandField::RemoveEntry),
+            "InsertAt" => Ok(DiffCommandField::InsertAt),
+            "RemoveAt" => Ok(DiffCommandField::RemoveAt),
+            "RemoveValueAt" => Ok(DiffCommandField::RemoveValueAt),
+            "Reorder" => Ok(DiffCommandField::Reorder),
+            "Fingerprint" => Ok(DiffCommandField::Fingerprint),
+            "Framed" => Ok(DiffCommandField::Framed),
             _ => Err(de::Error::unknown_variant(value, VARIANTS)),
         }
     }
@@ -396,6 +590,15 @@ impl<'de> de::Visitor<'de> for DiffCommandFieldVisitor {
             b"EnterKey" => Ok(DiffCommandField::EnterKey),
             b"RemoveKey" => Ok(DiffCommandField::RemoveKey),
             b"Exit" => Ok(DiffCommandField::Exit),
+            b"Replace" => Ok(DiffCommandField::Replace),
+            b"RemoveValues" => Ok(DiffCommandField::RemoveValues),
+            b"RemoveEntry" => Ok(DiffCommandField::RemoveEntry),
+            b"InsertAt" => Ok(DiffCommandField::InsertAt),
+            b"RemoveAt" => Ok(DiffCommandField::RemoveAt),
+            b"RemoveValueAt" => Ok(DiffCommandField::RemoveValueAt),
+            b"Reorder" => Ok(DiffCommandField::Reorder),
+            b"Fingerprint" => Ok(DiffCommandField::Fingerprint),
+            b"Framed" => Ok(DiffCommandField::Framed),
             _ => {
                 let value = &String::from_utf8_lossy(value);
                 Err(de::Error::unknown_variant(value, VARIANTS))
@@ -428,6 +631,7 @@ where
             T: de::Deserialize<'de>,
         {
             seed: DeserWrapper<'a, T>,
+            framed: bool,
             lifetime: std::marker::PhantomData<&'de ()>,
         }
         impl<'de, 'a, T> de::Visitor<'de> for Visitor<'de, 'a, T>
@@ -451,9 +655,29 @@ where
                     (DiffCommandField::Value, variant)
                     | (DiffCommandField::AddKey, variant)
                     | (DiffCommandField::EnterKey, variant)
-                    | (DiffCommandField::RemoveKey, variant) => {
-                        de::VariantAccess::newtype_variant_seed::<DeserWrapper<T>>(
-                            variant, self.seed,
+                    | (DiffCommandField::RemoveKey, variant)
+                    | (DiffCommandField::RemoveEntry, variant) => {
+                        if self.framed {
+                            de::VariantAccess::newtype_variant_seed(
+                                variant,
+                                FramedInPlace(self.seed.val),
+                            )?;
+                        } else {
+                            de::VariantAccess::newtype_variant_seed::<DeserWrapper<T>>(
+                                variant, self.seed,
+                            )?;
+                        }
+                        Ok(DiffCommandValue::DeserializedValue)
+                    }
+                    (DiffCommandField::Replace, variant) => {
+                        de::VariantAccess::tuple_variant(
+                            variant,
+                            2,
+                            ReplaceVisitor {
+                                seed: self.seed,
+                                framed: self.framed,
+                                lifetime: std::marker::PhantomData,
+                            },
                         )?;
                         Ok(DiffCommandValue::DeserializedValue)
                     }
@@ -461,10 +685,42 @@ where
                         let num_elements = de::VariantAccess::newtype_variant::<usize>(variant)?;
                         Ok(DiffCommandValue::Remove(num_elements))
                     }
+                    (DiffCommandField::InsertAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::InsertAt(idx))
+                    }
+                    (DiffCommandField::RemoveAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveAt(idx))
+                    }
+                    (DiffCommandField::RemoveValueAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveValueAt(idx))
+                    }
+                    (DiffCommandField::Reorder, variant) => {
+                        let order = de::VariantAccess::newtype_variant::<Vec<usize>>(variant)?;
+                        Ok(DiffCommandValue::Reorder(order))
+                    }
+                    (DiffCommandField::Fingerprint, variant) => {
+                        let fingerprint = de::VariantAccess::newtype_variant::<u64>(variant)?;
+                        Ok(DiffCommandValue::Fingerprint(fingerprint))
+                    }
+                    (DiffCommandField::RemoveValues, variant) => {
+                        let values = if self.framed {
+                            de::VariantAccess::newtype_variant::<FramedValue<Vec<T>>>(variant)?.0
+                        } else {
+                            de::VariantAccess::newtype_variant::<Vec<T>>(variant)?
+                        };
+                        Ok(DiffCommandValue::RemoveValues(values))
+                    }
                     (DiffCommandField::Exit, variant) => {
                         de::VariantAccess::unit_variant(variant)?;
                         Ok(DiffCommandValue::Exit)
                     }
+                    (DiffCommandField::Framed, variant) => {
+                        de::VariantAccess::unit_variant(variant)?;
+                        Ok(DiffCommandValue::Framed)
+                    }
                 }
             }
         }
@@ -474,6 +730,7 @@ where
             VARIANTS,
             Visitor {
                 seed: self.val_wrapper,
+                framed: self.framed,
                 lifetime: std::marker::PhantomData,
             },
         )
@@ -491,8 +748,43 @@ impl<'a, 'de, T: Deserialize<'de>> de::DeserializeSeed<'de> for DeserWrapper<'a,
     }
 }
 
+// Deserializes the (old, new) pair of a Replace command, discarding the old value and
+// deserializing the new one in place
+struct ReplaceVisitor<'de, 'a, T> {
+    seed: DeserWrapper<'a, T>,
+    framed: bool,
+    lifetime: std::marker::PhantomData<&'de ()>,
+}
+
+impl<'de, 'a, T: Deserialize<'de>> de::Visitor<'de> for ReplaceVisitor<'de, 'a, T> {
+    type Value = ();
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Formatter::write_str(formatter, "an old and a new value")
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        if self.framed {
+            seq.next_element::<SkipFramed>()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            seq.next_element_seed(FramedInPlace(self.seed.val))?
+                .ok_or_else(|| de::Error::invalid_length(1, &"an old and a new value"))?;
+        } else {
+            seq.next_element::<T>()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            seq.next_element_seed(self.seed)?
+                .ok_or_else(|| de::Error::invalid_length(1, &"an old and a new value"))?;
+        }
+        Ok(())
+    }
+}
+
 // Deserializes a DiffCommand but ignores values
-pub(crate) struct DiffCommandIgnoreValue;
+pub(crate) struct DiffCommandIgnoreValue {
+    /// If true, values are skipped in their framed form, which works in every format
+    pub(crate) framed: bool,
+}
 
 impl<'de> de::DeserializeSeed<'de> for DiffCommandIgnoreValue {
     type Value = DiffCommandValue<'de, ()>;
@@ -501,8 +793,19 @@ impl<'de> de::DeserializeSeed<'de> for DiffCommandIgnoreValue {
         D: de::Deserializer<'de>,
     {
         struct Visitor<'de> {
+            framed: bool,
             lifetime: std::marker::PhantomData<&'de ()>,
         }
+        impl<'de> Visitor<'de> {
+            fn skip_value<A: de::VariantAccess<'de>>(&self, variant: A) -> Result<(), A::Error> {
+                if self.framed {
+                    de::VariantAccess::newtype_variant::<SkipFramed>(variant)?;
+                } else {
+                    de::VariantAccess::newtype_variant::<de::IgnoredAny>(variant)?;
+                }
+                Ok(())
+            }
+        }
         impl<'de> de::Visitor<'de> for Visitor<'de> {
             type Value = DiffCommandValue<'de, ()>;
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -518,21 +821,178 @@ impl<'de> de::DeserializeSeed<'de> for DiffCommandIgnoreValue {
                             de::VariantAccess::newtype_variant::<DiffPathElementValue>(variant)?;
                         Ok(DiffCommandValue::Enter(enter))
                     }
-                    (DiffCommandField::Value, variant)
-                    | (DiffCommandField::AddKey, variant)
-                    | (DiffCommandField::EnterKey, variant)
-                    | (DiffCommandField::RemoveKey, variant) => {
-                        de::VariantAccess::newtype_variant::<de::IgnoredAny>(variant)?;
+                    (DiffCommandField::Value, variant) => {
+                        self.skip_value(variant)?;
                         Ok(DiffCommandValue::Value(()))
                     }
+                    (DiffCommandField::AddKey, variant) => {
+                        self.skip_value(variant)?;
+                        Ok(DiffCommandValue::AddKey(()))
+                    }
+                    (DiffCommandField::EnterKey, variant) => {
+                        self.skip_value(variant)?;
+                        Ok(DiffCommandValue::EnterKey(()))
+                    }
+                    (DiffCommandField::RemoveKey, variant) => {
+                        self.skip_value(variant)?;
+                        Ok(DiffCommandValue::RemoveKey(()))
+                    }
+                    (DiffCommandField::RemoveEntry, variant) => {
+                        self.skip_value(variant)?;
+                        Ok(DiffCommandValue::RemoveEntry(()))
+                    }
+                    (DiffCommandField::Replace, variant) => {
+                        if self.framed {
+                            de::VariantAccess::tuple_variant(
+                                variant,
+                                2,
+                                Pair::<SkipFramed>::default(),
+                            )?;
+                        } else {
+                            de::VariantAccess::tuple_variant(variant, 2, de::IgnoredAny)?;
+                        }
+                        Ok(DiffCommandValue::Replace((), ()))
+                    }
                     (DiffCommandField::Remove, variant) => {
                         let num_elements = de::VariantAccess::newtype_variant::<usize>(variant)?;
                         Ok(DiffCommandValue::Remove(num_elements))
                     }
+                    (DiffCommandField::InsertAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::InsertAt(idx))
+                    }
+                    (DiffCommandField::RemoveAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveAt(idx))
+                    }
+                    (DiffCommandField::RemoveValueAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveValueAt(idx))
+                    }
+                    (DiffCommandField::Reorder, variant) => {
+                        let order = de::VariantAccess::newtype_variant::<Vec<usize>>(variant)?;
+                        Ok(DiffCommandValue::Reorder(order))
+                    }
+                    (DiffCommandField::Fingerprint, variant) => {
+                        let fingerprint = de::VariantAccess::newtype_variant::<u64>(variant)?;
+                        Ok(DiffCommandValue::Fingerprint(fingerprint))
+                    }
+                    (DiffCommandField::RemoveValues, variant) => {
+                        self.skip_value(variant)?;
+                        Ok(DiffCommandValue::RemoveValues(Vec::new()))
+                    }
                     (DiffCommandField::Exit, variant) => {
                         de::VariantAccess::unit_variant(variant)?;
                         Ok(DiffCommandValue::Exit)
                     }
+                    (DiffCommandField::Framed, variant) => {
+                        de::VariantAccess::unit_variant(variant)?;
+                        Ok(DiffCommandValue::Framed)
+                    }
+                }
+            }
+        }
+        de::Deserializer::deserialize_enum(
+            deserializer,
+            "DiffCommandValueTest",
+            VARIANTS,
+            Visitor {
+                framed: self.framed,
+                lifetime: std::marker::PhantomData,
+            },
+        )
+    }
+}
+
+// Deserializes a DiffCommand whose values are framed
+pub(crate) struct DiffCommandFramedValue<T> {
+    pub(crate) value_type: std::marker::PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> de::DeserializeSeed<'de> for DiffCommandFramedValue<T> {
+    type Value = DiffCommandValue<'de, T>;
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor<'de, T> {
+            lifetime: std::marker::PhantomData<(&'de (), T)>,
+        }
+        impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Visitor<'de, T> {
+            type Value = DiffCommandValue<'de, T>;
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                std::fmt::Formatter::write_str(formatter, "enum DiffCommandValueTest")
+            }
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::EnumAccess<'de>,
+            {
+                fn value<'de, T: Deserialize<'de>, V: de::VariantAccess<'de>>(
+                    variant: V,
+                ) -> Result<T, V::Error> {
+                    Ok(de::VariantAccess::newtype_variant::<FramedValue<T>>(variant)?.0)
+                }
+                match de::EnumAccess::variant(data)? {
+                    (DiffCommandField::Enter, variant) => {
+                        let enter =
+                            de::VariantAccess::newtype_variant::<DiffPathElementValue>(variant)?;
+                        Ok(DiffCommandValue::Enter(enter))
+                    }
+                    (DiffCommandField::Value, variant) => {
+                        Ok(DiffCommandValue::Value(value(variant)?))
+                    }
+                    (DiffCommandField::AddKey, variant) => {
+                        Ok(DiffCommandValue::AddKey(value(variant)?))
+                    }
+                    (DiffCommandField::EnterKey, variant) => {
+                        Ok(DiffCommandValue::EnterKey(value(variant)?))
+                    }
+                    (DiffCommandField::RemoveKey, variant) => {
+                        Ok(DiffCommandValue::RemoveKey(value(variant)?))
+                    }
+                    (DiffCommandField::RemoveEntry, variant) => {
+                        Ok(DiffCommandValue::RemoveEntry(value(variant)?))
+                    }
+                    (DiffCommandField::RemoveValues, variant) => {
+                        Ok(DiffCommandValue::RemoveValues(value(variant)?))
+                    }
+                    (DiffCommandField::Replace, variant) => {
+                        let (FramedValue(old), FramedValue(new)) =
+                            de::VariantAccess::tuple_variant(variant, 2, Pair::default())?;
+                        Ok(DiffCommandValue::Replace(old, new))
+                    }
+                    (DiffCommandField::Remove, variant) => {
+                        let num_elements = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::Remove(num_elements))
+                    }
+                    (DiffCommandField::InsertAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::InsertAt(idx))
+                    }
+                    (DiffCommandField::RemoveAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveAt(idx))
+                    }
+                    (DiffCommandField::RemoveValueAt, variant) => {
+                        let idx = de::VariantAccess::newtype_variant::<usize>(variant)?;
+                        Ok(DiffCommandValue::RemoveValueAt(idx))
+                    }
+                    (DiffCommandField::Reorder, variant) => {
+                        let order = de::VariantAccess::newtype_variant::<Vec<usize>>(variant)?;
+                        Ok(DiffCommandValue::Reorder(order))
+                    }
+                    (DiffCommandField::Fingerprint, variant) => {
+                        let fingerprint = de::VariantAccess::newtype_variant::<u64>(variant)?;
+                        Ok(DiffCommandValue::Fingerprint(fingerprint))
+                    }
+                    (DiffCommandField::Exit, variant) => {
+                        de::VariantAccess::unit_variant(variant)?;
+                        Ok(DiffCommandValue::Exit)
+                    }
+                    (DiffCommandField::Framed, variant) => {
+                        de::VariantAccess::unit_variant(variant)?;
+                        Ok(DiffCommandValue::Framed)
+                    }
                 }
             }
         }
@@ -567,10 +1027,36 @@ pub enum DiffCommandRef<'a, T: Serialize> {
     RemoveKey(&'a T),
     /// Exit a path element
     Exit,
+    /// Replace a value, also recording the old value. Written instead of Value by invertible diffs.
+    Replace(&'a T, &'a T),
+    /// Remove the given items from the end of collection. Written instead of Remove by
+    /// invertible diffs.
+    RemoveValues(&'a [T]),
+    /// Remove a key from a map. Followed by a Value holding the removed value. Written instead of
+    /// RemoveKey by invertible diffs.
+    RemoveEntry(&'a T),
+    /// Insert an element into a collection at the given index, shifting the elements after it.
+    /// Followed by a Value holding the inserted element.
+    InsertAt(usize),
+    /// Remove the element at the given index from a collection, shifting the elements after it
+    RemoveAt(usize),
+    /// Remove the element at the given index from a collection. Followed by a Value holding the
+    /// removed element. Written instead of RemoveAt by invertible diffs.
+    RemoveValueAt(usize),
+    /// Reorder the elements of a collection, so the element at index i is the one that was at
+    /// index `order[i]`
+    Reorder(&'a [usize]),
+    /// The fingerprint of the type the diff was created for. Written before any other command
+    /// by diffs created with `Config::with_fingerprint`.
+    Fingerprint(u64),
+    /// The payloads of the commands that follow are framed as byte strings, so they can be
+    /// skipped in any format. Written before any other command by diffs created with
+    /// `Config::with_framed_values`.
+    Framed,
 }
 
 #[doc(hidden)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub enum DiffCommandValue<'a, T> {
     // Enter a path element
     #[serde(borrow)]
@@ -587,14 +1073,85 @@ pub enum DiffCommandValue<'a, T> {
     RemoveKey(T),
     // Exit a path element
     Exit,
+    /// Replace a value: the old value, then the new one
+    Replace(T, T),
+    /// Remove the given items from the end of collection
+    RemoveValues(Vec<T>),
+    /// Remove a key from a map, followed by a Value holding the removed value
+    RemoveEntry(T),
+    /// Insert an element at the given index, followed by a Value holding the element
+    InsertAt(usize),
+    /// Remove the element at the given index
+    RemoveAt(usize),
+    /// Remove the element at the given index, followed by a Value holding the removed element
+    RemoveValueAt(usize),
+    /// Reorder the elements of a collection, so the element at index i is the one that was at
+    /// index `order[i]`
+    Reorder(Vec<usize>),
+    /// The fingerprint of the type the diff was created for
+    Fingerprint(u64),
+    /// The payloads of the commands that follow are framed as byte strings
+    Framed,
     // Never serialized
     Nothing,
     // Never serialized, used to indicate that deserializer wrote a value into supplied reference
     DeserializedValue,
 }
 
+impl<'a, T> DiffCommandValue<'a, T> {
+    /// Name of the command, for use in error messages
+    pub fn name(&self) -> &'static str {
+        use DiffCommandValue::*;
+        match self {
+            Enter(_) => "Enter",
+            Value(_) => "Value",
+            Remove(_) => "Remove",
+            AddKey(_) => "AddKey",
+            EnterKey(_) => "EnterKey",
+            RemoveKey(_) => "RemoveKey",
+            Exit => "Exit",
+            Replace(..) => "Replace",
+            RemoveValues(_) => "RemoveValues",
+            RemoveEntry(_) => "RemoveEntry",
+            InsertAt(_) => "InsertAt",
+            RemoveAt(_) => "RemoveAt",
+            RemoveValueAt(_) => "RemoveValueAt",
+            Reorder(_) => "Reorder",
+            Fingerprint(_) => "Fingerprint",
+            Framed => "Framed",
+            Nothing => "Nothing",
+            DeserializedValue => "DeserializedValue",
+        }
+    }
+
+    /// Converts any borrowed path elements into owned ones so the command can outlive its source
+    pub fn into_owned(self) -> DiffCommandValue<'static, T> {
+        use DiffCommandValue::*;
+        match self {
+            Enter(element) => Enter(element.into_owned()),
+            Value(value) => Value(value),
+            Remove(num_elements) => Remove(num_elements),
+            AddKey(key) => AddKey(key),
+            EnterKey(key) => EnterKey(key),
+            RemoveKey(key) => RemoveKey(key),
+            Exit => Exit,
+            Replace(old, new) => Replace(old, new),
+            RemoveValues(values) => RemoveValues(values),
+            RemoveEntry(key) => RemoveEntry(key),
+            InsertAt(idx) => InsertAt(idx),
+            RemoveAt(idx) => RemoveAt(idx),
+            RemoveValueAt(idx) => RemoveValueAt(idx),
+            Reorder(order) => Reorder(order),
+            Fingerprint(fingerprint) => Fingerprint(fingerprint),
+            Framed => Framed,
+            Nothing => Nothing,
+            DeserializedValue => DeserializedValue,
+        }
+    }
+}
+
 #[doc(hidden)]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum DiffPathElementValue<'a> {
     /// A struct field
     #[serde(borrow)]
@@ -604,58 +1161,115 @@ pub enum DiffPathElementValue<'a> {
     FullEnumVariant,
     CollectionIndex(usize),
     AddToCollection,
+    /// An enum variant by its index or tag, written instead of `EnumVariant` in
+    /// `FieldPathMode::Index` and for tagged variants in `FieldPathMode::Tag`
+    EnumVariantIndex(u32),
 }
 
-impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Vec<T> {
-    fn diff<'a, S: SerializeSeq>(
-        &self,
-        ctx: &mut DiffContext<'a, S>,
-        other: &Self,
-    ) -> Result<bool, S::Error> {
-        let mut self_iter = self.iter();
-        let mut other_iter = other.iter();
-        let mut idx = 0;
-        let mut need_exit = false;
-        let mut changed = false;
-        loop {
-            let self_item = self_iter.next();
-            let other_item = other_iter.next();
-            match (self_item, other_item) {
-                (None, None) => break,
-                (Some(_), None) => {
+impl<'a> DiffPathElementValue<'a> {
+    /// Converts any borrowed names into owned strings so the element can outlive its source
+    pub fn into_owned(self) -> DiffPathElementValue<'static> {
+        match self {
+            DiffPathElementValue::Field(name) => {
+                DiffPathElementValue::Field(Cow::Owned(name.into_owned()))
+            }
+            DiffPathElementValue::FieldIndex(idx) => DiffPathElementValue::FieldIndex(idx),
+            DiffPathElementValue::EnumVariant(name) => {
+                DiffPathElementValue::EnumVariant(Cow::Owned(name.into_owned()))
+            }
+            DiffPathElementValue::FullEnumVariant => DiffPathElementValue::FullEnumVariant,
+            DiffPathElementValue::CollectionIndex(idx) => {
+                DiffPathElementValue::CollectionIndex(idx)
+            }
+            DiffPathElementValue::AddToCollection => DiffPathElementValue::AddToCollection,
+            DiffPathElementValue::EnumVariantIndex(idx) => {
+                DiffPathElementValue::EnumVariantIndex(idx)
+            }
+        }
+    }
+}
+
+/// Diffs two sequences of elements, such as the contents of two Vecs. The elements can be held
+/// by value or by reference, so collections that aren't stored as a slice can be diffed as a
+/// slice of references to their elements.
+pub(crate) fn diff_sequence<'a, S, T, E>(
+    ctx: &mut DiffContext<'a, S>,
+    old: &[E],
+    new: &[E],
+    mode: VecDiffMode,
+) -> Result<bool, S::Error>
+where
+    S: SerializeSeq,
+    T: SerdeDiff + Serialize,
+    E: Borrow<T> + Serialize,
+{
+    if mode == VecDiffMode::EditScript {
+        let edits = edit_script(old.len(), new.len(), |old_idx, new_idx| {
+            is_unchanged(old[old_idx].borrow(), new[new_idx].borrow())
+        });
+        if let Some(edits) = edits {
+            return diff_edit_script::<S, T, E>(ctx, old, new, &edits);
+        }
+    }
+    let mut self_iter = old.iter();
+    let mut other_iter = new.iter();
+    let mut idx = 0;
+    let mut need_exit = false;
+    let mut changed = false;
+    loop {
+        let self_item = self_iter.next();
+        let other_item = other_iter.next();
+        match (self_item, other_item) {
+            (None, None) => break,
+            (Some(_), None) => {
+                if ctx.is_invertible() {
+                    ctx.save_command(&DiffCommandRef::RemoveValues(&old[idx..]), true, true)?;
+                } else {
                     let mut num_to_remove = 1;
                     while self_iter.next().is_some() {
                         num_to_remove += 1;
                     }
                     ctx.save_command::<()>(&DiffCommandRef::Remove(num_to_remove), true, true)?;
-                    changed = true;
-                    need_exit = false;
                 }
-                (None, Some(other_item)) => {
-                    ctx.save_command::<()>(
-                        &DiffCommandRef::Enter(DiffPathElementValue::AddToCollection),
-                        false,
-                        true,
-                    )?;
-                    ctx.save_command(&DiffCommandRef::Value(other_item), true, true)?;
+                changed = true;
+                need_exit = false;
+                break;
+            }
+            (None, Some(other_item)) => {
+                ctx.save_command::<()>(
+                    &DiffCommandRef::Enter(DiffPathElementValue::AddToCollection),
+                    false,
+                    true,
+                )?;
+                ctx.save_command(&DiffCommandRef::Value(other_item), true, true)?;
+                need_exit = true;
+                changed = true;
+            }
+            (Some(self_item), Some(other_item)) => {
+                ctx.push_collection_index(idx);
+                if <T as SerdeDiff>::diff(self_item.borrow(), ctx, other_item.borrow())? {
                     need_exit = true;
                     changed = true;
                 }
-                (Some(self_item), Some(other_item)) => {
-                    ctx.push_collection_index(idx);
-                    if <T as SerdeDiff>::diff(self_item, ctx, other_item)? {
-                        need_exit = true;
-                        changed = true;
-                    }
-                    ctx.pop_path_element()?;
-                }
+                ctx.pop_path_element()?;
             }
-            idx += 1;
-        }
-        if need_exit {
-            ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
         }
-        Ok(changed)
+        idx += 1;
+    }
+    if need_exit {
+        ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+    }
+    Ok(changed)
+}
+
+impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Vec<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mode = ctx.vec_diff_mode();
+        diff_sequence::<S, T, T>(ctx, self, other, mode)
     }
 
     fn apply<'de, A>(
@@ -668,41 +1282,108 @@ impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Vec<T> {
     {
         let mut changed = false;
         while let Some(cmd) = ctx.read_next_command::<A, T>(seq)? {
-            use DiffCommandValue::*;
-            use DiffPathElementValue::*;
-            match cmd {
-                // we should not be getting fields when reading collection commands
-                Enter(Field(_)) => {
-                    ctx.skip_value(seq)?;
-                    break;
-                }
-                Enter(CollectionIndex(idx)) => {
-                    if let Some(value_ref) = self.get_mut(idx) {
-                        changed |= <T as SerdeDiff>::apply(value_ref, seq, ctx)?;
-                    } else {
-                        ctx.skip_value(seq)?;
-                    }
-                }
-                Enter(AddToCollection) => {
-                    if let Value(v) = ctx
-                        .read_next_command(seq)?
-                        .expect("Expected value after AddToCollection")
-                    {
-                        changed = true;
-                        self.push(v);
-                    } else {
-                        panic!("Expected value after AddToCollection");
-                    }
-                }
-                Remove(num_elements) => {
-                    let new_length = self.len().saturating_sub(num_elements);
-                    self.truncate(new_length);
-                    changed = true;
-                    break;
-                }
-                _ => break,
+            if !apply_sequence_command(self, cmd, seq, ctx, &mut changed)? {
+                break;
             }
         }
         Ok(changed)
     }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+/// Applies a command read for a Vec, setting `changed` if the Vec was changed. Returns false if
+/// the command ends the commands for the Vec.
+pub(crate) fn apply_sequence_command<'de, A, T>(
+    vec: &mut Vec<T>,
+    cmd: DiffCommandValue<'de, T>,
+    seq: &mut A,
+    ctx: &mut ApplyContext,
+    changed: &mut bool,
+) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+where
+    A: de::SeqAccess<'de>,
+    T: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
+{
+    use DiffCommandValue::*;
+    use DiffPathElementValue::*;
+    match cmd {
+        // we should not be getting fields when reading collection commands
+        Enter(Field(_)) => {
+            ctx.skip_value(seq)?;
+            return Ok(false);
+        }
+        Enter(CollectionIndex(idx)) => {
+            if let Some(value_ref) = vec.get_mut(idx) {
+                *changed |= <T as SerdeDiff>::apply(value_ref, seq, ctx)?;
+            } else {
+                ctx.skip_value(seq)?;
+            }
+        }
+        Enter(AddToCollection) => {
+            let v = ctx.read_expected_value(seq, "AddToCollection")?;
+            ctx.record_change(
+                ChangeKind::Added,
+                Some(PathElement::Element(CollectionIndex(vec.len()))),
+            );
+            *changed = true;
+            vec.push(v);
+        }
+        Remove(_) | RemoveValues(_) => {
+            let num_elements = match cmd {
+                RemoveValues(values) => values.len(),
+                Remove(num_elements) => num_elements,
+                _ => 0,
+            };
+            if num_elements > vec.len() {
+                ctx.unmatched_exited_element()?;
+            }
+            let new_length = vec.len().saturating_sub(num_elements);
+            for idx in new_length..vec.len() {
+                ctx.record_exited_change(
+                    ChangeKind::Removed,
+                    Some(PathElement::Element(CollectionIndex(idx))),
+                );
+            }
+            vec.truncate(new_length);
+            *changed = true;
+            return Ok(false);
+        }
+        InsertAt(idx) => {
+            let v = ctx.read_expected_value(seq, "InsertAt")?;
+            if idx <= vec.len() {
+                ctx.record_exited_change(ChangeKind::Added, None);
+                vec.insert(idx, v);
+                *changed = true;
+            } else {
+                ctx.unmatched_exited_element()?;
+            }
+        }
+        RemoveAt(idx) => {
+            if idx < vec.len() {
+                ctx.record_change(
+                    ChangeKind::Removed,
+                    Some(PathElement::Element(CollectionIndex(idx))),
+                );
+                vec.remove(idx);
+                *changed = true;
+            } else {
+                ctx.unmatched_element(PathElement::Element(CollectionIndex(idx)))?;
+            }
+        }
+        RemoveValueAt(idx) => {
+            ctx.read_expected_value::<A, T>(seq, "RemoveValueAt")?;
+            if idx < vec.len() {
+                ctx.record_exited_change(ChangeKind::Removed, None);
+                vec.remove(idx);
+                *changed = true;
+            } else {
+                ctx.unmatched_exited_element()?;
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
 }
@@ -0,0 +1,246 @@
+/// Implements SerdeDiff on a dynamic value type such as `serde_json::Value`, in a module of its
+/// own. Objects are diffed like maps and arrays like Vecs, so a change deep inside a document
+/// only sends the changed values. Values whose type changed are replaced as a whole.
+macro_rules! dynamic_value_serde_diff {
+    ($module:ident, $value:ty, $map:ty, $object:ident, $array:ident, $as_map_mut:ident) => {
+        mod $module {
+            use crate::{
+                apply::ApplyContext,
+                difference::{
+                    apply_sequence_command, diff_sequence, DiffCommandRef, DiffCommandValue,
+                    DiffContext,
+                },
+                ChangeKind, Fingerprint, SerdeDiff,
+            };
+            use serde::{de, ser::SerializeSeq};
+
+            type Value = $value;
+            type Map = $map;
+
+            impl SerdeDiff for Value {
+                fn diff<'a, S: SerializeSeq>(
+                    &self,
+                    ctx: &mut DiffContext<'a, S>,
+                    other: &Self,
+                ) -> Result<bool, S::Error> {
+                    match (self, other) {
+                        (Value::$object(old), Value::$object(new)) => diff_object(ctx, old, new),
+                        (Value::$array(old), Value::$array(new)) => {
+                            let mode = ctx.vec_diff_mode();
+                            diff_sequence::<S, Value, Value>(ctx, old, new, mode)
+                        }
+                        _ if self != other => {
+                            ctx.save_change(self, other)?;
+                            Ok(true)
+                        }
+                        _ => Ok(false),
+                    }
+                }
+
+                fn apply<'de, A>(
+                    &mut self,
+                    seq: &mut A,
+                    ctx: &mut ApplyContext,
+                ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+                where
+                    A: de::SeqAccess<'de>,
+                {
+                    let mut changed = false;
+                    while let Some(cmd) = ctx.read_next_command::<A, Value>(seq)? {
+                        match cmd {
+                            DiffCommandValue::Value(value)
+                            | DiffCommandValue::Replace(_, value) => {
+                                ctx.record_exited_change(ChangeKind::Modified, None);
+                                *self = value;
+                                return Ok(true);
+                            }
+                            DiffCommandValue::EnterKey(_)
+                            | DiffCommandValue::AddKey(_)
+                            | DiffCommandValue::RemoveKey(_)
+                            | DiffCommandValue::RemoveEntry(_) => {
+                                changed |= apply_object_command(self, cmd, seq, ctx)?;
+                            }
+                            DiffCommandValue::Exit => break,
+                            _ => match self {
+                                Value::$array(values) => {
+                                    let changes = &mut changed;
+                                    if !apply_sequence_command(values, cmd, seq, ctx, changes)? {
+                                        break;
+                                    }
+                                }
+                                // The array was replaced since the diff, so its elements are
+                                // skipped
+                                _ => {
+                                    if !skip_sequence_command(cmd, seq, ctx)? {
+                                        break;
+                                    }
+                                }
+                            },
+                        }
+                    }
+                    Ok(changed)
+                }
+
+                fn fingerprint() -> u64 {
+                    Fingerprint::new("dynamic value")
+                        .write_str(stringify!($module))
+                        .finish()
+                }
+            }
+
+            fn diff_object<'a, S: SerializeSeq>(
+                ctx: &mut DiffContext<'a, S>,
+                old: &Map,
+                new: &Map,
+            ) -> Result<bool, S::Error> {
+                let mut changed = false;
+                for (key, old_value) in old {
+                    match new.get(key) {
+                        Some(new_value) => {
+                            let save_closure = |serializer: &mut S| {
+                                serializer.serialize_element(&DiffCommandRef::EnterKey(key))
+                            };
+                            let mut subctx = ctx.reborrow();
+                            subctx.push_field_element(&save_closure);
+                            if <Value as SerdeDiff>::diff(old_value, &mut subctx, new_value)? {
+                                changed = true;
+                            }
+                            subctx.pop_path_element()?;
+                        }
+                        None => {
+                            if ctx.is_invertible() {
+                                ctx.save_command(&DiffCommandRef::RemoveEntry(key), true, true)?;
+                                ctx.save_command(&DiffCommandRef::Value(old_value), true, true)?;
+                            } else {
+                                ctx.save_command(&DiffCommandRef::RemoveKey(key), true, true)?;
+                            }
+                            changed = true;
+                        }
+                    }
+                }
+                for (key, new_value) in new {
+                    if !old.contains_key(key) {
+                        ctx.save_command(&DiffCommandRef::AddKey(key), true, true)?;
+                        ctx.save_command(&DiffCommandRef::Value(new_value), true, true)?;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+                }
+                Ok(changed)
+            }
+
+            /// Applies a map command to the object, which doesn't match if the value is no longer
+            /// an object
+            fn apply_object_command<'de, A>(
+                value: &mut Value,
+                cmd: DiffCommandValue<'de, Value>,
+                seq: &mut A,
+                ctx: &mut ApplyContext,
+            ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let object = value.$as_map_mut();
+                match cmd {
+                    DiffCommandValue::EnterKey(key) => match (object, key) {
+                        (Some(object), Value::String(key)) if object.contains_key(&key) => {
+                            <Value as SerdeDiff>::apply(&mut object[&key], seq, ctx)
+                        }
+                        _ => ctx.skip_value(seq).map(|_| false),
+                    },
+                    DiffCommandValue::AddKey(key) => {
+                        let new_value = ctx.read_expected_value(seq, "AddKey")?;
+                        match (object, key) {
+                            (Some(object), Value::String(key)) => {
+                                let kind = match object.insert(key, new_value) {
+                                    Some(_) => {
+                                        ctx.unmatched_exited_element()?;
+                                        ChangeKind::Modified
+                                    }
+                                    None => ChangeKind::Added,
+                                };
+                                ctx.record_exited_change(kind, None);
+                                Ok(true)
+                            }
+                            _ => ctx.unmatched_exited_element().map(|_| false),
+                        }
+                    }
+                    DiffCommandValue::RemoveKey(key) => {
+                        if remove_key(object, &key) {
+                            ctx.record_key_change(ChangeKind::Removed, &key)?;
+                            Ok(true)
+                        } else {
+                            ctx.unmatched_key(&key).map(|_| false)
+                        }
+                    }
+                    DiffCommandValue::RemoveEntry(key) => {
+                        ctx.read_expected_value::<A, Value>(seq, "RemoveEntry")?;
+                        if remove_key(object, &key) {
+                            ctx.record_exited_change(ChangeKind::Removed, None);
+                            Ok(true)
+                        } else {
+                            ctx.unmatched_exited_element().map(|_| false)
+                        }
+                    }
+                    _ => Ok(false),
+                }
+            }
+
+            fn remove_key(object: Option<&mut Map>, key: &Value) -> bool {
+                match (object, key) {
+                    (Some(object), Value::String(key)) => object.remove(key).is_some(),
+                    _ => false,
+                }
+            }
+
+            /// Skips a command for an array that is no longer an array. Returns false if the
+            /// command ends the commands for the array.
+            fn skip_sequence_command<'de, A>(
+                cmd: DiffCommandValue<'de, Value>,
+                seq: &mut A,
+                ctx: &mut ApplyContext,
+            ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                match cmd {
+                    DiffCommandValue::Enter(_) => ctx.skip_value(seq)?,
+                    DiffCommandValue::InsertAt(_) | DiffCommandValue::RemoveValueAt(_) => {
+                        ctx.read_expected_value::<A, Value>(seq, cmd.name())?;
+                        ctx.unmatched_exited_element()?;
+                    }
+                    DiffCommandValue::RemoveAt(_) | DiffCommandValue::Reorder(_) => {
+                        ctx.unmatched_current()?
+                    }
+                    DiffCommandValue::Remove(_) | DiffCommandValue::RemoveValues(_) => {
+                        ctx.unmatched_exited_element()?;
+                        return Ok(false);
+                    }
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "serde_json")]
+dynamic_value_serde_diff!(
+    json,
+    serde_json::Value,
+    serde_json::Map<String, serde_json::Value>,
+    Object,
+    Array,
+    as_object_mut
+);
+#[cfg(feature = "toml")]
+dynamic_value_serde_diff!(
+    toml,
+    toml::Value,
+    toml::map::Map<String, toml::Value>,
+    Table,
+    Array,
+    as_table_mut
+);
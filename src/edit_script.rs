@@ -0,0 +1,195 @@
+use crate::{
+    counting_serializer::CountingSerializer,
+    difference::{DiffCommandRef, DiffContext},
+    FieldPathMode, SerdeDiff, VecDiffMode,
+};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+use std::borrow::Borrow;
+
+/// Largest number of insertions and removals an edit script is searched for. The search keeps
+/// O(D²) state for an edit distance of D, so sequences that differ in more places are compared by
+/// index instead.
+const MAX_EDIT_DISTANCE: usize = 256;
+
+/// Largest number of element comparisons made while searching for an edit script. Each comparison
+/// diffs two elements, so long sequences with many repeated elements are compared by index
+/// instead of being diffed against each other over and over.
+pub(crate) const MAX_COMPARISONS: usize = 1 << 18;
+
+/// A step of an edit script turning the old sequence into the new one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Edit {
+    /// The next old element is kept as the next new element
+    Keep,
+    /// The next old element is removed
+    Remove,
+    /// The next new element is inserted
+    Insert,
+}
+
+/// Computes a shortest edit script between sequences of the given lengths, using Myers' O(ND)
+/// difference algorithm. Returns `None` if the sequences differ in too many places, or finding
+/// the edit script takes too many comparisons.
+pub(crate) fn edit_script(
+    old_len: usize,
+    new_len: usize,
+    equal: impl Fn(usize, usize) -> bool,
+) -> Option<Vec<Edit>> {
+    let (n, m) = (old_len as isize, new_len as isize);
+    // trace[d][k + d] is the furthest old index reached on diagonal k (x - y) with d edits
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let max_distance = std::cmp::min(old_len + new_len, MAX_EDIT_DISTANCE) as isize;
+    let mut comparisons = 0;
+    for d in 0..=max_distance {
+        let mut furthest = vec![0; 2 * d as usize + 1];
+        for k in (-d..=d).step_by(2) {
+            let mut x = if d == 0 {
+                0
+            } else {
+                let previous = &trace[d as usize - 1];
+                let at = |k: isize| previous[(k + d - 1) as usize];
+                if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+                    // Move down from diagonal k + 1 by inserting
+                    at(k + 1)
+                } else {
+                    // Move right from diagonal k - 1 by removing
+                    at(k - 1) + 1
+                }
+            };
+            let mut y = x - k;
+            while x < n && y < m {
+                comparisons += 1;
+                if comparisons > MAX_COMPARISONS {
+                    return None;
+                }
+                if !equal(x as usize, y as usize) {
+                    break;
+                }
+                x += 1;
+                y += 1;
+            }
+            furthest[(k + d) as usize] = x;
+            if x >= n && y >= m {
+                trace.push(furthest);
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(furthest);
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[d as usize - 1];
+        let at = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if x == previous_x {
+            Edit::Insert
+        } else {
+            Edit::Remove
+        });
+        x = previous_x;
+        y = previous_y;
+    }
+    while x > 0 && y > 0 {
+        edits.push(Edit::Keep);
+        x -= 1;
+        y -= 1;
+    }
+    edits.reverse();
+    edits
+}
+
+/// True if diffing the two values finds no changes
+pub(crate) fn is_unchanged<T: SerdeDiff>(old: &T, new: &T) -> bool {
+    let mut serializer = CountingSerializer { num_elements: 0 };
+    let mut seq = match serializer.serialize_seq(None) {
+        Ok(seq) => seq,
+        Err(_) => return false,
+    };
+    let mut ctx = DiffContext::new(&mut seq, FieldPathMode::Name, false, VecDiffMode::ByIndex);
+    !old.diff(&mut ctx, new).unwrap_or(true)
+}
+
+/// Writes the commands for an edit script that turns `old` into `new`. Removals are paired with
+/// the insertions that replace them where possible, and those elements are diffed in place.
+pub(crate) fn diff_edit_script<'a, S, T, E>(
+    ctx: &mut DiffContext<'a, S>,
+    old: &[E],
+    new: &[E],
+    edits: &[Edit],
+) -> Result<bool, S::Error>
+where
+    S: SerializeSeq,
+    T: SerdeDiff + Serialize,
+    E: Borrow<T> + Serialize,
+{
+    let mut changed = false;
+    // The collection being edited holds new[..new_idx] followed by old[old_idx..]
+    let (mut old_idx, mut new_idx) = (0, 0);
+    let mut edits = edits.iter().peekable();
+    while let Some(edit) = edits.next() {
+        if *edit == Edit::Keep {
+            old_idx += 1;
+            new_idx += 1;
+            continue;
+        }
+        let (mut removed, mut inserted) = (0, 0);
+        let mut edit = Some(edit);
+        while let Some(step) = edit {
+            match step {
+                Edit::Remove => removed += 1,
+                Edit::Insert => inserted += 1,
+                Edit::Keep => unreachable!(),
+            }
+            edit = edits.next_if(|next| **next != Edit::Keep);
+        }
+
+        let replaced = std::cmp::min(removed, inserted);
+        for offset in 0..replaced {
+            ctx.push_collection_index(new_idx + offset);
+            changed |= <T as SerdeDiff>::diff(
+                old[old_idx + offset].borrow(),
+                ctx,
+                new[new_idx + offset].borrow(),
+            )?;
+            ctx.pop_path_element()?;
+        }
+        for offset in replaced..removed {
+            let idx = new_idx + replaced;
+            if ctx.is_invertible() {
+                ctx.save_command::<()>(&DiffCommandRef::RemoveValueAt(idx), true, true)?;
+                ctx.save_command(&DiffCommandRef::Value(&old[old_idx + offset]), true, true)?;
+            } else {
+                ctx.save_command::<()>(&DiffCommandRef::RemoveAt(idx), true, true)?;
+            }
+            changed = true;
+        }
+        for offset in replaced..inserted {
+            ctx.save_command::<()>(&DiffCommandRef::InsertAt(new_idx + offset), true, true)?;
+            ctx.save_command(&DiffCommandRef::Value(&new[new_idx + offset]), true, true)?;
+            changed = true;
+        }
+        old_idx += removed;
+        new_idx += inserted;
+    }
+    if changed {
+        ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+    }
+    Ok(changed)
+}
@@ -0,0 +1,285 @@
+use serde::de::{self, Deserialize, DeserializeSeed, Visitor};
+use std::cell::RefCell;
+use std::fmt;
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Builds the fingerprint of a type's structure, as returned by `SerdeDiff::fingerprint`. Uses
+/// FNV-1a, so fingerprints don't depend on the platform or on a random seed.
+#[doc(hidden)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Starts the fingerprint of a type of the given kind, such as "struct" or "Vec"
+    pub fn new(kind: &str) -> Self {
+        let mut fingerprint = Fingerprint(OFFSET_BASIS);
+        fingerprint.write_str(kind);
+        fingerprint
+    }
+
+    /// Adds a name, such as the name of a field, to the fingerprint
+    pub fn write_str(&mut self, value: &str) -> &mut Self {
+        // Prefixing the length keeps "ab" + "c" apart from "a" + "bc"
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+        self
+    }
+
+    /// Adds a number, such as a tag or the fingerprint of a field's type, to the fingerprint
+    pub fn write_u64(&mut self, value: u64) -> &mut Self {
+        self.write_bytes(&value.to_le_bytes());
+        self
+    }
+
+    /// The fingerprint of everything added so far
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+
+    /// Computes the fingerprint of a type that may contain itself, such as a tree node holding
+    /// a `Vec` of nodes. A type reached again while its own fingerprint is being computed only
+    /// adds how many types up it was reached, which ends the recursion. Types are told apart by
+    /// `name` alone, so a generic type nested in another instance of itself counts as reached
+    /// again.
+    pub fn recursive(name: &'static str, compute: impl FnOnce() -> u64) -> u64 {
+        let depth = IN_PROGRESS.with(|in_progress| {
+            let mut in_progress = in_progress.borrow_mut();
+            let depth = in_progress
+                .iter()
+                .rev()
+                .position(|entered| *entered == name);
+            if depth.is_none() {
+                in_progress.push(name);
+            }
+            depth
+        });
+        if let Some(depth) = depth {
+            return Fingerprint::new("recursive")
+                .write_u64(depth as u64)
+                .finish();
+        }
+
+        // Pops the type even if computing its fingerprint panics
+        struct Exit;
+        impl Drop for Exit {
+            fn drop(&mut self) {
+                IN_PROGRESS.with(|in_progress| in_progress.borrow_mut().pop());
+            }
+        }
+        let _exit = Exit;
+        compute()
+    }
+
+    /// Fingerprint of a type that doesn't implement SerdeDiff, such as the key of a map, from
+    /// the data its `Deserialize` impl asks for: the kind of value, the names of structs, fields
+    /// and variants, and the types inside options and newtypes. Sequences and tuples only add
+    /// the type of their first element, and structs don't add the types of their fields.
+    pub fn deserialized<T: for<'de> Deserialize<'de>>() -> u64 {
+        let mut fingerprint = Fingerprint::new("deserialized");
+        // The probe fails as soon as it has written what was asked for
+        let _ = T::deserialize(Probe {
+            fingerprint: &mut fingerprint,
+            depth: 0,
+        });
+        fingerprint.finish()
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+}
+
+thread_local! {
+    /// Types whose fingerprints are being computed, innermost last
+    static IN_PROGRESS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Fingerprint of a container type, such as a `Vec`, from the fingerprints of its element types
+pub(crate) fn container(kind: &str, elements: &[u64]) -> u64 {
+    let mut fingerprint = Fingerprint::new(kind);
+    for element in elements {
+        fingerprint.write_u64(*element);
+    }
+    fingerprint.finish()
+}
+
+/// How many options, newtypes and elements deep a `Probe` looks, which also ends the recursion
+/// of types that contain themselves
+const MAX_PROBE_DEPTH: usize = 8;
+
+/// Deserializer that writes the hint it is asked to deserialize into a fingerprint, then fails
+struct Probe<'a> {
+    fingerprint: &'a mut Fingerprint,
+    depth: usize,
+}
+
+impl<'a> Probe<'a> {
+    /// Probes the type of a value inside the one asked for
+    fn nested(self) -> Result<Self, ProbeError> {
+        if self.depth == MAX_PROBE_DEPTH {
+            return Err(ProbeError);
+        }
+        Ok(Probe {
+            fingerprint: self.fingerprint,
+            depth: self.depth + 1,
+        })
+    }
+}
+
+/// Gives a sequence visitor a single element to probe
+struct ProbeSeq<'a>(Option<Probe<'a>>);
+
+/// The error a `Probe` stops with
+#[derive(Debug)]
+struct ProbeError;
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("fingerprint probe")
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        ProbeError
+    }
+}
+
+/// Implements deserialize methods that only write the kind of value they were asked for
+macro_rules! probe_kinds {
+    ($($method:ident => $kind:expr,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, ProbeError> {
+                self.fingerprint.write_str($kind);
+                Err(ProbeError)
+            }
+        )*
+    };
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Probe<'a> {
+    type Error = ProbeError;
+
+    probe_kinds! {
+        deserialize_any => "any",
+        deserialize_bool => "bool",
+        deserialize_i8 => "i8",
+        deserialize_i16 => "i16",
+        deserialize_i32 => "i32",
+        deserialize_i64 => "i64",
+        deserialize_i128 => "i128",
+        deserialize_u8 => "u8",
+        deserialize_u16 => "u16",
+        deserialize_u32 => "u32",
+        deserialize_u64 => "u64",
+        deserialize_u128 => "u128",
+        deserialize_f32 => "f32",
+        deserialize_f64 => "f64",
+        deserialize_char => "char",
+        deserialize_str => "str",
+        deserialize_string => "string",
+        deserialize_bytes => "bytes",
+        deserialize_byte_buf => "byte_buf",
+        deserialize_unit => "unit",
+        deserialize_map => "map",
+        deserialize_identifier => "identifier",
+        deserialize_ignored_any => "ignored_any",
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("option");
+        visitor.visit_some(self.nested()?)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("unit_struct").write_str(name);
+        Err(ProbeError)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("newtype_struct").write_str(name);
+        visitor.visit_newtype_struct(self.nested()?)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("seq");
+        visitor.visit_seq(ProbeSeq(Some(self.nested()?)))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("tuple").write_u64(len as u64);
+        visitor.visit_seq(ProbeSeq(Some(self.nested()?)))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint
+            .write_str("tuple_struct")
+            .write_str(name)
+            .write_u64(len as u64);
+        visitor.visit_seq(ProbeSeq(Some(self.nested()?)))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("struct").write_str(name);
+        for field in fields {
+            self.fingerprint.write_str(field);
+        }
+        Err(ProbeError)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.fingerprint.write_str("enum").write_str(name);
+        for variant in variants {
+            self.fingerprint.write_str(variant);
+        }
+        Err(ProbeError)
+    }
+}
+
+// Sequences, tuples and tuple structs are probed through the type of their first element
+impl<'a, 'de> de::SeqAccess<'de> for ProbeSeq<'a> {
+    type Error = ProbeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ProbeError> {
+        match self.0.take() {
+            Some(probe) => seed.deserialize(probe).map(Some),
+            None => Ok(None),
+        }
+    }
+}
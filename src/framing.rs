@@ -0,0 +1,550 @@
+use serde::{de, ser, Deserialize, Serialize, Serializer};
+use serde_value::{Value, ValueDeserializer};
+use std::collections::BTreeMap;
+
+// Framed diffs write the payload of every command carrying a value as a byte string, so the
+// command can be skipped without knowing the type of the value. This is needed for formats that
+// aren't self-describing, like bincode, which can't skip a value they know nothing about.
+// The payload is converted to a serde_value::Value, the same way a DiffPatch holds values, and
+// encoded into bytes with the small self-describing encoding below.
+
+/// Names of the commands whose payloads are framed
+const FRAMED_COMMANDS: &[&str] = &[
+    "Value",
+    "AddKey",
+    "EnterKey",
+    "RemoveKey",
+    "RemoveEntry",
+    "RemoveValues",
+    "Replace",
+];
+
+/// Nesting deeper than this is rejected when decoding, so a malicious payload can't overflow the
+/// stack
+const MAX_DEPTH: usize = 128;
+
+fn encode(value: &Value, out: &mut Vec<u8>) {
+    fn len(len: usize, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(len as u64).to_le_bytes());
+    }
+    match value {
+        Value::Bool(v) => out.extend_from_slice(&[0, u8::from(*v)]),
+        Value::U8(v) => out.extend_from_slice(&[1, *v]),
+        Value::U16(v) => {
+            out.push(2);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::U32(v) => {
+            out.push(3);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::U64(v) => {
+            out.push(4);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::I8(v) => out.extend_from_slice(&[5, *v as u8]),
+        Value::I16(v) => {
+            out.push(6);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::I32(v) => {
+            out.push(7);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::I64(v) => {
+            out.push(8);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::F32(v) => {
+            out.push(9);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::F64(v) => {
+            out.push(10);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::Char(v) => {
+            out.push(11);
+            out.extend_from_slice(&u32::from(*v).to_le_bytes());
+        }
+        Value::String(v) => {
+            out.push(12);
+            len(v.len(), out);
+            out.extend_from_slice(v.as_bytes());
+        }
+        Value::Unit => out.push(13),
+        Value::Option(None) => out.push(14),
+        Value::Option(Some(v)) => {
+            out.push(15);
+            encode(v, out);
+        }
+        Value::Newtype(v) => {
+            out.push(16);
+            encode(v, out);
+        }
+        Value::Seq(elements) => {
+            out.push(17);
+            len(elements.len(), out);
+            for element in elements {
+                encode(element, out);
+            }
+        }
+        Value::Map(entries) => {
+            out.push(18);
+            len(entries.len(), out);
+            for (key, value) in entries {
+                encode(key, out);
+                encode(value, out);
+            }
+        }
+        Value::Bytes(v) => {
+            out.push(19);
+            len(v.len(), out);
+            out.extend_from_slice(v);
+        }
+    }
+}
+
+/// Reads values written by `encode`
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.bytes.len() {
+            return Err("framed value ended unexpectedly");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn len(&mut self) -> Result<usize, &'static str> {
+        let len = u64::from_le_bytes(self.array()?);
+        // Every element takes at least one byte, which bounds what a valid length can be
+        if len > self.bytes.len() as u64 {
+            return Err("framed value ended unexpectedly");
+        }
+        Ok(len as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, &'static str> {
+        if depth > MAX_DEPTH {
+            return Err("framed value is nested too deeply");
+        }
+        let [tag] = self.array()?;
+        Ok(match tag {
+            0 => Value::Bool(self.array::<1>()?[0] != 0),
+            1 => Value::U8(u8::from_le_bytes(self.array()?)),
+            2 => Value::U16(u16::from_le_bytes(self.array()?)),
+            3 => Value::U32(u32::from_le_bytes(self.array()?)),
+            4 => Value::U64(u64::from_le_bytes(self.array()?)),
+            5 => Value::I8(i8::from_le_bytes(self.array()?)),
+            6 => Value::I16(i16::from_le_bytes(self.array()?)),
+            7 => Value::I32(i32::from_le_bytes(self.array()?)),
+            8 => Value::I64(i64::from_le_bytes(self.array()?)),
+            9 => Value::F32(f32::from_le_bytes(self.array()?)),
+            10 => Value::F64(f64::from_le_bytes(self.array()?)),
+            11 => Value::Char(
+                std::char::from_u32(u32::from_le_bytes(self.array()?))
+                    .ok_or("framed value holds an invalid char")?,
+            ),
+            12 => {
+                let len = self.len()?;
+                let bytes = self.take(len)?;
+                Value::String(
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|_| "framed value holds an invalid string")?,
+                )
+            }
+            13 => Value::Unit,
+            14 => Value::Option(None),
+            15 => Value::Option(Some(Box::new(self.value(depth + 1)?))),
+            16 => Value::Newtype(Box::new(self.value(depth + 1)?)),
+            17 => {
+                let len = self.len()?;
+                let mut elements = Vec::with_capacity(len);
+                for _ in 0..len {
+                    elements.push(self.value(depth + 1)?);
+                }
+                Value::Seq(elements)
+            }
+            18 => {
+                let len = self.len()?;
+                let mut entries = BTreeMap::new();
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    let value = self.value(depth + 1)?;
+                    entries.insert(key, value);
+                }
+                Value::Map(entries)
+            }
+            19 => {
+                let len = self.len()?;
+                Value::Bytes(self.take(len)?.to_vec())
+            }
+            _ => return Err("framed value has an unknown type tag"),
+        })
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<Value, &'static str> {
+    let mut decoder = Decoder { bytes };
+    let value = decoder.value(0)?;
+    if !decoder.bytes.is_empty() {
+        return Err("framed value has trailing bytes");
+    }
+    Ok(value)
+}
+
+/// Serializes a value as a byte string holding its encoded form
+struct Framed<'a, T: ?Sized>(&'a T);
+
+impl<T: ?Sized + Serialize> Serialize for Framed<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = serde_value::to_value(self.0).map_err(ser::Error::custom)?;
+        let mut bytes = Vec::new();
+        encode(&value, &mut bytes);
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+/// Reads a byte string, which formats without a native byte string type may write as a sequence
+struct BytesVisitor;
+
+impl<'de> de::Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a framed value")
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+fn read_framed<'de, D: de::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ValueDeserializer<D::Error>, D::Error> {
+    let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+    let value = decode(&bytes).map_err(de::Error::custom)?;
+    Ok(ValueDeserializer::new(value))
+}
+
+/// Deserializes a value from its framed form
+pub(crate) struct FramedValue<T>(pub(crate) T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FramedValue<T> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(read_framed(deserializer)?).map(FramedValue)
+    }
+}
+
+/// Deserializes a value from its framed form in place
+pub(crate) struct FramedInPlace<'a, T>(pub(crate) &'a mut T);
+
+impl<'a, 'de, T: Deserialize<'de>> de::DeserializeSeed<'de> for FramedInPlace<'a, T> {
+    type Value = ();
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        T::deserialize_in_place(read_framed(deserializer)?, self.0)
+    }
+}
+
+/// Skips a framed value, which every format can do as it only needs to read a byte string
+pub(crate) struct SkipFramed;
+
+impl<'de> Deserialize<'de> for SkipFramed {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)?;
+        Ok(SkipFramed)
+    }
+}
+
+/// Reads the two payloads of a Replace command
+pub(crate) struct Pair<T>(std::marker::PhantomData<T>);
+
+impl<T> Default for Pair<T> {
+    fn default() -> Self {
+        Pair(std::marker::PhantomData)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Pair<T> {
+    type Value = (T, T);
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an old and a new value")
+    }
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let old = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let new = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok((old, new))
+    }
+}
+
+/// A sequence of diff commands that frames the payloads of the commands written into it
+pub(crate) struct FramedSeq<'a, S> {
+    pub(crate) seq: &'a mut S,
+}
+
+impl<S: ser::SerializeSeq> ser::SerializeSeq for FramedSeq<'_, S> {
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_element<T>(&mut self, command: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.seq.serialize_element(&FramedCommand(command))
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        // The wrapped sequence is ended by its owner
+        Ok(())
+    }
+}
+
+/// Serializes a diff command with its payload framed
+struct FramedCommand<'a, T: ?Sized>(&'a T);
+
+impl<T: ?Sized + Serialize> Serialize for FramedCommand<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(CommandSerializer(serializer))
+    }
+}
+
+/// Passes a diff command on to the wrapped serializer, framing the payload of commands that
+/// carry values. Diff commands are enums, so everything else is passed on unchanged.
+struct CommandSerializer<S>(S);
+
+impl<S: Serializer> Serializer for CommandSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = FramedFields<S::SerializeTupleVariant>;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if FRAMED_COMMANDS.contains(&variant) {
+            self.0
+                .serialize_newtype_variant(name, variant_index, variant, &Framed(value))
+        } else {
+            self.0
+                .serialize_newtype_variant(name, variant_index, variant, value)
+        }
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Ok(FramedFields {
+            fields: self
+                .0
+                .serialize_tuple_variant(name, variant_index, variant, len)?,
+            framed: FRAMED_COMMANDS.contains(&variant),
+        })
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.0.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.0.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.0.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.0.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_struct(name)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.0.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.0.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.0.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.0.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.0.serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.0
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+/// Fields of a command with several payloads, such as Replace, which are framed one by one
+struct FramedFields<S> {
+    fields: S,
+    framed: bool,
+}
+
+impl<S: ser::SerializeTupleVariant> ser::SerializeTupleVariant for FramedFields<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.framed {
+            self.fields.serialize_field(&Framed(value))
+        } else {
+            self.fields.serialize_field(value)
+        }
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.fields.end()
+    }
+}
@@ -1,85 +1,68 @@
 use crate::{
     apply::ApplyContext,
-    difference::{DiffCommandRef, DiffContext, DiffPathElementValue},
-    SerdeDiff,
+    difference::{diff_sequence, DiffCommandRef, DiffContext, DiffPathElementValue},
+    ChangeKind, SerdeDiff, VecDiffMode,
 };
 
 use serde::{de, ser::SerializeSeq, Deserialize, Serialize};
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque},
     hash::Hash,
+    num::Wrapping,
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError, RwLock},
 };
 
-macro_rules! array_impls {
-    ($($len:tt)+) => {
-        $(
-            impl<T: $crate::SerdeDiff + serde::Serialize + for<'a> serde::Deserialize<'a>> $crate::SerdeDiff for [T; $len] {
-                fn diff<'a, S: serde::ser::SerializeSeq>(
-                    &self,
-                    ctx: &mut $crate::difference::DiffContext<'a, S>,
-                    other: &Self,
-                ) -> Result<bool, S::Error> {
-                    use $crate::difference::DiffCommandRef;
-
-                    let mut need_exit = false;
-                    let mut changed = false;
-                    for (idx, (self_item, other_item)) in self.iter().zip(other.iter()).enumerate() {
-                        ctx.push_collection_index(idx);
-                        if <T as $crate::SerdeDiff>::diff(self_item, ctx, other_item)? {
-                            need_exit = true;
-                            changed = true;
-                        }
-                        ctx.pop_path_element()?;
-                    }
-                    if need_exit {
-                        ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
-                    }
-                    Ok(changed)
-                }
+// Arrays only contain diffs of their elements, so the elements don't need to be serializable
+impl<T: SerdeDiff, const N: usize> SerdeDiff for [T; N] {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mut need_exit = false;
+        let mut changed = false;
+        for (idx, (self_item, other_item)) in self.iter().zip(other.iter()).enumerate() {
+            ctx.push_collection_index(idx);
+            if <T as SerdeDiff>::diff(self_item, ctx, other_item)? {
+                need_exit = true;
+                changed = true;
+            }
+            ctx.pop_path_element()?;
+        }
+        if need_exit {
+            ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+        }
+        Ok(changed)
+    }
 
-                fn apply<'de, A>(
-                    &mut self,
-                    seq: &mut A,
-                    ctx: &mut $crate::apply::ApplyContext,
-                ) -> Result<bool, <A as serde::de::SeqAccess<'de>>::Error>
-                where
-                    A: serde::de::SeqAccess<'de>,
-                {
-                    let mut changed = false;
-                    while let Some(cmd) = ctx.read_next_command::<A, T>(seq)? {
-                        use $crate::difference::DiffCommandValue::*;
-                        use $crate::difference::DiffPathElementValue::*;
-                        match cmd {
-                            // we should not be getting fields when reading collection commands
-                            Enter(Field(_)) => {
-                                ctx.skip_value(seq)?;
-                                break;
-                            }
-                            Enter(CollectionIndex(idx)) => {
-                                if let Some(value_ref) = self.get_mut(idx) {
-                                    changed |= <T as $crate::SerdeDiff>::apply(value_ref, seq, ctx)?;
-                                } else {
-                                    ctx.skip_value(seq)?;
-                                }
-                            }
-                            _ => break,
-                        }
-                    }
-                    Ok(changed)
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut changed = false;
+        while let Some(element) = ctx.next_path_element(seq)? {
+            match element {
+                DiffPathElementValue::CollectionIndex(idx) if idx < N => {
+                    changed |= <T as SerdeDiff>::apply(&mut self[idx], seq, ctx)?;
                 }
+                _ => ctx.skip_value(seq)?,
             }
-        )+
+        }
+        Ok(changed)
     }
-}
 
-array_impls! {
-    01 02 03 04 05 06 07 08 09 10
-    11 12 13 14 15 16 17 18 19 20
-    21 22 23 24 25 26 27 28 29 30
-    31 32
-    40 48 50 56 64 72 96 100 128 160 192 200 224 256 384 512
-    768 1024 2048 4096 8192 16384 32768 65536
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("array", &[T::fingerprint(), N as u64])
+    }
 }
 
 macro_rules! tuple_impls {
@@ -87,7 +70,7 @@ macro_rules! tuple_impls {
         $(
             impl<$($name),+> $crate::SerdeDiff for ($($name,)+)
             where
-                $($name: $crate::SerdeDiff + serde::Serialize + for<'a> serde::Deserialize<'a>,)+
+                $($name: $crate::SerdeDiff,)+
             {
                 fn diff<'a, S: serde::ser::SerializeSeq>(
                     &self,
@@ -96,7 +79,7 @@ macro_rules! tuple_impls {
                 ) -> Result<bool, S::Error> {
                     let mut changed = false;
                     $(
-                        ctx.push_field(stringify!($n));
+                        ctx.push_field_with_index(stringify!($n), $n);
                         changed |= <$name as $crate::SerdeDiff>::diff(&self.$n, ctx, &other.$n)?;
                         ctx.pop_path_element()?;
                     )+
@@ -112,16 +95,25 @@ macro_rules! tuple_impls {
                     A: serde::de::SeqAccess<'de>,
                 {
                     let mut changed = false;
-                    while let Some($crate::difference::DiffPathElementValue::Field(element)) = ctx.next_path_element(seq)? {
-                        match element.as_ref() {
+                    while let Some(element) = ctx.next_path_element(seq)? {
+                        match element {
                             $(
-                                stringify!($n) => changed |= <$name as $crate::SerdeDiff>::apply(&mut self.$n, seq, ctx)?,
+                                $crate::difference::DiffPathElementValue::Field(ref field) if field == stringify!($n) => {
+                                    changed |= <$name as $crate::SerdeDiff>::apply(&mut self.$n, seq, ctx)?
+                                }
+                                $crate::difference::DiffPathElementValue::FieldIndex($n) => {
+                                    changed |= <$name as $crate::SerdeDiff>::apply(&mut self.$n, seq, ctx)?
+                                }
                             )+
                             _ => ctx.skip_value(seq)?,
                         }
                     }
                     Ok(changed)
                 }
+
+                fn fingerprint() -> u64 {
+                    $crate::fingerprint::container("tuple", &[$(<$name as $crate::SerdeDiff>::fingerprint()),+])
+                }
             }
         )+
     }
@@ -144,13 +136,29 @@ tuple_impls! {
     14 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13)
     15 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14)
     16 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15)
+    17 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16)
+    18 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17)
+    19 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18)
+    20 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19)
+    21 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20)
+    22 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21)
+    23 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22)
+    24 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23)
+    25 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24)
+    26 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25)
+    27 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26)
+    28 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26 27 T27)
+    29 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26 27 T27 28 T28)
+    30 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26 27 T27 28 T28 29 T29)
+    31 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26 27 T27 28 T28 29 T29 30 T30)
+    32 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15 16 T16 17 T17 18 T18 19 T19 20 T20 21 T21 22 T22 23 T23 24 T24 25 T25 26 T26 27 T27 28 T28 29 T29 30 T30 31 T31)
 }
 /// Implement SerdeDiff on a "map-like" type such as HashMap.
 macro_rules! map_serde_diff {
-    ($t:ty, $($extra_traits:path),*) => {
+    ($t:ty, $remove:ident, $($extra_traits:path),*) => {
         impl<K, V> SerdeDiff for $t
         where
-            K: SerdeDiff + Serialize + for<'a> Deserialize<'a> $(+ $extra_traits)*, // + Hash + Eq,
+            K: Serialize + for<'a> Deserialize<'a> $(+ $extra_traits)*,
             V: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
         {
             fn diff<'a, S: SerializeSeq>(
@@ -172,9 +180,15 @@ macro_rules! map_serde_diff {
                             if <V as SerdeDiff>::diff(self_value, &mut subctx, other_value)? {
                                 changed = true;
                             }
+                            subctx.pop_path_element()?;
                         },
                         None => {
-                            ctx.save_command(&DiffCommandRef::RemoveKey(key), true, true)?;
+                            if ctx.is_invertible() {
+                                ctx.save_command(&DiffCommandRef::RemoveEntry(key), true, true)?;
+                                ctx.save_command(&DiffCommandRef::Value(self_value), true, true)?;
+                            } else {
+                                ctx.save_command(&DiffCommandRef::RemoveKey(key), true, true)?;
+                            }
                             changed = true;
                         },
                     }
@@ -212,37 +226,587 @@ macro_rules! map_serde_diff {
                             ctx.skip_value(seq)?;
                             break;
                         }
-                        AddKey(key) => if let Some(Value(v)) = ctx.read_next_command(seq)? {
+                        AddKey(key) => {
+                            let v = ctx.read_expected_value(seq, "AddKey")?;
+                            if self.contains_key(&key) {
+                                ctx.unmatched_exited_element()?;
+                                ctx.record_exited_change(ChangeKind::Modified, None);
+                            } else {
+                                ctx.record_exited_change(ChangeKind::Added, None);
+                            }
                             //changed |= self.insert(key, v).map(|old_val| v != old_val).unwrap_or(true);
                             self.insert(key, v);
                             changed = true;
-                        } else {
-                            panic!("Expected value after AddKey");
                         }
                         EnterKey(key) => if let Some(value_ref) = self.get_mut(&key) {
                             changed |= <V as SerdeDiff>::apply(value_ref, seq, ctx)?;
                         } else {
                             ctx.skip_value(seq)?;
                         }
-                        RemoveKey(key) => changed |= self.remove(&key).is_some(),
+                        RemoveKey(key) => match self.$remove(&key) {
+                            Some(_) => {
+                                ctx.record_key_change(ChangeKind::Removed, &key)?;
+                                changed = true;
+                            }
+                            None => ctx.unmatched_key(&key)?,
+                        },
+                        RemoveEntry(key) => {
+                            ctx.read_expected_value::<A, V>(seq, "RemoveEntry")?;
+                            match self.$remove(&key) {
+                                Some(_) => {
+                                    ctx.record_exited_change(ChangeKind::Removed, None);
+                                    changed = true;
+                                }
+                                None => ctx.unmatched_exited_element()?,
+                            }
+                        }
+                        _ => break,
+                    }
+                }
+                Ok(changed)
+            }
+
+            fn fingerprint() -> u64 {
+                $crate::fingerprint::container(
+                    "map",
+                    &[$crate::Fingerprint::deserialized::<K>(), V::fingerprint()],
+                )
+            }
+        }
+    };
+}
+
+map_serde_diff!(HashMap<K, V>, remove, Hash, Eq);
+map_serde_diff!(BTreeMap<K, V>, remove, Ord);
+#[cfg(feature = "indexmap")]
+map_serde_diff!(indexmap::IndexMap<K, V>, shift_remove, Hash, Eq);
+
+/// Implement SerdeDiff on a "set-like" type such as HashSet. Sets are diffed like maps with unit
+/// values: added elements are written as AddKey and removed ones as RemoveKey, each followed by a
+/// unit Value where a map would have its value.
+macro_rules! set_serde_diff {
+    ($t:ty, $remove:ident, $($extra_traits:path),*) => {
+        impl<K> SerdeDiff for $t
+        where
+            K: Serialize + for<'a> Deserialize<'a> $(+ $extra_traits)*,
+        {
+            fn diff<'a, S: SerializeSeq>(
+                &self,
+                ctx: &mut $crate::difference::DiffContext<'a, S>,
+                other: &Self,
+            ) -> Result<bool, S::Error> {
+                use $crate::difference::DiffCommandRef;
+
+                let mut changed = false;
+                for key in self.iter() {
+                    if !other.contains(key) {
+                        if ctx.is_invertible() {
+                            ctx.save_command(&DiffCommandRef::RemoveEntry(key), true, true)?;
+                            ctx.save_command(&DiffCommandRef::Value(&()), true, true)?;
+                        } else {
+                            ctx.save_command(&DiffCommandRef::RemoveKey(key), true, true)?;
+                        }
+                        changed = true;
+                    }
+                }
+
+                for key in other.iter() {
+                    if !self.contains(key) {
+                        ctx.save_command(&DiffCommandRef::AddKey(key), true, true)?;
+                        ctx.save_command(&DiffCommandRef::Value(&()), true, true)?;
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+                }
+                Ok(changed)
+            }
+
+            fn apply<'de, A>(
+                &mut self,
+                seq: &mut A,
+                ctx: &mut ApplyContext,
+            ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut changed = false;
+                while let Some(cmd) = ctx.read_next_command::<A, K>(seq)? {
+                    use $crate::difference::DiffCommandValue::*;
+                    use $crate::difference::DiffPathElementValue::*;
+                    match cmd {
+                        // we should not be getting fields when reading collection commands
+                        Enter(Field(_)) => {
+                            ctx.skip_value(seq)?;
+                            break;
+                        }
+                        AddKey(key) => {
+                            ctx.read_expected_value::<A, ()>(seq, "AddKey")?;
+                            if self.contains(&key) {
+                                ctx.unmatched_exited_element()?;
+                            } else {
+                                ctx.record_exited_change(ChangeKind::Added, None);
+                                self.insert(key);
+                                changed = true;
+                            }
+                        }
+                        // Elements of a set have no parts to enter
+                        EnterKey(_) => ctx.skip_value(seq)?,
+                        RemoveKey(key) => {
+                            if self.$remove(&key) {
+                                ctx.record_key_change(ChangeKind::Removed, &key)?;
+                                changed = true;
+                            } else {
+                                ctx.unmatched_key(&key)?;
+                            }
+                        }
+                        RemoveEntry(key) => {
+                            ctx.read_expected_value::<A, ()>(seq, "RemoveEntry")?;
+                            if self.$remove(&key) {
+                                ctx.record_exited_change(ChangeKind::Removed, None);
+                                changed = true;
+                            } else {
+                                ctx.unmatched_exited_element()?;
+                            }
+                        }
                         _ => break,
                     }
                 }
                 Ok(changed)
             }
+
+            fn fingerprint() -> u64 {
+                $crate::fingerprint::container("set", &[$crate::Fingerprint::deserialized::<K>()])
+            }
+        }
+    };
+}
+
+set_serde_diff!(HashSet<K>, remove, Hash, Eq);
+set_serde_diff!(BTreeSet<K>, remove, Ord);
+// Removing shifts the later elements down, so the order of the rest is kept
+#[cfg(feature = "indexmap")]
+set_serde_diff!(indexmap::IndexSet<K>, shift_remove, Hash, Eq);
+
+/// Applies the commands for a sequence to a collection by moving its elements into a Vec, applying
+/// them to the Vec and moving the elements back
+fn apply_as_vec<'de, A, T, C>(
+    collection: &mut C,
+    seq: &mut A,
+    ctx: &mut ApplyContext,
+    into_vec: impl FnOnce(C) -> Vec<T>,
+    from_vec: impl FnOnce(Vec<T>) -> C,
+) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+where
+    A: de::SeqAccess<'de>,
+    T: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
+    C: Default,
+{
+    let mut vec = into_vec(std::mem::take(collection));
+    let result = <Vec<T> as SerdeDiff>::apply(&mut vec, seq, ctx);
+    *collection = from_vec(vec);
+    result
+}
+
+// Deques are diffed as edit scripts, as elements are commonly pushed and popped at both ends
+impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for VecDeque<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let old: Vec<&T> = self.iter().collect();
+        let new: Vec<&T> = other.iter().collect();
+        diff_sequence::<S, T, &T>(ctx, &old, &new, VecDiffMode::EditScript)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        apply_as_vec(self, seq, ctx, Vec::from, VecDeque::from)
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for LinkedList<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let old: Vec<&T> = self.iter().collect();
+        let new: Vec<&T> = other.iter().collect();
+        diff_sequence::<S, T, &T>(ctx, &old, &new, VecDiffMode::EditScript)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        apply_as_vec(
+            self,
+            seq,
+            ctx,
+            |list| list.into_iter().collect(),
+            |vec| vec.into_iter().collect(),
+        )
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+// The order of a heap's elements is an implementation detail, so heaps are diffed as sorted
+// sequences. Indices in the diff refer to the sorted order rather than the serialized one.
+impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a> + Ord> SerdeDiff for BinaryHeap<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mut old: Vec<&T> = self.iter().collect();
+        let mut new: Vec<&T> = other.iter().collect();
+        old.sort();
+        new.sort();
+        diff_sequence::<S, T, &T>(ctx, &old, &new, VecDiffMode::EditScript)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        apply_as_vec(
+            self,
+            seq,
+            ctx,
+            BinaryHeap::into_sorted_vec,
+            BinaryHeap::from,
+        )
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Box<[T]> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mode = ctx.vec_diff_mode();
+        diff_sequence::<S, T, T>(ctx, self, other, mode)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        apply_as_vec(self, seq, ctx, Vec::from, Vec::into_boxed_slice)
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> SerdeDiff for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
+{
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mode = ctx.vec_diff_mode();
+        diff_sequence::<S, A::Item, A::Item>(ctx, self, other, mode)
+    }
+
+    fn apply<'de, D>(
+        &mut self,
+        seq: &mut D,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <D as de::SeqAccess<'de>>::Error>
+    where
+        D: de::SeqAccess<'de>,
+    {
+        apply_as_vec(
+            self,
+            seq,
+            ctx,
+            smallvec::SmallVec::into_vec,
+            smallvec::SmallVec::from_vec,
+        )
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[A::Item::fingerprint()])
+    }
+}
+
+#[cfg(feature = "arrayvec")]
+impl<T, const CAP: usize> SerdeDiff for arrayvec::ArrayVec<T, CAP>
+where
+    T: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
+{
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let mode = ctx.vec_diff_mode();
+        diff_sequence::<S, T, T>(ctx, self, other, mode)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        // Elements added beyond the capacity are dropped, and reported as an error
+        let mut overflow = false;
+        let changed = apply_as_vec(
+            self,
+            seq,
+            ctx,
+            |array| array.into_iter().collect(),
+            |vec| {
+                overflow = vec.len() > CAP;
+                vec.into_iter().take(CAP).collect()
+            },
+        )?;
+        if overflow {
+            return Err(de::Error::custom(format_args!(
+                "applying the diff exceeds the ArrayVec capacity of {}",
+                CAP
+            )));
+        }
+        Ok(changed)
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("sequence", &[T::fingerprint()])
+    }
+}
+
+/// Implements SerdeDiff on a wrapper by diffing and applying to the value it wraps, so the wrapper
+/// is transparent in the diff. `$inner` is the type of the value, `$get` borrows it from `&Self` and
+/// `$get_mut` from `&mut Self`.
+macro_rules! forward_serde_diff {
+    ([$($generics:tt)*] $t:ty => $inner:ty, |$this:ident| $get:expr, |$this_mut:ident| $get_mut:expr) => {
+        impl<$($generics)*> SerdeDiff for $t {
+            fn diff<'a, S: SerializeSeq>(
+                &self,
+                ctx: &mut DiffContext<'a, S>,
+                other: &Self,
+            ) -> Result<bool, S::Error> {
+                // Comparing a lock with itself would deadlock, and shared pointers can't differ
+                if std::ptr::eq(self, other) {
+                    return Ok(false);
+                }
+                let old = {
+                    let $this = self;
+                    $get
+                };
+                let new = {
+                    let $this = other;
+                    $get
+                };
+                if std::ptr::eq(&*old, &*new) {
+                    return Ok(false);
+                }
+                SerdeDiff::diff(&*old, ctx, &*new)
+            }
+
+            fn apply<'de, A>(
+                &mut self,
+                seq: &mut A,
+                ctx: &mut ApplyContext,
+            ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let $this_mut = self;
+                SerdeDiff::apply($get_mut, seq, ctx)
+            }
+
+            fn fingerprint() -> u64 {
+                <$inner as SerdeDiff>::fingerprint()
+            }
         }
     };
 }
 
-map_serde_diff!(HashMap<K, V>, Hash, Eq);
-map_serde_diff!(BTreeMap<K, V>, Ord);
+forward_serde_diff!([T: SerdeDiff] Box<T> => T, |this| &**this, |this| &mut **this);
+// Shared values are only cloned when a diff is applied to them, which leaves other owners unchanged
+forward_serde_diff!([T: SerdeDiff + Clone] Rc<T> => T, |this| &**this, |this| Rc::make_mut(this));
+forward_serde_diff!([T: SerdeDiff + Clone] Arc<T> => T, |this| &**this, |this| Arc::make_mut(this));
+forward_serde_diff!(['c, T: SerdeDiff + Clone] Cow<'c, T> => T, |this| &**this, |this| this.to_mut());
+forward_serde_diff!([T: SerdeDiff] RefCell<T> => T, |this| this.borrow(), |this| this.get_mut());
+forward_serde_diff!(
+    [T: SerdeDiff] Mutex<T> => T,
+    |this| this.lock().unwrap_or_else(PoisonError::into_inner),
+    |this| this.get_mut().unwrap_or_else(PoisonError::into_inner)
+);
+forward_serde_diff!(
+    [T: SerdeDiff] RwLock<T> => T,
+    |this| this.read().unwrap_or_else(PoisonError::into_inner),
+    |this| this.get_mut().unwrap_or_else(PoisonError::into_inner)
+);
+forward_serde_diff!([T: SerdeDiff] Wrapping<T> => T, |this| &this.0, |this| &mut this.0);
+forward_serde_diff!([T: SerdeDiff] Reverse<T> => T, |this| &this.0, |this| &mut this.0);
+// Matrices, including nalgebra's vectors, are diffed component-wise as their column-major array
+// of columns
+#[cfg(feature = "nalgebra")]
+forward_serde_diff!(
+    [T: SerdeDiff, const R: usize, const C: usize] nalgebra::SMatrix<T, R, C> => [[T; R]; C],
+    |this| &this.data.0,
+    |this| &mut this.data.0
+);
+#[cfg(feature = "nalgebra")]
+forward_serde_diff!(
+    [T: nalgebra::Scalar + SerdeDiff, const D: usize] nalgebra::Point<T, D> => nalgebra::SVector<T, D>,
+    |this| &this.coords,
+    |this| &mut this.coords
+);
+
+impl<T: SerdeDiff + Copy> SerdeDiff for Cell<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        <T as SerdeDiff>::diff(&self.get(), ctx, &other.get())
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        <T as SerdeDiff>::apply(self.get_mut(), seq, ctx)
+    }
+
+    fn fingerprint() -> u64 {
+        T::fingerprint()
+    }
+}
+
+/// Implements SerdeDiff on a vector type with named components, such as glam's Vec3. Each
+/// component is diffed like a struct field.
+#[cfg(feature = "glam")]
+macro_rules! vector_serde_diff {
+    ($($krate:ident::$name:ident => ($($component:ident)+))+) => {
+        $(
+            impl SerdeDiff for $krate::$name {
+                fn diff<'a, S: SerializeSeq>(
+                    &self,
+                    ctx: &mut DiffContext<'a, S>,
+                    other: &Self,
+                ) -> Result<bool, S::Error> {
+                    let mut changed = false;
+                    $(
+                        ctx.push_field(stringify!($component));
+                        changed |= SerdeDiff::diff(&self.$component, ctx, &other.$component)?;
+                        ctx.pop_path_element()?;
+                    )+
+                    Ok(changed)
+                }
+
+                fn apply<'de, A>(
+                    &mut self,
+                    seq: &mut A,
+                    ctx: &mut ApplyContext,
+                ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+                where
+                    A: de::SeqAccess<'de>,
+                {
+                    let mut changed = false;
+                    while let Some(DiffPathElementValue::Field(element)) = ctx.next_path_element(seq)? {
+                        match element.as_ref() {
+                            $(
+                                stringify!($component) => changed |= SerdeDiff::apply(&mut self.$component, seq, ctx)?,
+                            )+
+                            _ => ctx.skip_value(seq)?,
+                        }
+                    }
+                    Ok(changed)
+                }
+
+                fn fingerprint() -> u64 {
+                    // The name tells the scalar type of the components apart
+                    let mut fingerprint = crate::Fingerprint::new("vector");
+                    fingerprint.write_str(stringify!($name));
+                    $(
+                        fingerprint.write_str(stringify!($component));
+                    )+
+                    fingerprint.finish()
+                }
+            }
+        )+
+    }
+}
+
+#[cfg(feature = "glam")]
+vector_serde_diff! {
+    glam::Vec2 => (x y)
+    glam::Vec3 => (x y z)
+    glam::Vec3A => (x y z)
+    glam::Vec4 => (x y z w)
+    glam::DVec2 => (x y)
+    glam::DVec3 => (x y z)
+    glam::DVec4 => (x y z w)
+    glam::IVec2 => (x y)
+    glam::IVec3 => (x y z)
+    glam::IVec4 => (x y z w)
+    glam::UVec2 => (x y)
+    glam::UVec3 => (x y z)
+    glam::UVec4 => (x y z w)
+    glam::Quat => (x y z w)
+    glam::DQuat => (x y z w)
+}
 
 /// Implements SerdeDiff on a type given that it impls Serialize + Deserialize + PartialEq.
 /// This makes the type a "terminal" type in the SerdeDiff hierarchy, meaning deeper inspection
 /// will not be possible. Use the SerdeDiff derive macro for recursive field inspection.
+/// An optional name, as in `opaque_serde_diff!(MyType, "MyType")`, gives the type a fingerprint
+/// of its own.
 #[macro_export]
 macro_rules! opaque_serde_diff {
-    ($t:ty) => {
+    ($t:ty $(, $name:expr)?) => {
         impl SerdeDiff for $t {
             fn diff<'a, S: $crate::_serde::ser::SerializeSeq>(
                 &self,
@@ -250,7 +814,7 @@ macro_rules! opaque_serde_diff {
                 other: &Self,
             ) -> Result<bool, S::Error> {
                 if self != other {
-                    ctx.save_value(other)?;
+                    ctx.save_change(self, other)?;
                     Ok(true)
                 } else {
                     Ok(false)
@@ -267,44 +831,72 @@ macro_rules! opaque_serde_diff {
             {
                 ctx.read_value(seq, self)
             }
+            $(
+                fn fingerprint() -> u64 {
+                    $crate::Fingerprint::new($name).finish()
+                }
+            )?
         }
     };
 }
 
 // Implement `SerdeDiff` for primitive types and types defined in the standard library.
-opaque_serde_diff!(bool);
-opaque_serde_diff!(isize);
-opaque_serde_diff!(i8);
-opaque_serde_diff!(i16);
-opaque_serde_diff!(i32);
-opaque_serde_diff!(i64);
-opaque_serde_diff!(usize);
-opaque_serde_diff!(u8);
-opaque_serde_diff!(u16);
-opaque_serde_diff!(u32);
-opaque_serde_diff!(u64);
-opaque_serde_diff!(i128);
-opaque_serde_diff!(u128);
-opaque_serde_diff!(f32);
-opaque_serde_diff!(f64);
-opaque_serde_diff!(char);
-opaque_serde_diff!(String);
-opaque_serde_diff!(std::ffi::CString);
+opaque_serde_diff!(bool, "bool");
+opaque_serde_diff!(isize, "isize");
+opaque_serde_diff!(i8, "i8");
+opaque_serde_diff!(i16, "i16");
+opaque_serde_diff!(i32, "i32");
+opaque_serde_diff!(i64, "i64");
+opaque_serde_diff!(usize, "usize");
+opaque_serde_diff!(u8, "u8");
+opaque_serde_diff!(u16, "u16");
+opaque_serde_diff!(u32, "u32");
+opaque_serde_diff!(u64, "u64");
+opaque_serde_diff!(i128, "i128");
+opaque_serde_diff!(u128, "u128");
+opaque_serde_diff!(f32, "f32");
+opaque_serde_diff!(f64, "f64");
+opaque_serde_diff!(char, "char");
+opaque_serde_diff!(String, "String");
+opaque_serde_diff!(std::ffi::CString, "CString");
 #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
-opaque_serde_diff!(std::ffi::OsString);
-opaque_serde_diff!(std::num::NonZeroU8);
-opaque_serde_diff!(std::num::NonZeroU16);
-opaque_serde_diff!(std::num::NonZeroU32);
-opaque_serde_diff!(std::num::NonZeroU64);
-opaque_serde_diff!(std::time::Duration);
-opaque_serde_diff!(std::time::SystemTime);
-opaque_serde_diff!(std::net::IpAddr);
-opaque_serde_diff!(std::net::Ipv4Addr);
-opaque_serde_diff!(std::net::Ipv6Addr);
-opaque_serde_diff!(std::net::SocketAddr);
-opaque_serde_diff!(std::net::SocketAddrV4);
-opaque_serde_diff!(std::net::SocketAddrV6);
-opaque_serde_diff!(std::path::PathBuf);
+opaque_serde_diff!(std::ffi::OsString, "OsString");
+opaque_serde_diff!(std::num::NonZeroU8, "NonZeroU8");
+opaque_serde_diff!(std::num::NonZeroU16, "NonZeroU16");
+opaque_serde_diff!(std::num::NonZeroU32, "NonZeroU32");
+opaque_serde_diff!(std::num::NonZeroU64, "NonZeroU64");
+opaque_serde_diff!(std::time::Duration, "Duration");
+opaque_serde_diff!(std::time::SystemTime, "SystemTime");
+opaque_serde_diff!(std::net::IpAddr, "IpAddr");
+opaque_serde_diff!(std::net::Ipv4Addr, "Ipv4Addr");
+opaque_serde_diff!(std::net::Ipv6Addr, "Ipv6Addr");
+opaque_serde_diff!(std::net::SocketAddr, "SocketAddr");
+opaque_serde_diff!(std::net::SocketAddrV4, "SocketAddrV4");
+opaque_serde_diff!(std::net::SocketAddrV6, "SocketAddrV6");
+opaque_serde_diff!(std::path::PathBuf, "PathBuf");
+
+// Implement `SerdeDiff` for types from optional dependencies that are compared as a whole
+#[cfg(feature = "uuid")]
+opaque_serde_diff!(uuid::Uuid, "Uuid");
+#[cfg(feature = "chrono")]
+opaque_serde_diff!(chrono::NaiveDate, "NaiveDate");
+#[cfg(feature = "chrono")]
+opaque_serde_diff!(chrono::NaiveTime, "NaiveTime");
+#[cfg(feature = "chrono")]
+opaque_serde_diff!(chrono::NaiveDateTime, "NaiveDateTime");
+#[cfg(feature = "chrono")]
+opaque_serde_diff!(chrono::DateTime<chrono::Utc>, "DateTime<Utc>");
+#[cfg(feature = "chrono")]
+opaque_serde_diff!(
+    chrono::DateTime<chrono::FixedOffset>,
+    "DateTime<FixedOffset>"
+);
+#[cfg(feature = "bytes")]
+opaque_serde_diff!(bytes::Bytes, "Bytes");
+#[cfg(feature = "bytes")]
+opaque_serde_diff!(bytes::BytesMut, "BytesMut");
+#[cfg(feature = "url")]
+opaque_serde_diff!(url::Url, "Url");
 
 impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Option<T> {
     fn diff<'a, S: SerializeSeq>(
@@ -323,12 +915,21 @@ impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Option<T>
             match (self_item, other_item) {
                 (None, None) => break,
                 (Some(_), None) => {
-                    let mut num_to_remove = 1;
-                    while self_iter.next().is_some() {
-                        num_to_remove += 1;
+                    if ctx.is_invertible() {
+                        ctx.save_command(
+                            &DiffCommandRef::RemoveValues(self.as_slice()),
+                            true,
+                            true,
+                        )?;
+                    } else {
+                        let mut num_to_remove = 1;
+                        while self_iter.next().is_some() {
+                            num_to_remove += 1;
+                        }
+                        ctx.save_command::<()>(&DiffCommandRef::Remove(num_to_remove), true, true)?;
                     }
-                    ctx.save_command::<()>(&DiffCommandRef::Remove(num_to_remove), true, true)?;
                     changed = true;
+                    break;
                 }
                 (None, Some(other_item)) => {
                     ctx.save_command::<()>(
@@ -384,19 +985,29 @@ impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Option<T>
                     }
                 }
                 Enter(AddToCollection) => {
-                    if let Value(v) = ctx
-                        .read_next_command(seq)?
-                        .expect("Expected value after AddToCollection")
-                    {
-                        debug_assert!(self.is_none());
-                        changed = true;
-                        *self = Some(v);
+                    let v = ctx.read_expected_value(seq, "AddToCollection")?;
+                    if self.is_some() {
+                        ctx.unmatched_exited_element()?;
+                        ctx.record_change(ChangeKind::Modified, None);
                     } else {
-                        panic!("Expected value after AddToCollection");
+                        ctx.record_change(ChangeKind::Added, None);
                     }
+                    changed = true;
+                    *self = Some(v);
                 }
                 Remove(1) => {
-                    *self = None;
+                    match self.take() {
+                        Some(_) => ctx.record_exited_change(ChangeKind::Removed, None),
+                        None => ctx.unmatched_exited_element()?,
+                    }
+                    changed = true;
+                    break;
+                }
+                RemoveValues(ref values) if values.len() == 1 => {
+                    match self.take() {
+                        Some(_) => ctx.record_exited_change(ChangeKind::Removed, None),
+                        None => ctx.unmatched_exited_element()?,
+                    }
                     changed = true;
                     break;
                 }
@@ -405,7 +1016,11 @@ impl<T: SerdeDiff + Serialize + for<'a> Deserialize<'a>> SerdeDiff for Option<T>
         }
         Ok(changed)
     }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("Option", &[T::fingerprint()])
+    }
 }
 
 type Unit = ();
-opaque_serde_diff!(Unit);
+opaque_serde_diff!(Unit, "()");
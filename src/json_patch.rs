@@ -0,0 +1,489 @@
+use crate::{
+    difference::DiffPathElementValue,
+    patch::{is_prefix, PatchOperation, PathElement},
+    Config, FieldPathMode, SerdeDiff,
+};
+use serde::{de, ser, Deserialize, Serialize};
+use serde_value::{DeserializerError, SerializerError, Value, ValueDeserializer};
+use std::collections::HashMap;
+
+/// An RFC 6902 JSON Patch document: a list of operations that change a JSON value.
+///
+/// A `JsonPatch` can be computed from two values and serialized with any serde format, or
+/// deserialized from JSON sent by other tools and applied to a `SerdeDiff` type. Only the `add`,
+/// `remove` and `replace` operations are supported.
+///
+/// JSON Pointer paths follow the serialized representation of the value: struct fields and map
+/// keys are object members, sequences and tuples are arrays, `Option`s are their value or `null`
+/// and enums are externally tagged.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_diff::{SerdeDiff, JsonPatch, JsonPatchOperation, serde_value::Value};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug)]
+/// struct Test {
+///     a: i32,
+///     b: Vec<i32>,
+/// }
+/// let patch = JsonPatch::new(&Test { a: 3, b: vec![1, 2] }, &Test { a: 5, b: vec![1] })
+///     .expect("failed to diff");
+/// assert_eq!(
+///     patch.0,
+///     vec![
+///         JsonPatchOperation::Replace { path: "/a".to_string(), value: Value::I32(5) },
+///         JsonPatchOperation::Remove { path: "/b/1".to_string() },
+///     ]
+/// );
+/// let mut target = Test { a: 3, b: vec![1, 2] };
+/// patch.apply(&mut target).expect("failed to apply patch");
+/// assert_eq!(target, Test { a: 5, b: vec![1] });
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonPatch(pub Vec<JsonPatchOperation>);
+
+/// A single JSON Patch operation. Paths are JSON Pointers as described in RFC 6901.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOperation {
+    /// Adds a value to an array or object. The path `/-` appends to an array.
+    Add {
+        /// Location to add the value at
+        path: String,
+        /// The value to add
+        value: Value,
+    },
+    /// Removes the value at the path
+    Remove {
+        /// Location of the value to remove
+        path: String,
+    },
+    /// Replaces the value at the path, which must exist
+    Replace {
+        /// Location of the value to replace
+        path: String,
+        /// The new value
+        value: Value,
+    },
+}
+
+impl JsonPatch {
+    /// Computes the differences between the old and new value as JSON Patch operations
+    pub fn new<T: SerdeDiff + Serialize>(old: &T, new: &T) -> Result<Self, SerializerError> {
+        Config::default().json_patch(old, new)
+    }
+
+    /// Applies the operations to the target
+    pub fn apply<T>(&self, target: &mut T) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        Config::default().apply_json_patch(self, target)
+    }
+
+    pub(crate) fn compute<T: SerdeDiff + Serialize>(
+        config: Config,
+        old: &T,
+        new: &T,
+    ) -> Result<Self, SerializerError> {
+        // Old values are needed to find the indices of removed elements, and the serialized field
+        // names to build the pointers
+        let operations = config
+            .with_invertible(true)
+            .with_field_path_mode(FieldPathMode::Name)
+            .with_serialized_names(true)
+            .diff_patch(old, new)?
+            .operations()
+            .map_err(ser::Error::custom)?;
+        let old = serde_value::to_value(old)?;
+        // JSON Pointers address array elements by index, so arrays that are reordered or whose
+        // elements are addressed by key are replaced as a whole
+        let rewritten = rewritten_arrays(&old, &operations);
+        let new = if rewritten.is_empty() {
+            Value::Unit
+        } else {
+            serde_value::to_value(new)?
+        };
+        let mut replaced: Vec<&[PathElement]> = Vec::new();
+        let mut patch = Vec::new();
+        for operation in &operations {
+            let rewritten = rewritten
+                .iter()
+                .find(|container| is_prefix(container, operation.path()));
+            if let Some(container) = rewritten {
+                if !replaced.contains(container) {
+                    patch.push(JsonPatchOperation::Replace {
+                        path: locate(&old, container)?.0,
+                        value: locate(&new, container)?.1.cloned().unwrap_or(Value::Unit),
+                    });
+                    replaced.push(container);
+                }
+                continue;
+            }
+            match operation {
+                PatchOperation::Value(path, value) | PatchOperation::Replace(path, _, value) => {
+                    match path.split_last() {
+                        Some((
+                            PathElement::Element(DiffPathElementValue::AddToCollection),
+                            container,
+                        )) => {
+                            let (pointer, node) = locate(&old, container)?;
+                            if let Some(Value::Option(_)) = node {
+                                // An Option that was None is serialized as null, so it is replaced
+                                patch.push(JsonPatchOperation::Replace {
+                                    path: pointer,
+                                    value: value.clone(),
+                                });
+                            } else {
+                                patch.push(JsonPatchOperation::Add {
+                                    path: pointer + "/-",
+                                    value: value.clone(),
+                                });
+                            }
+                        }
+                        _ => patch.push(JsonPatchOperation::Replace {
+                            path: locate(&old, path)?.0,
+                            value: value.clone(),
+                        }),
+                    }
+                }
+                PatchOperation::Remove(path, _) | PatchOperation::RemoveValues(path, _) => {
+                    let num_elements = match operation {
+                        PatchOperation::RemoveValues(_, values) => values.len(),
+                        PatchOperation::Remove(_, num_elements) => *num_elements,
+                        _ => 0,
+                    };
+                    let (pointer, node) = locate(&old, path)?;
+                    match node {
+                        Some(Value::Option(_)) => patch.push(JsonPatchOperation::Replace {
+                            path: pointer,
+                            value: Value::Unit,
+                        }),
+                        Some(Value::Seq(values)) => {
+                            // Remove from the end, so the remaining indices stay valid
+                            let len = values.len();
+                            for idx in (len.saturating_sub(num_elements)..len).rev() {
+                                patch.push(JsonPatchOperation::Remove {
+                                    path: format!("{}/{}", pointer, idx),
+                                });
+                            }
+                        }
+                        _ => {
+                            return Err(ser::Error::custom(format!(
+                                "no collection to remove elements from at {}",
+                                pointer
+                            )))
+                        }
+                    }
+                }
+                PatchOperation::AddKey(path, value) | PatchOperation::InsertAt(path, value) => {
+                    patch.push(JsonPatchOperation::Add {
+                        path: locate(&old, path)?.0,
+                        value: value.clone(),
+                    })
+                }
+                PatchOperation::RemoveKey(path)
+                | PatchOperation::RemoveEntry(path, _)
+                | PatchOperation::RemoveAt(path)
+                | PatchOperation::RemoveValueAt(path, _) => {
+                    patch.push(JsonPatchOperation::Remove {
+                        path: locate(&old, path)?.0,
+                    })
+                }
+                // Reordered arrays are always rewritten
+                PatchOperation::Reorder(..) => {}
+            }
+        }
+        Ok(JsonPatch(patch))
+    }
+
+    pub(crate) fn apply_to<T>(
+        &self,
+        config: Config,
+        target: &mut T,
+    ) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        // Patch the serialized representation, then apply the difference it makes to the target
+        let mut document =
+            serde_value::to_value(&*target).map_err(<DeserializerError as de::Error>::custom)?;
+        let mut key_samples = HashMap::new();
+        for operation in &self.0 {
+            apply_operation(&mut document, operation, &mut key_samples)?;
+        }
+        let new = T::deserialize(ValueDeserializer::<DeserializerError>::new(document))?;
+        let patch = config
+            .diff_patch(target, &new)
+            .map_err(<DeserializerError as de::Error>::custom)?;
+        config.apply_patch(&patch, target)
+    }
+}
+
+/// Finds the arrays that are reordered, or whose elements are addressed by key instead of index
+fn rewritten_arrays<'a>(old: &Value, operations: &'a [PatchOperation]) -> Vec<&'a [PathElement]> {
+    let mut arrays: Vec<&[PathElement]> = Vec::new();
+    for operation in operations {
+        let path = operation.path();
+        let array = match operation {
+            PatchOperation::Reorder(..) => Some(path),
+            _ => path
+                .iter()
+                .enumerate()
+                .find_map(|(idx, element)| match element {
+                    PathElement::Key(_) if is_array(old, &path[..idx]) => Some(&path[..idx]),
+                    _ => None,
+                }),
+        };
+        if let Some(array) = array {
+            if !arrays.iter().any(|outer| is_prefix(outer, array)) {
+                arrays.retain(|inner| !is_prefix(array, inner));
+                arrays.push(array);
+            }
+        }
+    }
+    arrays
+}
+
+fn is_array(old: &Value, path: &[PathElement]) -> bool {
+    match locate(old, path) {
+        Ok((_, Some(node))) => matches!(skip_option(skip_newtypes(node)), Value::Seq(_)),
+        _ => false,
+    }
+}
+
+/// Builds the JSON Pointer for a diff path, also returning the part of the old value it points to
+fn locate<'a>(
+    old: &'a Value,
+    path: &[PathElement],
+) -> Result<(String, Option<&'a Value>), SerializerError> {
+    let mut pointer = String::new();
+    let mut node = Some(old);
+    for element in path {
+        node = node.map(skip_newtypes);
+        match element {
+            PathElement::Element(DiffPathElementValue::Field(name))
+            | PathElement::Element(DiffPathElementValue::EnumVariant(name)) => {
+                push_token(&mut pointer, name);
+                node = match node.map(skip_option) {
+                    Some(Value::Map(map)) => map.get(&Value::String(name.to_string())),
+                    // Tuple fields are named by their index
+                    Some(Value::Seq(values)) => {
+                        name.parse::<usize>().ok().and_then(|idx| values.get(idx))
+                    }
+                    _ => None,
+                };
+            }
+            PathElement::Element(DiffPathElementValue::CollectionIndex(idx)) => match node {
+                // An Option's value is serialized in its place
+                Some(Value::Option(value)) => node = value.as_deref(),
+                Some(Value::Seq(values)) => {
+                    push_token(&mut pointer, &idx.to_string());
+                    node = values.get(*idx);
+                }
+                _ => {
+                    push_token(&mut pointer, &idx.to_string());
+                    node = None;
+                }
+            },
+            PathElement::Element(DiffPathElementValue::AddToCollection) => {
+                push_token(&mut pointer, "-");
+                node = None;
+            }
+            PathElement::Element(DiffPathElementValue::FullEnumVariant) => {}
+            PathElement::Element(DiffPathElementValue::FieldIndex(_))
+            | PathElement::Element(DiffPathElementValue::EnumVariantIndex(_)) => {
+                return Err(ser::Error::custom(
+                    "JSON Pointers require field names, not indices",
+                ))
+            }
+            PathElement::Key(key) => {
+                push_token(&mut pointer, &key_token(key)?);
+                node = match node.map(skip_option) {
+                    Some(Value::Map(map)) => map.get(key),
+                    _ => None,
+                };
+            }
+        }
+    }
+    Ok((pointer, node))
+}
+
+pub(crate) fn key_token(key: &Value) -> Result<String, SerializerError> {
+    Ok(match skip_newtypes(key) {
+        Value::String(key) => key.clone(),
+        Value::Char(key) => key.to_string(),
+        Value::Bool(key) => key.to_string(),
+        Value::U8(key) => key.to_string(),
+        Value::U16(key) => key.to_string(),
+        Value::U32(key) => key.to_string(),
+        Value::U64(key) => key.to_string(),
+        Value::I8(key) => key.to_string(),
+        Value::I16(key) => key.to_string(),
+        Value::I32(key) => key.to_string(),
+        Value::I64(key) => key.to_string(),
+        key => {
+            return Err(ser::Error::custom(format!(
+                "map key {:?} can't be used in a JSON Pointer",
+                key
+            )))
+        }
+    })
+}
+
+fn push_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, DeserializerError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(de::Error::custom(format!(
+            "JSON Pointer {:?} must start with '/'",
+            pointer
+        )));
+    }
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+pub(crate) fn skip_newtypes(mut value: &Value) -> &Value {
+    while let Value::Newtype(inner) = value {
+        value = inner;
+    }
+    value
+}
+
+pub(crate) fn skip_option(value: &Value) -> &Value {
+    match value {
+        Value::Option(Some(inner)) => skip_newtypes(inner),
+        value => value,
+    }
+}
+
+// Follows newtypes and present Options down to the value that holds members or elements
+pub(crate) fn container_mut(mut value: &mut Value) -> &mut Value {
+    loop {
+        match value {
+            Value::Newtype(inner) | Value::Option(Some(inner)) => value = inner,
+            value => return value,
+        }
+    }
+}
+
+pub(crate) fn key_matches(key: &Value, token: &str) -> bool {
+    key_token(key).map(|key| key == token).unwrap_or(false)
+}
+
+// New map keys are given the same type as the existing keys, so they deserialize the same way.
+// Maps that are empty and have never held a key get string keys.
+pub(crate) fn new_key(sample: Option<&Value>, token: &str) -> Value {
+    match sample.map(skip_newtypes) {
+        Some(Value::U8(_)) | Some(Value::U16(_)) | Some(Value::U32(_)) | Some(Value::U64(_)) => {
+            token.parse().map(Value::U64).ok()
+        }
+        Some(Value::I8(_)) | Some(Value::I16(_)) | Some(Value::I32(_)) | Some(Value::I64(_)) => {
+            token.parse().map(Value::I64).ok()
+        }
+        Some(Value::Bool(_)) => token.parse().map(Value::Bool).ok(),
+        Some(Value::Char(_)) => token.parse().map(Value::Char).ok(),
+        _ => None,
+    }
+    .unwrap_or_else(|| Value::String(token.to_string()))
+}
+
+fn apply_operation(
+    document: &mut Value,
+    operation: &JsonPatchOperation,
+    key_samples: &mut HashMap<Vec<String>, Value>,
+) -> Result<(), DeserializerError> {
+    let (pointer, value) = match operation {
+        JsonPatchOperation::Add { path, value } | JsonPatchOperation::Replace { path, value } => {
+            (path, Some(value))
+        }
+        JsonPatchOperation::Remove { path } => (path, None),
+    };
+    let tokens = parse_pointer(pointer)?;
+    let (last, parents) = match tokens.split_last() {
+        Some(split) => split,
+        None => {
+            return match value {
+                Some(value) => {
+                    *document = value.clone();
+                    Ok(())
+                }
+                None => Err(de::Error::custom("can't remove the whole document")),
+            }
+        }
+    };
+    let missing = || de::Error::custom(format!("JSON Pointer {:?} doesn't exist", pointer));
+
+    let mut parent = document;
+    for token in parents {
+        parent = match container_mut(parent) {
+            Value::Map(map) => map
+                .iter_mut()
+                .find(|(key, _)| key_matches(key, token))
+                .map(|(_, value)| value)
+                .ok_or_else(missing)?,
+            Value::Seq(values) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(move |idx| values.get_mut(idx))
+                .ok_or_else(missing)?,
+            _ => return Err(missing()),
+        };
+    }
+
+    match container_mut(parent) {
+        Value::Map(map) => {
+            // Remember the type of the keys in case the map is emptied before a key is added
+            if let Some(sample) = map.keys().next() {
+                key_samples.insert(parents.to_vec(), sample.clone());
+            }
+            let key = map.keys().find(|key| key_matches(key, last)).cloned();
+            match (operation, key) {
+                (JsonPatchOperation::Remove { .. }, Some(key)) => {
+                    map.remove(&key);
+                }
+                (JsonPatchOperation::Replace { value, .. }, Some(key))
+                | (JsonPatchOperation::Add { value, .. }, Some(key)) => {
+                    map.insert(key, value.clone());
+                }
+                (JsonPatchOperation::Add { value, .. }, None) => {
+                    let key = new_key(key_samples.get(parents), last);
+                    map.insert(key, value.clone());
+                }
+                (_, None) => return Err(missing()),
+            }
+        }
+        Value::Seq(values) => {
+            let idx = if last == "-" {
+                values.len()
+            } else {
+                last.parse::<usize>().map_err(|_| missing())?
+            };
+            match operation {
+                JsonPatchOperation::Add { value, .. } if idx <= values.len() => {
+                    values.insert(idx, value.clone())
+                }
+                JsonPatchOperation::Replace { value, .. } if idx < values.len() => {
+                    values[idx] = value.clone()
+                }
+                JsonPatchOperation::Remove { .. } if idx < values.len() => {
+                    values.remove(idx);
+                }
+                _ => return Err(missing()),
+            }
+        }
+        _ => return Err(missing()),
+    }
+    Ok(())
+}
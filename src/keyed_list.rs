@@ -0,0 +1,215 @@
+use crate::{
+    apply::ApplyContext,
+    difference::{DiffCommandRef, DiffCommandValue, DiffContext, DiffPathElementValue},
+    ChangeKind, PathElement, SerdeDiff,
+};
+use serde::{de, ser::SerializeSeq, Deserialize, Serialize};
+use std::{collections::HashMap, hash::Hash};
+
+/// Diffs two lists whose elements are identified by a key, such as an `id` field. Used by fields
+/// marked `#[serde_diff(key = "...")]`.
+///
+/// Elements with the same key are diffed in place with EnterKey, wherever they are in the list.
+/// Removed elements are removed by key, new elements are inserted at their index, and a Reorder is
+/// written if the elements that are kept have changed order. Elements whose key is shared with
+/// another element of the same list are removed and inserted like new ones.
+#[doc(hidden)]
+pub fn diff_keyed_list<'a, S, T, K, F>(
+    ctx: &mut DiffContext<'a, S>,
+    old: &[T],
+    new: &[T],
+    key: F,
+) -> Result<bool, S::Error>
+where
+    S: SerializeSeq,
+    T: SerdeDiff + Serialize,
+    K: Serialize + Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    let old_keys = unique_keys(old, &key);
+    let new_keys = unique_keys(new, &key);
+    let is_kept = |k: &K| {
+        matches!(old_keys.get(k), Some(Some(_))) && matches!(new_keys.get(k), Some(Some(_)))
+    };
+    let mut changed = false;
+
+    // Removals go from the back, so the indices of the elements before them stay valid
+    for (idx, item) in old.iter().enumerate().rev() {
+        let k = key(item);
+        if is_kept(k) {
+            continue;
+        }
+        if ctx.is_invertible() {
+            ctx.save_command::<()>(&DiffCommandRef::RemoveValueAt(idx), true, true)?;
+            ctx.save_command(&DiffCommandRef::Value(item), true, true)?;
+        } else if let Some(Some(_)) = old_keys.get(k) {
+            ctx.save_command(&DiffCommandRef::RemoveKey(k), true, true)?;
+        } else {
+            ctx.save_command::<()>(&DiffCommandRef::RemoveAt(idx), true, true)?;
+        }
+        changed = true;
+    }
+
+    for (self_value, k) in old.iter().map(|item| (item, key(item))) {
+        if let Some(Some(new_idx)) = new_keys.get(k).filter(|_| is_kept(k)) {
+            let save_closure =
+                |serializer: &mut S| serializer.serialize_element(&DiffCommandRef::EnterKey(k));
+            let mut subctx = ctx.reborrow();
+            subctx.push_field_element(&save_closure);
+            if <T as SerdeDiff>::diff(self_value, &mut subctx, &new[*new_idx])? {
+                changed = true;
+            }
+            subctx.pop_path_element()?;
+        }
+    }
+
+    // The kept elements are still in their old order, which is moved into their new order
+    let kept_positions: HashMap<&K, usize> = old
+        .iter()
+        .map(&key)
+        .filter(|k| is_kept(k))
+        .enumerate()
+        .map(|(position, k)| (k, position))
+        .collect();
+    let order: Vec<usize> = new
+        .iter()
+        .filter_map(|item| kept_positions.get(key(item)).copied())
+        .collect();
+    if order
+        .iter()
+        .enumerate()
+        .any(|(idx, previous)| idx != *previous)
+    {
+        ctx.save_command::<()>(&DiffCommandRef::Reorder(&order), true, true)?;
+        changed = true;
+    }
+
+    // Insertions go from the front, so each element lands at its final index
+    for (idx, item) in new.iter().enumerate() {
+        if !is_kept(key(item)) {
+            ctx.save_command::<()>(&DiffCommandRef::InsertAt(idx), true, true)?;
+            ctx.save_command(&DiffCommandRef::Value(item), true, true)?;
+            changed = true;
+        }
+    }
+
+    if changed {
+        ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+    }
+    Ok(changed)
+}
+
+/// Applies the commands written by `diff_keyed_list`
+#[doc(hidden)]
+pub fn apply_keyed_list<'de, A, T, K, F>(
+    list: &mut Vec<T>,
+    seq: &mut A,
+    ctx: &mut ApplyContext,
+    key: F,
+) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+where
+    A: de::SeqAccess<'de>,
+    T: SerdeDiff + Serialize + for<'b> Deserialize<'b>,
+    K: Serialize + for<'b> Deserialize<'b> + PartialEq,
+    F: Fn(&T) -> &K,
+{
+    let position = |list: &[T], k: &K| list.iter().position(|item| key(item) == k);
+    let mut changed = false;
+    while let Some(cmd) = ctx.read_next_command::<A, K>(seq)? {
+        use DiffCommandValue::*;
+        match cmd {
+            EnterKey(k) => match position(list, &k) {
+                Some(idx) => changed |= <T as SerdeDiff>::apply(&mut list[idx], seq, ctx)?,
+                None => ctx.skip_value(seq)?,
+            },
+            RemoveKey(k) => match position(list, &k) {
+                Some(idx) => {
+                    ctx.record_key_change(ChangeKind::Removed, &k)?;
+                    list.remove(idx);
+                    changed = true;
+                }
+                None => ctx.unmatched_key(&k)?,
+            },
+            RemoveAt(idx) => {
+                if idx < list.len() {
+                    ctx.record_change(
+                        ChangeKind::Removed,
+                        Some(PathElement::Element(DiffPathElementValue::CollectionIndex(
+                            idx,
+                        ))),
+                    );
+                    list.remove(idx);
+                    changed = true;
+                } else {
+                    ctx.unmatched_element(PathElement::Element(
+                        DiffPathElementValue::CollectionIndex(idx),
+                    ))?;
+                }
+            }
+            // The element at the index is removed if it has the removed key, which it does
+            // unless other elements have been added or removed since the diff
+            RemoveValueAt(idx) => {
+                let removed: T = ctx.read_expected_value(seq, "RemoveValueAt")?;
+                let k = key(&removed);
+                let idx = match list.get(idx) {
+                    Some(item) if key(item) == k => Some(idx),
+                    _ => position(list, k),
+                };
+                match idx {
+                    Some(idx) => {
+                        ctx.record_exited_change(ChangeKind::Removed, None);
+                        list.remove(idx);
+                        changed = true;
+                    }
+                    None => ctx.unmatched_exited_element()?,
+                }
+            }
+            InsertAt(idx) => {
+                let v: T = ctx.read_expected_value(seq, "InsertAt")?;
+                if idx <= list.len() {
+                    ctx.record_exited_change(ChangeKind::Added, None);
+                    list.insert(idx, v);
+                    changed = true;
+                } else {
+                    ctx.unmatched_exited_element()?;
+                }
+            }
+            Reorder(order) => {
+                if is_permutation(&order, list.len()) {
+                    let mut items: Vec<Option<T>> = list.drain(..).map(Some).collect();
+                    list.extend(order.iter().filter_map(|idx| items[*idx].take()));
+                    ctx.record_change(ChangeKind::Modified, None);
+                    changed = true;
+                } else {
+                    ctx.unmatched_current()?;
+                }
+            }
+            // Elements are only entered by key
+            Enter(_) => ctx.skip_value(seq)?,
+            _ => break,
+        }
+    }
+    Ok(changed)
+}
+
+/// Maps each key to the index of its element, or to `None` if several elements share it
+fn unique_keys<'t, T, K: Hash + Eq>(
+    list: &'t [T],
+    key: &impl Fn(&T) -> &K,
+) -> HashMap<&'t K, Option<usize>> {
+    let mut keys = HashMap::with_capacity(list.len());
+    for (idx, item) in list.iter().enumerate() {
+        keys.entry(key(item))
+            .and_modify(|existing| *existing = None)
+            .or_insert(Some(idx));
+    }
+    keys
+}
+
+fn is_permutation(order: &[usize], len: usize) -> bool {
+    let mut seen = vec![false; len];
+    order.len() == len
+        && order
+            .iter()
+            .all(|idx| idx < &len && !std::mem::replace(&mut seen[*idx], true))
+}
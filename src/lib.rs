@@ -17,20 +17,44 @@ pub use serde_diff_derive::SerdeDiff;
 
 #[doc(hidden)]
 pub(crate) mod apply;
+pub(crate) mod buffered_serializer;
 pub(crate) mod config;
 pub(crate) mod counting_serializer;
 #[doc(hidden)]
 pub(crate) mod difference;
+#[cfg(any(feature = "serde_json", feature = "toml"))]
+pub(crate) mod dynamic_value;
+pub(crate) mod edit_script;
+pub(crate) mod fingerprint;
+pub(crate) mod framing;
 pub(crate) mod implementation;
+pub(crate) mod json_patch;
+pub(crate) mod keyed_list;
+pub(crate) mod merge;
+pub(crate) mod merge_patch;
+pub(crate) mod patch;
+pub(crate) mod render;
+pub(crate) mod report;
+pub(crate) mod serialized;
 
-pub use apply::Apply;
+pub use apply::{Apply, ApplyError, ApplyFailure};
 pub use config::Config;
 pub use difference::Diff;
+pub use json_patch::{JsonPatch, JsonPatchOperation};
+pub use merge::{Merge, MergeConflict, MergeError};
+pub use merge_patch::JsonMergePatch;
+pub use patch::{DiffPatch, PatchOperation, PathElement};
+pub use render::RenderStyle;
+pub use report::{AppliedChange, ApplyReport, ChangeKind};
+pub use serde_value;
+pub use serialized::Serialized;
 
 // Used by the proc_macro
 pub use apply::ApplyContext;
 pub use difference::DiffContext;
 pub use difference::DiffPathElementValue;
+pub use fingerprint::Fingerprint;
+pub use keyed_list::{apply_keyed_list, diff_keyed_list};
 
 // NEXT STEPS:
 // - Decouple from serde_json as much as possible. We might need to use a "stream" format with
@@ -46,6 +70,8 @@ pub use difference::DiffPathElementValue;
 // To do so, we need to implement the serde::ser::Serializer trait and
 // make the implementation only count up every time an element is serialized, doing nothing else.
 // This is implemented as CountingSerializer
+// Counting diffs the values twice, so Config::with_buffered_serialization can instead buffer the
+// commands as BufferedSeq in one pass and write them once their number is known.
 
 /// Anything diffable implements this trait
 pub trait SerdeDiff {
@@ -66,6 +92,18 @@ pub trait SerdeDiff {
     ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
     where
         A: de::SeqAccess<'de>;
+
+    /// A fingerprint of the type's structure, which diffs can start with so that applying them
+    /// to a different type fails instead of skipping the paths it doesn't have. Derived impls
+    /// combine the type's name with the names, tags and fingerprints of its fields. The default
+    /// is the same for every type that doesn't override it, so manual impls should at least start
+    /// a `Fingerprint` with a name of their own.
+    fn fingerprint() -> u64
+    where
+        Self: Sized,
+    {
+        Fingerprint::new("unknown").finish()
+    }
 }
 
 /// Configures how to serialize field identifiers
@@ -73,8 +111,27 @@ pub trait SerdeDiff {
 pub enum FieldPathMode {
     /// Use the field's string name as its identifier
     Name,
-    /// Use the field's index in the struct as its identifier
+    /// Use the field's index in the struct as its identifier, and the variant's index in the enum
+    /// for enum variants. Smaller than names, but the diff only applies to types whose fields and
+    /// variants are in the same order. Fields and variants tagged with `#[serde_diff(id = N)]`
+    /// use their tag instead. Diffs in any mode can be applied.
     Index,
+    /// Use the `#[serde_diff(id = N)]` tag of fields and variants that have one as their
+    /// identifier, and the name of those that don't. Tags stay the same when fields are
+    /// reordered or added, so stored diffs keep applying as the type changes.
+    Tag,
+}
+
+/// Configures how `Vec`s are compared
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VecDiffMode {
+    /// Compare the elements at each index, then append or remove elements at the end. Inserting
+    /// or removing an element anywhere else changes every element after it.
+    ByIndex,
+    /// Compute a minimal edit script of insertions and removals at any index, with the elements
+    /// that are kept compared in place. Vecs that differ in too many places to compute an edit
+    /// script cheaply are compared by index instead.
+    EditScript,
 }
 
 pub(crate) enum ElementStackEntry<'a, S: SerializeSeq> {
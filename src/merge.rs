@@ -0,0 +1,227 @@
+use crate::patch::is_prefix;
+use crate::{
+    difference::DiffPathElementValue,
+    patch::{PatchOperation, PathElement},
+    Config, DiffPatch, SerdeDiff,
+};
+use serde_value::{DeserializerError, SerializerError};
+
+/// The result of a three-way merge of two values against a common base
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_diff::{SerdeDiff, Merge};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Clone, Debug)]
+/// struct Test {
+///     a: i32,
+///     b: i32,
+/// }
+/// let base = Test { a: 1, b: 1 };
+/// let ours = Test { a: 2, b: 1 };
+/// let theirs = Test { a: 1, b: 3 };
+/// let merge = Merge::new(&base, &ours, &theirs).expect("failed to merge");
+/// assert!(!merge.has_conflicts());
+/// assert_eq!(merge.merged, Test { a: 2, b: 3 });
+/// ```
+#[derive(Debug)]
+pub struct Merge<T> {
+    /// The base value with all non-conflicting changes from both sides applied. Where the sides
+    /// conflict, our changes are kept.
+    pub merged: T,
+    /// Places where both sides made incompatible changes
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Incompatible changes made by both sides of a merge
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConflict {
+    /// Path to the part of the value that both sides changed
+    pub path: Vec<PathElement>,
+    /// The conflicting changes made by our side, relative to the base
+    pub ours: DiffPatch,
+    /// The conflicting changes made by their side, relative to the base
+    pub theirs: DiffPatch,
+}
+
+/// Errors that can occur while merging
+#[derive(Debug)]
+pub enum MergeError {
+    /// Failed to compute the difference between the base and one of the sides
+    Diff(SerializerError),
+    /// Failed to apply the merged changes to the base
+    Apply(DeserializerError),
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::Diff(e) => write!(f, "failed to diff merge input: {}", e),
+            MergeError::Apply(e) => write!(f, "failed to apply merged changes: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MergeError::Diff(e) => Some(e),
+            MergeError::Apply(e) => Some(e),
+        }
+    }
+}
+
+impl<T: SerdeDiff + Clone> Merge<T> {
+    /// Merges the changes from base to ours and from base to theirs
+    pub fn new(base: &T, ours: &T, theirs: &T) -> Result<Self, MergeError> {
+        Config::default().merge(base, ours, theirs)
+    }
+
+    /// True if any of the changes could not be merged automatically
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    pub(crate) fn compute(
+        config: Config,
+        base: &T,
+        ours: &T,
+        theirs: &T,
+    ) -> Result<Self, MergeError> {
+        let ours_ops = config
+            .diff_patch(base, ours)
+            .map_err(MergeError::Diff)?
+            .operations()
+            .map_err(MergeError::Apply)?;
+        let theirs_ops = config
+            .diff_patch(base, theirs)
+            .map_err(MergeError::Diff)?
+            .operations()
+            .map_err(MergeError::Apply)?;
+
+        let mut merged_ops: Vec<&PatchOperation> = ours_ops.iter().collect();
+        // Each group holds the conflicting path and the indices of the operations involved
+        let mut groups: Vec<(&[PathElement], Vec<usize>, Vec<usize>)> = Vec::new();
+        for (theirs_idx, theirs_op) in theirs_ops.iter().enumerate() {
+            // Identical changes made by both sides are not a conflict
+            if ours_ops.contains(theirs_op) {
+                continue;
+            }
+            let theirs_footprint = Footprint::of(theirs_op);
+            let overlapping: Vec<usize> = ours_ops
+                .iter()
+                .enumerate()
+                .filter(|(_, ours_op)| Footprint::of(ours_op).overlaps(&theirs_footprint))
+                .map(|(idx, _)| idx)
+                .collect();
+            if overlapping.is_empty() {
+                merged_ops.push(theirs_op);
+                continue;
+            }
+
+            // The conflict is reported at the outermost path touched by any of the operations
+            let path = overlapping
+                .iter()
+                .map(|idx| Footprint::of(&ours_ops[*idx]).path)
+                .chain(std::iter::once(theirs_footprint.path))
+                .min_by_key(|path| path.len())
+                .unwrap();
+            match groups.iter_mut().find(|(group_path, _, _)| {
+                is_prefix(group_path, path) || is_prefix(path, group_path)
+            }) {
+                Some((group_path, group_ours, group_theirs)) => {
+                    if path.len() < group_path.len() {
+                        *group_path = path;
+                    }
+                    for idx in overlapping {
+                        if !group_ours.contains(&idx) {
+                            group_ours.push(idx);
+                        }
+                    }
+                    group_theirs.push(theirs_idx);
+                }
+                None => groups.push((path, overlapping, vec![theirs_idx])),
+            }
+        }
+
+        let mut merged = base.clone();
+        config
+            .apply_patch(&DiffPatch::from_operations(merged_ops), &mut merged)
+            .map_err(MergeError::Apply)?;
+
+        let conflicts = groups
+            .into_iter()
+            .map(|(path, mut ours_idx, theirs_idx)| {
+                ours_idx.sort_unstable();
+                MergeConflict {
+                    path: path.to_vec(),
+                    ours: DiffPatch::from_operations(ours_idx.iter().map(|idx| &ours_ops[*idx])),
+                    theirs: DiffPatch::from_operations(
+                        theirs_idx.iter().map(|idx| &theirs_ops[*idx]),
+                    ),
+                }
+            })
+            .collect();
+        Ok(Merge { merged, conflicts })
+    }
+}
+
+/// The part of a value an operation may change
+struct Footprint<'a> {
+    path: &'a [PathElement],
+    /// True if the operation only appends to the end of the collection at `path`, leaving the
+    /// existing elements alone
+    tail: bool,
+}
+
+impl<'a> Footprint<'a> {
+    fn of(operation: &'a PatchOperation) -> Self {
+        match operation {
+            PatchOperation::Value(path, _) | PatchOperation::Replace(path, _, _) => match path
+                .split_last()
+            {
+                Some((PathElement::Element(DiffPathElementValue::AddToCollection), container)) => {
+                    Footprint {
+                        path: container,
+                        tail: true,
+                    }
+                }
+                // Replacing the whole variant touches every field of the enum
+                Some((PathElement::Element(DiffPathElementValue::FullEnumVariant), container)) => {
+                    Footprint {
+                        path: container,
+                        tail: false,
+                    }
+                }
+                _ => Footprint { path, tail: false },
+            },
+            // The patch does not record how long the collection is, so we can't tell which
+            // elements are removed. Assume the whole collection is affected.
+            PatchOperation::Remove(path, _) | PatchOperation::RemoveValues(path, _) => {
+                Footprint { path, tail: false }
+            }
+            PatchOperation::AddKey(path, _)
+            | PatchOperation::RemoveKey(path)
+            | PatchOperation::RemoveEntry(path, _) => Footprint { path, tail: false },
+            // Reordering moves every element of the collection
+            PatchOperation::Reorder(path, _) => Footprint { path, tail: false },
+            // Inserting or removing an element moves every element after it
+            PatchOperation::InsertAt(path, _)
+            | PatchOperation::RemoveAt(path)
+            | PatchOperation::RemoveValueAt(path, _) => Footprint {
+                path: &path[..path.len().saturating_sub(1)],
+                tail: false,
+            },
+        }
+    }
+
+    fn overlaps(&self, other: &Footprint) -> bool {
+        match (self.tail, other.tail) {
+            (false, false) => is_prefix(self.path, other.path) || is_prefix(other.path, self.path),
+            (false, true) => is_prefix(self.path, other.path),
+            (true, false) => is_prefix(other.path, self.path),
+            (true, true) => self.path == other.path,
+        }
+    }
+}
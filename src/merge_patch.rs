@@ -0,0 +1,271 @@
+use crate::{
+    difference::DiffPathElementValue,
+    json_patch::{container_mut, key_matches, key_token, new_key, skip_newtypes, skip_option},
+    patch::PathElement,
+    Config, FieldPathMode, SerdeDiff,
+};
+use serde::{de, ser, Deserialize, Serialize};
+use serde_value::{DeserializerError, SerializerError, Value, ValueDeserializer};
+use std::collections::BTreeMap;
+
+/// An RFC 7386 JSON Merge Patch: an object holding the members that changed, where `null`
+/// removes a member and nested objects are merged recursively.
+///
+/// Merge patches follow the serialized representation of the value, like `JsonPatch`. They can't
+/// address array elements, so a sequence or tuple that changed is replaced as a whole. A member
+/// that becomes `None` is removed, which deserializes back to `None` for `Option` fields. A
+/// replaced enum variant removes the old variant and adds the new one.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_diff::{SerdeDiff, JsonMergePatch};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug)]
+/// struct Test {
+///     a: i32,
+///     b: Vec<i32>,
+///     c: Option<String>,
+/// }
+/// let old = Test { a: 3, b: vec![1, 2], c: Some("c".to_string()) };
+/// let new = Test { a: 3, b: vec![1], c: None };
+/// let patch = JsonMergePatch::new(&old, &new).expect("failed to diff");
+/// assert_eq!(serde_json::to_string(&patch).unwrap(), r#"{"b":[1],"c":null}"#);
+/// let mut target = old;
+/// patch.apply(&mut target).expect("failed to apply patch");
+/// assert_eq!(target, new);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonMergePatch(pub Value);
+
+impl Default for JsonMergePatch {
+    fn default() -> Self {
+        JsonMergePatch(Value::Map(BTreeMap::new()))
+    }
+}
+
+impl JsonMergePatch {
+    /// Computes the differences between the old and new value as a JSON Merge Patch
+    pub fn new<T: SerdeDiff + Serialize>(old: &T, new: &T) -> Result<Self, SerializerError> {
+        Config::default().json_merge_patch(old, new)
+    }
+
+    /// Merges the patch into the target
+    pub fn apply<T>(&self, target: &mut T) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        Config::default().apply_json_merge_patch(self, target)
+    }
+
+    /// Returns true if the patch doesn't change anything
+    pub fn is_empty(&self) -> bool {
+        match &self.0 {
+            Value::Map(members) => members.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn compute<T: SerdeDiff + Serialize>(
+        config: Config,
+        old: &T,
+        new: &T,
+    ) -> Result<Self, SerializerError> {
+        // The diff only decides which members changed, their contents are taken from the new value
+        let operations = config
+            .with_invertible(true)
+            .with_field_path_mode(FieldPathMode::Name)
+            .with_serialized_names(true)
+            .diff_patch(old, new)?
+            .operations()
+            .map_err(ser::Error::custom)?;
+        let old = serde_value::to_value(old)?;
+        let new = serde_value::to_value(new)?;
+        let mut patch = JsonMergePatch::default();
+        for operation in &operations {
+            let location = locate(&old, &new, operation.path())?;
+            let value = match location.new {
+                Some(new) => replacement(location.old, new),
+                None => Value::Unit,
+            };
+            insert(&mut patch.0, &location.members, value);
+        }
+        Ok(patch)
+    }
+
+    pub(crate) fn apply_to<T>(
+        &self,
+        config: Config,
+        target: &mut T,
+    ) -> Result<(), DeserializerError>
+    where
+        T: SerdeDiff + Serialize + for<'de> Deserialize<'de>,
+    {
+        // Merge into the serialized representation, then apply the difference it makes to the
+        // target
+        let mut document =
+            serde_value::to_value(&*target).map_err(<DeserializerError as de::Error>::custom)?;
+        merge(&mut document, &self.0);
+        let new = T::deserialize(ValueDeserializer::<DeserializerError>::new(document))?;
+        let patch = config
+            .diff_patch(target, &new)
+            .map_err(<DeserializerError as de::Error>::custom)?;
+        config.apply_patch(&patch, target)
+    }
+}
+
+/// The part of a value that a diff operation changes
+struct Location<'a> {
+    /// Object members leading to the changed part
+    members: Vec<Value>,
+    old: Option<&'a Value>,
+    new: Option<&'a Value>,
+}
+
+/// Finds the object members leading to the part of the value a diff path changes, also returning
+/// that part of the old and new value. The path is cut short where it enters an array or replaces
+/// an enum variant.
+fn locate<'a>(
+    old: &'a Value,
+    new: &'a Value,
+    path: &[PathElement],
+) -> Result<Location<'a>, SerializerError> {
+    let mut members = Vec::new();
+    let (mut old, mut new) = (Some(old), Some(new));
+    for element in path {
+        old = old.map(skip_newtypes);
+        new = new.map(skip_newtypes);
+        let member = match element {
+            PathElement::Element(DiffPathElementValue::Field(name))
+            | PathElement::Element(DiffPathElementValue::EnumVariant(name)) => {
+                Value::String(name.to_string())
+            }
+            PathElement::Key(key) => key.clone(),
+            PathElement::Element(DiffPathElementValue::CollectionIndex(_))
+                if matches!(old, Some(Value::Option(_)))
+                    || matches!(new, Some(Value::Option(_))) =>
+            {
+                // An Option's value is serialized in its place
+                old = old.and_then(option_value);
+                new = new.and_then(option_value);
+                continue;
+            }
+            PathElement::Element(DiffPathElementValue::FieldIndex(_))
+            | PathElement::Element(DiffPathElementValue::EnumVariantIndex(_)) => {
+                return Err(ser::Error::custom(
+                    "JSON Merge Patches require field names, not indices",
+                ))
+            }
+            _ => break,
+        };
+        // Tuples are serialized as arrays
+        if matches!(old.map(skip_option), Some(Value::Seq(_)))
+            || matches!(new.map(skip_option), Some(Value::Seq(_)))
+        {
+            break;
+        }
+        old = member_of(old, &member);
+        new = member_of(new, &member);
+        members.push(member);
+    }
+    Ok(Location { members, old, new })
+}
+
+fn option_value(node: &Value) -> Option<&Value> {
+    match node {
+        Value::Option(value) => value.as_deref(),
+        node => Some(node),
+    }
+}
+
+fn member_of<'a>(node: Option<&'a Value>, member: &Value) -> Option<&'a Value> {
+    match node.map(skip_option) {
+        Some(Value::Map(map)) => map.get(member),
+        _ => None,
+    }
+}
+
+/// Builds the patch value that turns the old value into the new one. Objects are merged rather
+/// than replaced, so members that only exist in the old value have to be removed explicitly.
+fn replacement(old: Option<&Value>, new: &Value) -> Value {
+    let old = old.map(|old| skip_option(skip_newtypes(old)));
+    match (old, skip_option(skip_newtypes(new))) {
+        (Some(Value::Map(old)), Value::Map(new)) => {
+            let mut members = BTreeMap::new();
+            for (key, value) in new {
+                members.insert(key.clone(), replacement(old.get(key), value));
+            }
+            for key in old.keys() {
+                if !new.contains_key(key) {
+                    members.insert(key.clone(), Value::Unit);
+                }
+            }
+            Value::Map(members)
+        }
+        _ => new.clone(),
+    }
+}
+
+fn insert(patch: &mut Value, members: &[Value], value: Value) {
+    match members.split_first() {
+        None => *patch = value,
+        Some((member, rest)) => {
+            // Otherwise a parent has already been replaced with its new value
+            if let Value::Map(map) = patch {
+                let child = map
+                    .entry(member.clone())
+                    .or_insert_with(|| Value::Map(BTreeMap::new()));
+                insert(child, rest, value);
+            }
+        }
+    }
+}
+
+/// The MergePatch algorithm from RFC 7386
+fn merge(target: &mut Value, patch: &Value) {
+    let patch = match skip_newtypes(patch) {
+        Value::Option(Some(value)) => skip_newtypes(value),
+        patch => patch,
+    };
+    let members = match patch {
+        Value::Map(members) => members,
+        patch => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    let target = container_mut(target);
+    if !matches!(target, Value::Map(_)) {
+        *target = Value::Map(BTreeMap::new());
+    }
+    if let Value::Map(map) = target {
+        // New keys are given the type of the existing ones, as patches read from JSON only have
+        // string keys
+        let sample = map.keys().next().cloned();
+        for (key, value) in members {
+            let existing = find_key(map, key);
+            if matches!(value, Value::Unit | Value::Option(None)) {
+                if let Some(existing) = existing {
+                    map.remove(&existing);
+                }
+                continue;
+            }
+            let key = existing.unwrap_or_else(|| match (key, &sample) {
+                (Value::String(token), Some(_)) => new_key(sample.as_ref(), token),
+                (key, _) => key.clone(),
+            });
+            merge(map.entry(key).or_insert(Value::Unit), value);
+        }
+    }
+}
+
+fn find_key(map: &BTreeMap<Value, Value>, key: &Value) -> Option<Value> {
+    if map.contains_key(key) {
+        return Some(key.clone());
+    }
+    let token = key_token(key).ok()?;
+    map.keys()
+        .find(|existing| key_matches(existing, &token))
+        .cloned()
+}
@@ -0,0 +1,755 @@
+use crate::{
+    difference::{DiffCommandRef, DiffCommandValue, DiffPathElementValue},
+    Config, SerdeDiff,
+};
+use serde::{de, ser, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use serde_value::{DeserializerError, SerializerError, Value, ValueDeserializer};
+
+/// An owned, format-independent diff between two values.
+///
+/// Where `Diff` borrows the old and new values and writes diff commands straight into a
+/// serializer, a `DiffPatch` owns the commands, holding every value as a `serde_value::Value`.
+/// It can be kept in memory, inspected, applied to any number of targets and serialized later
+/// with any serde format. A serialized `DiffPatch` is identical to a serialized `Diff` of the same
+/// values, so it can also be applied with `Apply`.
+///
+/// Deserializing a `DiffPatch` requires a self-describing format, as the values it contains are
+/// read without knowing their type.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_diff::{SerdeDiff, DiffPatch};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq)]
+/// struct Test {
+///     a: i32,
+/// }
+/// let patch = DiffPatch::new(&Test { a: 3 }, &Test { a: 5 }).expect("failed to diff");
+/// let mut target = Test { a: 4 };
+/// patch.apply(&mut target).expect("failed to apply patch");
+/// assert_eq!(target.a, 5);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffPatch {
+    pub(crate) commands: Vec<DiffCommandValue<'static, Value>>,
+}
+
+impl DiffPatch {
+    /// Computes the differences between the old and new value into an owned patch
+    pub fn new<T: SerdeDiff>(old: &T, new: &T) -> Result<Self, SerializerError> {
+        Config::default().diff_patch(old, new)
+    }
+
+    /// Applies the patch to the target
+    pub fn apply<T: SerdeDiff>(&self, target: &mut T) -> Result<(), DeserializerError> {
+        Config::default().apply_patch(self, target)
+    }
+
+    /// True if the patch contains any changes
+    pub fn has_changes(&self) -> bool {
+        self.commands.iter().any(|command| {
+            !matches!(
+                command,
+                DiffCommandValue::Exit | DiffCommandValue::Fingerprint(_)
+            )
+        })
+    }
+
+    /// Creates the patch that undoes this one, turning the new value back into the old value.
+    ///
+    /// Only patches created with `Config::with_invertible` record the values they replace and
+    /// remove. Returns `None` if the patch is missing the information needed to invert it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use serde_diff::{SerdeDiff, Config};
+    /// use serde::{Serialize, Deserialize};
+    /// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug)]
+    /// struct Test {
+    ///     a: i32,
+    ///     b: Vec<i32>,
+    /// }
+    /// let old = Test { a: 3, b: vec![1, 2] };
+    /// let new = Test { a: 5, b: vec![1] };
+    /// let patch = Config::new()
+    ///     .with_invertible(true)
+    ///     .diff_patch(&old, &new)
+    ///     .expect("failed to diff");
+    /// let mut target = Test { a: 5, b: vec![1] };
+    /// patch.invert().unwrap().apply(&mut target).expect("failed to undo");
+    /// assert_eq!(target, old);
+    /// ```
+    pub fn invert(&self) -> Option<DiffPatch> {
+        let operations = self.operations().ok()?;
+        // Operations are undone in reverse order, as insertions and removals at an index move the
+        // elements after it. Each group holds the operations undoing one or more operations.
+        let mut groups = Vec::with_capacity(operations.len());
+        let mut iter = operations.into_iter().peekable();
+        while let Some(operation) = iter.next() {
+            let mut inverted = Vec::new();
+            match operation {
+                PatchOperation::Replace(path, old, new) => {
+                    inverted.push(PatchOperation::Replace(path, new, old))
+                }
+                // Values appended to the same collection are removed again in one go
+                PatchOperation::Value(path, value) if is_append(&path) => {
+                    let container = path[..path.len() - 1].to_vec();
+                    let mut values = vec![value];
+                    while let Some(PatchOperation::Value(next_path, _)) = iter.peek() {
+                        if !is_append(next_path)
+                            || next_path[..next_path.len() - 1] != container[..]
+                        {
+                            break;
+                        }
+                        if let Some(PatchOperation::Value(_, value)) = iter.next() {
+                            values.push(value);
+                        }
+                    }
+                    inverted.push(PatchOperation::RemoveValues(container, values));
+                }
+                PatchOperation::RemoveValues(path, values) => {
+                    let mut element_path = path;
+                    element_path.push(PathElement::Element(DiffPathElementValue::AddToCollection));
+                    inverted.extend(
+                        values
+                            .into_iter()
+                            .map(|value| PatchOperation::Value(element_path.clone(), value)),
+                    );
+                }
+                PatchOperation::AddKey(path, value) => {
+                    inverted.push(PatchOperation::RemoveEntry(path, value))
+                }
+                PatchOperation::RemoveEntry(path, value) => {
+                    inverted.push(PatchOperation::AddKey(path, value))
+                }
+                PatchOperation::InsertAt(path, value) => {
+                    inverted.push(PatchOperation::RemoveValueAt(path, value))
+                }
+                PatchOperation::RemoveValueAt(path, value) => {
+                    inverted.push(PatchOperation::InsertAt(path, value))
+                }
+                PatchOperation::Reorder(path, order) => {
+                    let mut inverse = vec![0; order.len()];
+                    for (idx, previous) in order.into_iter().enumerate() {
+                        *inverse.get_mut(previous)? = idx;
+                    }
+                    inverted.push(PatchOperation::Reorder(path, inverse))
+                }
+                // The old value was not recorded
+                PatchOperation::Value(..)
+                | PatchOperation::Remove(..)
+                | PatchOperation::RemoveKey(..)
+                | PatchOperation::RemoveAt(..) => return None,
+            }
+            groups.push(inverted);
+        }
+        let mut inverted = DiffPatch::from_operations(groups.iter().rev().flatten());
+        // The inverted patch applies to the same type, so it keeps the fingerprint
+        if let Some(fingerprint @ DiffCommandValue::Fingerprint(_)) = self.commands.first() {
+            inverted.commands.insert(0, fingerprint.clone());
+        }
+        Some(inverted)
+    }
+}
+
+/// True if the path ends by appending to a collection
+pub(crate) fn is_append(path: &[PathElement]) -> bool {
+    matches!(
+        path.last(),
+        Some(PathElement::Element(DiffPathElementValue::AddToCollection))
+    )
+}
+
+/// A step along the path from the root of a value to a part of it touched by a patch
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathElement {
+    /// A struct field, enum variant or collection element
+    Element(DiffPathElementValue<'static>),
+    /// A key in a map
+    Key(Value),
+}
+
+impl std::fmt::Display for PathElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathElement::Element(DiffPathElementValue::Field(name))
+            | PathElement::Element(DiffPathElementValue::EnumVariant(name)) => f.write_str(name),
+            PathElement::Element(DiffPathElementValue::FieldIndex(idx)) => write!(f, "{}", idx),
+            PathElement::Element(DiffPathElementValue::FullEnumVariant) => f.write_str("<variant>"),
+            PathElement::Element(DiffPathElementValue::EnumVariantIndex(idx)) => {
+                write!(f, "<variant {}>", idx)
+            }
+            PathElement::Element(DiffPathElementValue::CollectionIndex(idx)) => {
+                write!(f, "[{}]", idx)
+            }
+            PathElement::Element(DiffPathElementValue::AddToCollection) => f.write_str("[+]"),
+            PathElement::Key(Value::String(key)) => write!(f, "[{:?}]", key),
+            PathElement::Key(key) => match key {
+                Value::Bool(v) => write!(f, "[{}]", v),
+                Value::U8(v) => write!(f, "[{}]", v),
+                Value::U16(v) => write!(f, "[{}]", v),
+                Value::U32(v) => write!(f, "[{}]", v),
+                Value::U64(v) => write!(f, "[{}]", v),
+                Value::I8(v) => write!(f, "[{}]", v),
+                Value::I16(v) => write!(f, "[{}]", v),
+                Value::I32(v) => write!(f, "[{}]", v),
+                Value::I64(v) => write!(f, "[{}]", v),
+                Value::Char(v) => write!(f, "[{:?}]", v),
+                key => write!(f, "[{:?}]", key),
+            },
+        }
+    }
+}
+
+/// Formats a path as `field.inner[3]["key"]`, or `<root>` if it is empty
+pub(crate) struct DisplayPath<'a>(pub(crate) &'a [PathElement]);
+
+impl std::fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("<root>");
+        }
+        for (idx, element) in self.0.iter().enumerate() {
+            let is_index = matches!(
+                element,
+                PathElement::Key(_)
+                    | PathElement::Element(DiffPathElementValue::CollectionIndex(_))
+                    | PathElement::Element(DiffPathElementValue::AddToCollection)
+            );
+            if idx > 0 && !is_index {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", element)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single change made by a patch, addressed by its full path from the root
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchOperation {
+    /// Sets the value at the path. If the last path element is `AddToCollection`, the value is
+    /// appended to the collection instead.
+    Value(Vec<PathElement>, Value),
+    /// Removes a number of elements from the end of the collection at the path
+    Remove(Vec<PathElement>, usize),
+    /// Inserts a value into a map. The last path element is the new key.
+    AddKey(Vec<PathElement>, Value),
+    /// Removes an entry from a map. The last path element is the removed key.
+    RemoveKey(Vec<PathElement>),
+    /// Replaces the old value at the path with the new one
+    Replace(Vec<PathElement>, Value, Value),
+    /// Removes the given values from the end of the collection at the path
+    RemoveValues(Vec<PathElement>, Vec<Value>),
+    /// Removes an entry holding the given value from a map. The last path element is the removed
+    /// key.
+    RemoveEntry(Vec<PathElement>, Value),
+    /// Inserts a value into a collection, shifting the elements after it. The last path element is
+    /// the index of the new element.
+    InsertAt(Vec<PathElement>, Value),
+    /// Removes an element from a collection, shifting the elements after it. The last path element
+    /// is the index of the removed element.
+    RemoveAt(Vec<PathElement>),
+    /// Removes an element holding the given value from a collection. The last path element is the
+    /// index of the removed element.
+    RemoveValueAt(Vec<PathElement>, Value),
+    /// Reorders the elements of the collection at the path, so the element at index i is the one
+    /// that was at index `order[i]`
+    Reorder(Vec<PathElement>, Vec<usize>),
+}
+
+impl PatchOperation {
+    /// Path of the value the operation changes
+    pub fn path(&self) -> &[PathElement] {
+        match self {
+            PatchOperation::Value(path, _)
+            | PatchOperation::Replace(path, _, _)
+            | PatchOperation::AddKey(path, _)
+            | PatchOperation::Remove(path, _)
+            | PatchOperation::RemoveValues(path, _)
+            | PatchOperation::RemoveKey(path)
+            | PatchOperation::RemoveEntry(path, _)
+            | PatchOperation::InsertAt(path, _)
+            | PatchOperation::RemoveAt(path)
+            | PatchOperation::RemoveValueAt(path, _)
+            | PatchOperation::Reorder(path, _) => path,
+        }
+    }
+}
+
+impl DiffPatch {
+    /// The changes made by the patch, in the order they are applied, each with the full path of
+    /// the value it changes. Fails if the patch was deserialized from commands that don't form
+    /// complete operations, such as an AddKey without its value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use serde_diff::{DiffPatch, PatchOperation, SerdeDiff};
+    /// #[derive(SerdeDiff, Clone)]
+    /// struct Test {
+    ///     a: i32,
+    ///     b: Vec<i32>,
+    /// }
+    /// let patch = DiffPatch::new(&Test { a: 3, b: vec![1] }, &Test { a: 5, b: vec![1] }).unwrap();
+    /// let operations = patch.operations().unwrap();
+    /// assert_eq!(operations.len(), 1);
+    /// assert!(matches!(operations[0], PatchOperation::Value(..)));
+    /// assert_eq!(operations[0].path()[0].to_string(), "a");
+    /// ```
+    pub fn operations(&self) -> Result<Vec<PatchOperation>, DeserializerError> {
+        use DiffCommandValue::*;
+        let mut operations = Vec::new();
+        // Path elements that have been entered but not yet exited
+        let mut path = Vec::new();
+        let mut commands = self.commands.iter();
+        while let Some(command) = commands.next() {
+            match command {
+                Enter(element) => path.push(PathElement::Element(element.clone())),
+                EnterKey(key) => path.push(PathElement::Key(key.clone())),
+                // A value is an implicit Exit of the element it was written for
+                Value(value) => {
+                    operations.push(PatchOperation::Value(path.clone(), value.clone()));
+                    path.pop();
+                }
+                Replace(old, new) => {
+                    operations.push(PatchOperation::Replace(
+                        path.clone(),
+                        old.clone(),
+                        new.clone(),
+                    ));
+                    path.pop();
+                }
+                // Remove is an implicit Exit of the collection it was written for
+                Remove(num_elements) => {
+                    operations.push(PatchOperation::Remove(path.clone(), *num_elements));
+                    path.pop();
+                }
+                RemoveValues(values) => {
+                    operations.push(PatchOperation::RemoveValues(path.clone(), values.clone()));
+                    path.pop();
+                }
+                AddKey(key) => match commands.next() {
+                    Some(Value(value)) => {
+                        operations.push(PatchOperation::AddKey(key_path(&path, key), value.clone()))
+                    }
+                    _ => return Err(de::Error::custom("expected value after AddKey")),
+                },
+                RemoveKey(key) => operations.push(PatchOperation::RemoveKey(key_path(&path, key))),
+                RemoveEntry(key) => match commands.next() {
+                    Some(Value(value)) => operations.push(PatchOperation::RemoveEntry(
+                        key_path(&path, key),
+                        value.clone(),
+                    )),
+                    _ => return Err(de::Error::custom("expected value after RemoveEntry")),
+                },
+                InsertAt(idx) => match commands.next() {
+                    Some(Value(value)) => operations.push(PatchOperation::InsertAt(
+                        index_path(&path, *idx),
+                        value.clone(),
+                    )),
+                    _ => return Err(de::Error::custom("expected value after InsertAt")),
+                },
+                RemoveAt(idx) => operations.push(PatchOperation::RemoveAt(index_path(&path, *idx))),
+                RemoveValueAt(idx) => match commands.next() {
+                    Some(Value(value)) => operations.push(PatchOperation::RemoveValueAt(
+                        index_path(&path, *idx),
+                        value.clone(),
+                    )),
+                    _ => return Err(de::Error::custom("expected value after RemoveValueAt")),
+                },
+                Reorder(order) => {
+                    operations.push(PatchOperation::Reorder(path.clone(), order.clone()))
+                }
+                // The fingerprint is checked when the patch is applied, and changes nothing
+                Fingerprint(_) => {}
+                // Collections at the root write an Exit that has no matching Enter
+                Exit => {
+                    path.pop();
+                }
+                // Patches hold values unframed, so never contain Framed
+                Framed | Nothing | DeserializedValue => {
+                    return Err(de::Error::custom(
+                        "unexpected internal diff command in patch",
+                    ))
+                }
+            }
+        }
+        Ok(operations)
+    }
+
+    /// Encodes a list of operations back into a command stream
+    pub(crate) fn from_operations<'a, I>(operations: I) -> Self
+    where
+        I: IntoIterator<Item = &'a PatchOperation>,
+    {
+        let mut commands = Vec::new();
+        let mut path: Vec<PathElement> = Vec::new();
+        for operation in operations {
+            let (container, last) = match operation {
+                PatchOperation::Value(op_path, _)
+                | PatchOperation::Replace(op_path, _, _)
+                | PatchOperation::AddKey(op_path, _)
+                | PatchOperation::RemoveKey(op_path)
+                | PatchOperation::RemoveEntry(op_path, _)
+                | PatchOperation::InsertAt(op_path, _)
+                | PatchOperation::RemoveAt(op_path)
+                | PatchOperation::RemoveValueAt(op_path, _) => match op_path.split_last() {
+                    Some((last, container)) => (container, Some(last)),
+                    None => (&op_path[..], None),
+                },
+                PatchOperation::Remove(op_path, _)
+                | PatchOperation::RemoveValues(op_path, _)
+                | PatchOperation::Reorder(op_path, _) => (&op_path[..], None),
+            };
+
+            // Exit elements until we reach a common ancestor, then enter the container
+            let common = path
+                .iter()
+                .zip(container)
+                .take_while(|(a, b)| a == b)
+                .count();
+            while path.len() > common {
+                commands.push(DiffCommandValue::Exit);
+                path.pop();
+            }
+            for element in &container[common..] {
+                commands.push(element.enter_command());
+                path.push(element.clone());
+            }
+
+            match operation {
+                PatchOperation::Value(_, value) => {
+                    if let Some(last) = last {
+                        commands.push(last.enter_command());
+                    }
+                    commands.push(DiffCommandValue::Value(value.clone()));
+                }
+                PatchOperation::Replace(_, old, new) => {
+                    if let Some(last) = last {
+                        commands.push(last.enter_command());
+                    }
+                    commands.push(DiffCommandValue::Replace(old.clone(), new.clone()));
+                }
+                PatchOperation::Remove(_, num_elements) => {
+                    commands.push(DiffCommandValue::Remove(*num_elements));
+                    path.pop();
+                }
+                PatchOperation::RemoveValues(_, values) => {
+                    commands.push(DiffCommandValue::RemoveValues(values.clone()));
+                    path.pop();
+                }
+                PatchOperation::AddKey(_, value) => {
+                    if let Some(PathElement::Key(key)) = last {
+                        commands.push(DiffCommandValue::AddKey(key.clone()));
+                        commands.push(DiffCommandValue::Value(value.clone()));
+                    }
+                }
+                PatchOperation::RemoveKey(_) => {
+                    if let Some(PathElement::Key(key)) = last {
+                        commands.push(DiffCommandValue::RemoveKey(key.clone()));
+                    }
+                }
+                PatchOperation::RemoveEntry(_, value) => {
+                    if let Some(PathElement::Key(key)) = last {
+                        commands.push(DiffCommandValue::RemoveEntry(key.clone()));
+                        commands.push(DiffCommandValue::Value(value.clone()));
+                    }
+                }
+                PatchOperation::InsertAt(_, value) => {
+                    if let Some(idx) = last.and_then(PathElement::collection_index) {
+                        commands.push(DiffCommandValue::InsertAt(idx));
+                        commands.push(DiffCommandValue::Value(value.clone()));
+                    }
+                }
+                PatchOperation::RemoveAt(_) => {
+                    if let Some(idx) = last.and_then(PathElement::collection_index) {
+                        commands.push(DiffCommandValue::RemoveAt(idx));
+                    }
+                }
+                PatchOperation::RemoveValueAt(_, value) => {
+                    if let Some(idx) = last.and_then(PathElement::collection_index) {
+                        commands.push(DiffCommandValue::RemoveValueAt(idx));
+                        commands.push(DiffCommandValue::Value(value.clone()));
+                    }
+                }
+                PatchOperation::Reorder(_, order) => {
+                    commands.push(DiffCommandValue::Reorder(order.clone()));
+                }
+            }
+        }
+        commands.extend(path.iter().map(|_| DiffCommandValue::Exit));
+        DiffPatch { commands }
+    }
+}
+
+/// True if `prefix` is `path` or one of its ancestors
+pub(crate) fn is_prefix(prefix: &[PathElement], path: &[PathElement]) -> bool {
+    prefix.len() <= path.len() && prefix == &path[..prefix.len()]
+}
+
+fn key_path(path: &[PathElement], key: &Value) -> Vec<PathElement> {
+    let mut path = path.to_vec();
+    path.push(PathElement::Key(key.clone()));
+    path
+}
+
+fn index_path(path: &[PathElement], idx: usize) -> Vec<PathElement> {
+    let mut path = path.to_vec();
+    path.push(PathElement::Element(DiffPathElementValue::CollectionIndex(
+        idx,
+    )));
+    path
+}
+
+impl PathElement {
+    fn collection_index(&self) -> Option<usize> {
+        match self {
+            PathElement::Element(DiffPathElementValue::CollectionIndex(idx)) => Some(*idx),
+            _ => None,
+        }
+    }
+
+    fn enter_command(&self) -> DiffCommandValue<'static, Value> {
+        match self {
+            PathElement::Element(element) => DiffCommandValue::Enter(element.clone()),
+            PathElement::Key(key) => DiffCommandValue::EnterKey(key.clone()),
+        }
+    }
+}
+
+impl Serialize for DiffPatch {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use DiffCommandValue::*;
+        let mut seq = serializer.serialize_seq(Some(self.commands.len()))?;
+        for command in &self.commands {
+            let command = match command {
+                Enter(element) => DiffCommandRef::Enter(element.clone()),
+                Value(value) => DiffCommandRef::Value(value),
+                Remove(num_elements) => DiffCommandRef::Remove(*num_elements),
+                AddKey(key) => DiffCommandRef::AddKey(key),
+                EnterKey(key) => DiffCommandRef::EnterKey(key),
+                RemoveKey(key) => DiffCommandRef::RemoveKey(key),
+                Exit => DiffCommandRef::Exit,
+                Replace(old, new) => DiffCommandRef::Replace(old, new),
+                RemoveValues(values) => DiffCommandRef::RemoveValues(values),
+                RemoveEntry(key) => DiffCommandRef::RemoveEntry(key),
+                InsertAt(idx) => DiffCommandRef::InsertAt(*idx),
+                RemoveAt(idx) => DiffCommandRef::RemoveAt(*idx),
+                RemoveValueAt(idx) => DiffCommandRef::RemoveValueAt(*idx),
+                Reorder(order) => DiffCommandRef::Reorder(order),
+                Fingerprint(fingerprint) => DiffCommandRef::Fingerprint(*fingerprint),
+                Framed | Nothing | DeserializedValue => {
+                    return Err(ser::Error::custom(
+                        "patch contains an internal diff command",
+                    ))
+                }
+            };
+            seq.serialize_element(&command)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for DiffPatch {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let commands = Vec::<DiffCommandValue<'de, Value>>::deserialize(deserializer)?;
+        let commands = commands
+            .into_iter()
+            .map(|command| match command {
+                DiffCommandValue::Framed => Err(de::Error::custom(
+                    "framed diffs can't be read into a patch, apply them directly instead",
+                )),
+                DiffCommandValue::Nothing | DiffCommandValue::DeserializedValue => Err(
+                    de::Error::custom("unexpected internal diff command in patch"),
+                ),
+                command => Ok(command.into_owned()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(DiffPatch { commands })
+    }
+}
+
+impl DiffPatch {
+    /// Reads the commands of the patch the way a serialized diff is read, so the patch can be
+    /// applied without serializing it first
+    pub(crate) fn deserializer(&self) -> PatchDeserializer<'_> {
+        PatchDeserializer {
+            commands: self.commands.iter(),
+        }
+    }
+}
+
+/// Deserializes the commands of a patch as a sequence of diff commands
+pub(crate) struct PatchDeserializer<'a> {
+    commands: std::slice::Iter<'a, DiffCommandValue<'static, Value>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for PatchDeserializer<'a> {
+    type Error = DeserializerError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for PatchDeserializer<'a> {
+    type Error = DeserializerError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.commands
+            .next()
+            .map(|command| seed.deserialize(CommandDeserializer(command)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.commands.len())
+    }
+}
+
+/// Deserializes a single command of a patch as the enum a serialized diff holds. Only the values
+/// in the command's payload are cloned.
+struct CommandDeserializer<'a>(&'a DiffCommandValue<'static, Value>);
+
+impl CommandDeserializer<'_> {
+    fn invalid<E: de::Error>(&self, expected: &str) -> E {
+        E::custom(format_args!(
+            "diff command {} is not a {}",
+            self.0.name(),
+            expected
+        ))
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for CommandDeserializer<'a> {
+    type Error = DeserializerError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for CommandDeserializer<'a> {
+    type Error = DeserializerError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), Self::Error> {
+        let name = de::IntoDeserializer::<DeserializerError>::into_deserializer(self.0.name());
+        Ok((seed.deserialize(name)?, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for CommandDeserializer<'a> {
+    type Error = DeserializerError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.0 {
+            DiffCommandValue::Exit | DiffCommandValue::Framed => Ok(()),
+            _ => Err(self.invalid("unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        use DiffCommandValue::*;
+        let payload = match self.0 {
+            Enter(element) => {
+                serde_value::to_value(element).map_err(<DeserializerError as de::Error>::custom)?
+            }
+            Value(value) | AddKey(value) | EnterKey(value) | RemoveKey(value)
+            | RemoveEntry(value) => value.clone(),
+            Remove(idx) | InsertAt(idx) | RemoveAt(idx) | RemoveValueAt(idx) => {
+                serde_value::Value::U64(*idx as u64)
+            }
+            RemoveValues(values) => serde_value::Value::Seq(values.clone()),
+            Reorder(order) => serde_value::Value::Seq(
+                order
+                    .iter()
+                    .map(|idx| serde_value::Value::U64(*idx as u64))
+                    .collect(),
+            ),
+            Fingerprint(fingerprint) => serde_value::Value::U64(*fingerprint),
+            _ => return Err(self.invalid("newtype variant")),
+        };
+        seed.deserialize(ValueDeserializer::new(payload))
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            DiffCommandValue::Replace(old, new) => {
+                ValueDeserializer::new(Value::Seq(vec![old.clone(), new.clone()]))
+                    .deserialize_tuple(len, visitor)
+            }
+            _ => Err(self.invalid("tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(self.invalid("struct variant"))
+    }
+}
+
+/// Collects the diff commands written by a `DiffContext` into a `DiffPatch`
+pub(crate) struct PatchSerializer {
+    pub(crate) commands: Vec<DiffCommandValue<'static, Value>>,
+}
+
+impl SerializeSeq for PatchSerializer {
+    type Ok = DiffPatch;
+    type Error = SerializerError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        // Commands are written generically over their value type, so round-trip them through
+        // serde_value to store the value in its intermediate representation
+        let value = serde_value::to_value(value)?;
+        let command = DiffCommandValue::deserialize(value).map_err(ser::Error::custom)?;
+        self.commands.push(command);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(DiffPatch {
+            commands: self.commands,
+        })
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    difference::DiffPathElementValue,
+    patch::{is_append, DisplayPath, PatchOperation, PathElement},
+    DiffPatch,
+};
+use serde_value::{DeserializerError, Value};
+use std::fmt::Write;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Selects how a diff is rendered as text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// Plain text
+    Plain,
+    /// Text with ANSI color codes for terminals: additions are green and removals red
+    Ansi,
+}
+
+impl DiffPatch {
+    /// Renders the patch as human-readable text, with one line per change:
+    ///
+    /// ```text
+    /// player.inventory[3].count: 4 -> 7
+    /// + settings["volume"] = 0.5
+    /// - 2 items removed from list
+    /// ```
+    ///
+    /// Old values are only known if the patch was created with `Config::with_invertible`, which
+    /// `Config::render_diff` does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use serde_diff::{SerdeDiff, Config, RenderStyle};
+    /// use serde::{Serialize, Deserialize};
+    /// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq)]
+    /// struct Test {
+    ///     a: i32,
+    ///     b: Vec<i32>,
+    /// }
+    /// let text = Config::new()
+    ///     .render_diff(&Test { a: 3, b: vec![1, 2] }, &Test { a: 5, b: vec![] }, RenderStyle::Plain)
+    ///     .expect("failed to diff");
+    /// assert_eq!(text, "a: 3 -> 5\n- 2 items removed from b: [1, 2]\n");
+    /// ```
+    pub fn render(&self, style: RenderStyle) -> Result<String, DeserializerError> {
+        let mut out = String::new();
+        for operation in self.operations()? {
+            render_operation(&mut out, &operation, style);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+impl std::fmt::Display for DiffPatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self
+            .render(RenderStyle::Plain)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(&text)
+    }
+}
+
+fn render_operation(out: &mut String, operation: &PatchOperation, style: RenderStyle) {
+    let (red, green, reset) = match style {
+        RenderStyle::Plain => ("", "", ""),
+        RenderStyle::Ansi => (RED, GREEN, RESET),
+    };
+    match operation {
+        PatchOperation::Value(path, value) if is_append(path) => {
+            let _ = write!(out, "{}+ {} = ", green, DisplayPath(path));
+            write_value(out, value);
+            out.push_str(reset);
+        }
+        PatchOperation::Value(path, value) => {
+            let _ = write!(out, "{}: {}", DisplayPath(display_path(path)), green);
+            write_value(out, value);
+            out.push_str(reset);
+        }
+        PatchOperation::Replace(path, old, new) => {
+            let _ = write!(out, "{}: {}", DisplayPath(display_path(path)), red);
+            write_value(out, old);
+            let _ = write!(out, "{} -> {}", reset, green);
+            write_value(out, new);
+            out.push_str(reset);
+        }
+        PatchOperation::AddKey(path, value) | PatchOperation::InsertAt(path, value) => {
+            let _ = write!(out, "{}+ {} = ", green, DisplayPath(path));
+            write_value(out, value);
+            out.push_str(reset);
+        }
+        PatchOperation::Remove(path, num_elements) => {
+            let _ = write!(
+                out,
+                "{}- {} removed from {}{}",
+                red,
+                items(*num_elements),
+                DisplayPath(path),
+                reset
+            );
+        }
+        PatchOperation::RemoveValues(path, values) => {
+            let _ = write!(
+                out,
+                "{}- {} removed from {}: ",
+                red,
+                items(values.len()),
+                DisplayPath(path)
+            );
+            write_seq(out, values);
+            out.push_str(reset);
+        }
+        PatchOperation::RemoveKey(path) | PatchOperation::RemoveAt(path) => {
+            let _ = write!(out, "{}- {}{}", red, DisplayPath(path), reset);
+        }
+        PatchOperation::Reorder(path, order) => {
+            let _ = write!(out, "{} reordered: {:?}", DisplayPath(path), order);
+        }
+        PatchOperation::RemoveEntry(path, value) | PatchOperation::RemoveValueAt(path, value) => {
+            let _ = write!(out, "{}- {} = ", red, DisplayPath(path));
+            write_value(out, value);
+            out.push_str(reset);
+        }
+    }
+}
+
+// A replaced enum variant is shown as a change to the enum itself
+fn display_path(path: &[PathElement]) -> &[PathElement] {
+    match path.split_last() {
+        Some((PathElement::Element(DiffPathElementValue::FullEnumVariant), rest)) => rest,
+        _ => path,
+    }
+}
+
+fn items(count: usize) -> String {
+    if count == 1 {
+        "1 item".to_string()
+    } else {
+        format!("{} items", count)
+    }
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    let _ = match value {
+        Value::Bool(v) => write!(out, "{}", v),
+        Value::U8(v) => write!(out, "{}", v),
+        Value::U16(v) => write!(out, "{}", v),
+        Value::U32(v) => write!(out, "{}", v),
+        Value::U64(v) => write!(out, "{}", v),
+        Value::I8(v) => write!(out, "{}", v),
+        Value::I16(v) => write!(out, "{}", v),
+        Value::I32(v) => write!(out, "{}", v),
+        Value::I64(v) => write!(out, "{}", v),
+        Value::F32(v) => write!(out, "{}", v),
+        Value::F64(v) => write!(out, "{}", v),
+        Value::Char(v) => write!(out, "{:?}", v),
+        Value::String(v) => write!(out, "{:?}", v),
+        Value::Unit => write!(out, "()"),
+        Value::Option(None) => write!(out, "None"),
+        Value::Option(Some(v)) => {
+            out.push_str("Some(");
+            write_value(out, v);
+            write!(out, ")")
+        }
+        Value::Newtype(v) => {
+            write_value(out, v);
+            Ok(())
+        }
+        Value::Seq(values) => {
+            write_seq(out, values);
+            Ok(())
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (idx, (key, value)) in map.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, key);
+                out.push_str(": ");
+                write_value(out, value);
+            }
+            write!(out, "}}")
+        }
+        Value::Bytes(v) => write!(out, "{:?}", v),
+    };
+}
+
+fn write_seq(out: &mut String, values: &[Value]) {
+    out.push('[');
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            out.push_str(", ");
+        }
+        write_value(out, value);
+    }
+    out.push(']');
+}
@@ -0,0 +1,71 @@
+use crate::patch::{is_prefix, PathElement};
+
+/// The changes made to a target by applying a diff
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_diff::{SerdeDiff, Config, DiffPatch, ChangeKind};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(SerdeDiff, Serialize, Deserialize, PartialEq)]
+/// struct Test {
+///     a: i32,
+///     b: Vec<i32>,
+/// }
+/// let patch = DiffPatch::new(&Test { a: 3, b: vec![] }, &Test { a: 5, b: vec![1] })
+///     .expect("failed to diff");
+/// let mut target = Test { a: 4, b: vec![] };
+/// let report = Config::new()
+///     .apply_patch_with_report(&patch, &mut target)
+///     .expect("failed to apply patch");
+/// assert_eq!(report.changes.len(), 2);
+/// assert_eq!(report.changes[0].kind, ChangeKind::Modified);
+/// assert_eq!(report.changes[1].kind, ChangeKind::Added);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ApplyReport {
+    /// Every part of the target that was modified, added or removed, in the order the changes
+    /// were applied
+    pub changes: Vec<AppliedChange>,
+    /// Paths in the diff that don't match the target. Changes to these are skipped, except for
+    /// additions of elements that already exist, which overwrite them.
+    pub unmatched: Vec<Vec<PathElement>>,
+}
+
+/// A single change made to the target
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppliedChange {
+    /// Path to the part of the target that changed
+    pub path: Vec<PathElement>,
+    /// How it changed
+    pub kind: ChangeKind,
+}
+
+/// How part of a target changed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// An existing value was replaced
+    Modified,
+    /// A collection element or map entry was added
+    Added,
+    /// A collection element or map entry was removed
+    Removed,
+}
+
+impl ApplyReport {
+    /// True if applying the diff changed the target
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// The paths that were modified, added or removed
+    pub fn changed_paths(&self) -> impl Iterator<Item = &[PathElement]> {
+        self.changes.iter().map(|change| change.path.as_slice())
+    }
+
+    /// True if any change was made at the given path, inside it or to a value containing it
+    pub fn touches(&self, path: &[PathElement]) -> bool {
+        self.changed_paths()
+            .any(|changed| is_prefix(changed, path) || is_prefix(path, changed))
+    }
+}
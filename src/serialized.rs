@@ -0,0 +1,941 @@
+use crate::{
+    apply::ApplyContext,
+    difference::{DiffCommandRef, DiffCommandValue, DiffContext, DiffPathElementValue},
+    report::ChangeKind,
+    Fingerprint, SerdeDiff,
+};
+use serde::{
+    de::{self, DeserializeOwned},
+    ser::{self, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_value::{SerializerError, Value};
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Diffs any type that implements `Serialize` and `Deserialize`, for types that don't implement
+/// SerdeDiff and can't derive it, such as types from other crates.
+///
+/// Both values are serialized into a tree that records their structs, enums, sequences and maps,
+/// and the trees are compared. The diff has the same commands a derived SerdeDiff implementation
+/// would write. The target is applied to by serializing it into a tree, applying the diff to the
+/// tree and deserializing the result.
+///
+/// Serde writes tuples and arrays the same way, so both are diffed by index like arrays, and
+/// derived diffs of tuples are applied by their field names.
+///
+/// Applying needs a self-describing format such as JSON or MessagePack, or a `DiffPatch`, as the
+/// values in the diff are read without knowing their type.
+/// ```rust
+/// use serde_diff::{Apply, Diff, Serialized};
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct External {
+///     a: u32,
+///     b: Vec<String>,
+/// }
+/// let old = Serialized(External { a: 1, b: vec!["one".to_string()] });
+/// let new = Serialized(External { a: 1, b: vec!["one".to_string(), "two".to_string()] });
+/// let json_data = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+/// let mut target = Serialized(External { a: 2, b: vec![] });
+/// let mut deserializer = serde_json::Deserializer::from_str(&json_data);
+/// Apply::apply(&mut deserializer, &mut target).unwrap();
+/// assert_eq!(target.0, External { a: 2, b: vec!["two".to_string()] });
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(transparent)]
+pub struct Serialized<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned> SerdeDiff for Serialized<T> {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        let old = Node::of(&self.0).map_err(ser::Error::custom)?;
+        let new = Node::of(&other.0).map_err(ser::Error::custom)?;
+        old.diff(ctx, &new)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut node = Node::of(&self.0).map_err(de::Error::custom)?;
+        let changed = node.apply(seq, ctx)?;
+        if changed {
+            self.0 = T::deserialize(node.into_value()).map_err(de::Error::custom)?;
+        }
+        Ok(changed)
+    }
+
+    fn fingerprint() -> u64 {
+        crate::fingerprint::container("Serialized", &[Fingerprint::deserialized::<T>()])
+    }
+}
+
+/// The serialized form of a value, keeping the structure that a derived SerdeDiff implementation
+/// diffs by. Values that are written whole, such as numbers and strings, are kept as a `Value`.
+#[derive(Clone, PartialEq, Debug)]
+enum Node {
+    Value(Value),
+    Option(Option<Box<Node>>),
+    Seq(Vec<Node>),
+    Tuple(Vec<Node>),
+    Map(BTreeMap<MapKey, Node>),
+    Struct {
+        name: &'static str,
+        fields: Fields,
+    },
+    Variant {
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        fields: Fields,
+    },
+}
+
+/// The fields of a struct or enum variant. Names are only owned for fields that are added when
+/// applying a diff, which are never serialized again.
+#[derive(Clone, PartialEq, Debug)]
+enum Fields {
+    Unit,
+    Newtype(Box<Node>),
+    Tuple(Vec<Node>),
+    Named(Vec<(Cow<'static, str>, Node)>),
+}
+
+impl Node {
+    fn of<T: Serialize + ?Sized>(value: &T) -> Result<Node, SerializerError> {
+        value.serialize(NodeSerializer)
+    }
+
+    /// Converts the node into a Value that the original type can be deserialized from. Structs
+    /// become maps of their field names, and enum variants maps of the variant name to its fields.
+    fn into_value(self) -> Value {
+        match self {
+            Node::Value(value) => value,
+            Node::Option(node) => Value::Option(node.map(|node| Box::new(node.into_value()))),
+            Node::Seq(nodes) | Node::Tuple(nodes) => {
+                Value::Seq(nodes.into_iter().map(Node::into_value).collect())
+            }
+            Node::Map(entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, node)| (key.0, node.into_value()))
+                    .collect(),
+            ),
+            Node::Struct { fields, .. } => match fields {
+                Fields::Newtype(node) => Value::Newtype(Box::new(node.into_value())),
+                fields => fields.into_value(),
+            },
+            Node::Variant {
+                variant, fields, ..
+            } => match fields {
+                Fields::Unit => Value::String(variant.to_string()),
+                Fields::Newtype(node) => single_entry(variant, node.into_value()),
+                fields => single_entry(variant, fields.into_value()),
+            },
+        }
+    }
+}
+
+fn single_entry(variant: &str, value: Value) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert(Value::String(variant.to_string()), value);
+    Value::Map(map)
+}
+
+impl Fields {
+    fn into_value(self) -> Value {
+        match self {
+            Fields::Unit => Value::Unit,
+            Fields::Newtype(node) => node.into_value(),
+            Fields::Tuple(nodes) => Value::Seq(nodes.into_iter().map(Node::into_value).collect()),
+            Fields::Named(fields) => Value::Map(
+                fields
+                    .into_iter()
+                    .map(|(name, node)| (Value::String(name.into_owned()), node.into_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn is_same_kind(&self, other: &Fields) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Diffs the fields like a derived implementation: named fields by name and the others by
+    /// their index
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Fields,
+    ) -> Result<bool, S::Error> {
+        let mut changed = false;
+        match (self, other) {
+            (Fields::Newtype(old), Fields::Newtype(new)) => {
+                ctx.push_field_index(0);
+                changed |= old.diff(ctx, new)?;
+                ctx.pop_path_element()?;
+            }
+            (Fields::Tuple(old), Fields::Tuple(new)) => {
+                for (idx, (old, new)) in old.iter().zip(new).enumerate() {
+                    ctx.push_field_index(idx as u16);
+                    changed |= old.diff(ctx, new)?;
+                    ctx.pop_path_element()?;
+                }
+            }
+            (Fields::Named(old), Fields::Named(new)) => {
+                for (name, new) in new {
+                    match name {
+                        Cow::Borrowed(name) => ctx.push_field(name),
+                        Cow::Owned(_) => continue,
+                    }
+                    match old.iter().find(|(old_name, _)| old_name == name) {
+                        Some((_, old)) => changed |= old.diff(ctx, new)?,
+                        // Fields skipped when serializing the old value are written whole
+                        None => {
+                            ctx.save_value(new)?;
+                            changed = true;
+                        }
+                    }
+                    ctx.pop_path_element()?;
+                }
+                // Fields skipped when serializing the new value are removed like map entries, so
+                // deserializing the target gives them their default
+                for (name, old) in old {
+                    if new.iter().any(|(new_name, _)| new_name == name) {
+                        continue;
+                    }
+                    let key = Value::String(name.to_string());
+                    if ctx.is_invertible() {
+                        ctx.save_command(&DiffCommandRef::RemoveEntry(&key), false, true)?;
+                        ctx.save_command(&DiffCommandRef::Value(old), false, true)?;
+                    } else {
+                        ctx.save_command(&DiffCommandRef::RemoveKey(&key), false, true)?;
+                    }
+                    changed = true;
+                }
+            }
+            _ => {}
+        }
+        Ok(changed)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut changed = false;
+        while let Some(cmd) = ctx.read_next_command::<A, Value>(seq)? {
+            let element = match cmd {
+                DiffCommandValue::Enter(element) => element,
+                // Named fields are removed and added by name, like the entries of a map
+                DiffCommandValue::AddKey(key) => {
+                    let value = ctx.read_expected_value::<A, Value>(seq, "AddKey")?;
+                    match (&mut *self, key) {
+                        (Fields::Named(fields), Value::String(name)) => {
+                            let node = Node::Value(value);
+                            match fields.iter().position(|(field, _)| *field == name) {
+                                Some(idx) => {
+                                    ctx.record_exited_change(ChangeKind::Modified, None);
+                                    fields[idx].1 = node;
+                                }
+                                None => {
+                                    ctx.record_exited_change(ChangeKind::Added, None);
+                                    fields.push((Cow::Owned(name), node));
+                                }
+                            }
+                            changed = true;
+                        }
+                        _ => ctx.unmatched_exited_element()?,
+                    }
+                    continue;
+                }
+                DiffCommandValue::RemoveKey(key) => {
+                    if self.remove_field(&key) {
+                        ctx.record_key_change(ChangeKind::Removed, &key)?;
+                        changed = true;
+                    } else {
+                        ctx.unmatched_key(&key)?;
+                    }
+                    continue;
+                }
+                DiffCommandValue::RemoveEntry(key) => {
+                    ctx.read_expected_value::<A, Value>(seq, "RemoveEntry")?;
+                    if self.remove_field(&key) {
+                        ctx.record_exited_change(ChangeKind::Removed, None);
+                        changed = true;
+                    } else {
+                        ctx.unmatched_exited_element()?;
+                    }
+                    continue;
+                }
+                _ => break,
+            };
+            let field = match (&mut *self, element) {
+                (Fields::Newtype(node), DiffPathElementValue::FieldIndex(0)) => Some(&mut **node),
+                (Fields::Tuple(nodes), DiffPathElementValue::FieldIndex(idx)) => {
+                    nodes.get_mut(idx as usize)
+                }
+                (Fields::Named(fields), DiffPathElementValue::FieldIndex(idx)) => {
+                    fields.get_mut(idx as usize).map(|(_, node)| node)
+                }
+                (Fields::Named(fields), DiffPathElementValue::Field(name)) => {
+                    match fields.iter().position(|(field, _)| *field == name) {
+                        Some(idx) => Some(&mut fields[idx].1),
+                        // The field was skipped when serializing the target, so it's added with
+                        // the name it has in the target's type
+                        None => {
+                            let mut node = Node::Value(Value::Unit);
+                            if node.apply(seq, ctx)? {
+                                fields.push((Cow::Owned(name.into_owned()), node));
+                                changed = true;
+                            }
+                            continue;
+                        }
+                    }
+                }
+                _ => None,
+            };
+            match field {
+                Some(node) => changed |= node.apply(seq, ctx)?,
+                None => ctx.skip_value(seq)?,
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Removes the named field with the given key, returning false if there was none
+    fn remove_field(&mut self, key: &Value) -> bool {
+        match (self, key) {
+            (Fields::Named(fields), Value::String(name)) => {
+                let len = fields.len();
+                fields.retain(|(field, _)| field != name);
+                fields.len() != len
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SerdeDiff for Node {
+    fn diff<'a, S: SerializeSeq>(
+        &self,
+        ctx: &mut DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        match (self, other) {
+            (Node::Option(old), Node::Option(new)) => old.diff(ctx, new),
+            (Node::Seq(old), Node::Seq(new)) => old.diff(ctx, new),
+            (Node::Tuple(old), Node::Tuple(new)) if old.len() == new.len() => {
+                let mut changed = false;
+                for (idx, (old, new)) in old.iter().zip(new).enumerate() {
+                    ctx.push_collection_index(idx);
+                    changed |= old.diff(ctx, new)?;
+                    ctx.pop_path_element()?;
+                }
+                if changed {
+                    ctx.save_command::<()>(&DiffCommandRef::Exit, true, false)?;
+                }
+                Ok(changed)
+            }
+            (Node::Map(old), Node::Map(new)) => old.diff(ctx, new),
+            (
+                Node::Struct { name, fields },
+                Node::Struct {
+                    name: new_name,
+                    fields: new_fields,
+                },
+            ) if name == new_name && fields.is_same_kind(new_fields) => {
+                fields.diff(ctx, new_fields)
+            }
+            (
+                Node::Variant {
+                    name,
+                    index,
+                    variant,
+                    fields,
+                },
+                Node::Variant {
+                    name: new_name,
+                    variant: new_variant,
+                    fields: new_fields,
+                    ..
+                },
+            ) if name == new_name => {
+                // A variant that changed is written whole, like a derived enum does
+                if variant == new_variant && fields.is_same_kind(new_fields) {
+                    // Field positions shift when fields are skipped, so only variants are written by
+                    // index in `FieldPathMode::Index`
+                    ctx.push_variant_with_index(variant, *index);
+                    let changed = fields.diff(ctx, new_fields)?;
+                    ctx.pop_path_element()?;
+                    Ok(changed)
+                } else {
+                    ctx.push_full_variant();
+                    ctx.save_change(self, other)?;
+                    ctx.pop_path_element()?;
+                    Ok(true)
+                }
+            }
+            _ if self != other => {
+                ctx.save_change(self, other)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        match self {
+            Node::Option(node) => node.apply(seq, ctx),
+            Node::Seq(nodes) => nodes.apply(seq, ctx),
+            Node::Tuple(nodes) => {
+                let mut changed = false;
+                while let Some(element) = ctx.next_path_element(seq)? {
+                    match element {
+                        DiffPathElementValue::CollectionIndex(idx) if idx < nodes.len() => {
+                            changed |= nodes[idx].apply(seq, ctx)?;
+                        }
+                        DiffPathElementValue::Field(name) => {
+                            match name
+                                .parse::<usize>()
+                                .ok()
+                                .and_then(|idx| nodes.get_mut(idx))
+                            {
+                                Some(node) => changed |= node.apply(seq, ctx)?,
+                                None => ctx.skip_value(seq)?,
+                            }
+                        }
+                        _ => ctx.skip_value(seq)?,
+                    }
+                }
+                Ok(changed)
+            }
+            Node::Map(entries) => entries.apply(seq, ctx),
+            Node::Struct { fields, .. } => fields.apply(seq, ctx),
+            Node::Variant { .. } => self.apply_variant(seq, ctx),
+            Node::Value(_) => ctx.read_value(seq, self),
+        }
+    }
+}
+
+impl Node {
+    fn apply_variant<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, <A as de::SeqAccess<'de>>::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut changed = false;
+        match (ctx.next_path_element(seq)?, &mut *self) {
+            (Some(DiffPathElementValue::FullEnumVariant), _) => {
+                changed = ctx.read_value(seq, self)?;
+            }
+            (
+                Some(DiffPathElementValue::EnumVariant(name)),
+                Node::Variant {
+                    variant, fields, ..
+                },
+            ) if name == *variant => {
+                changed = fields.apply(seq, ctx)?;
+            }
+            (
+                Some(DiffPathElementValue::EnumVariantIndex(idx)),
+                Node::Variant { index, fields, .. },
+            ) if idx == *index => {
+                changed = fields.apply(seq, ctx)?;
+            }
+            _ => ctx.skip_value(seq)?,
+        }
+        // The variant is written as a path element below the one the enum was entered with, so
+        // the Exit for the enclosing element is still pending
+        ctx.next_path_element(seq)?;
+        Ok(changed)
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{
+            SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct,
+            SerializeTupleVariant,
+        };
+        match self {
+            Node::Value(value) => value.serialize(serializer),
+            Node::Option(None) => serializer.serialize_none(),
+            Node::Option(Some(node)) => serializer.serialize_some(node),
+            Node::Seq(nodes) => serializer.collect_seq(nodes),
+            Node::Tuple(nodes) => {
+                let mut tuple = serializer.serialize_tuple(nodes.len())?;
+                for node in nodes {
+                    tuple.serialize_element(node)?;
+                }
+                tuple.end()
+            }
+            Node::Map(entries) => serializer.collect_map(entries),
+            Node::Struct { name, fields } => match fields {
+                Fields::Unit => serializer.serialize_unit_struct(name),
+                Fields::Newtype(node) => serializer.serialize_newtype_struct(name, node),
+                Fields::Tuple(nodes) => {
+                    let mut tuple = serializer.serialize_tuple_struct(name, nodes.len())?;
+                    for node in nodes {
+                        tuple.serialize_field(node)?;
+                    }
+                    tuple.end()
+                }
+                Fields::Named(fields) => {
+                    let mut fields_ser = serializer.serialize_struct(name, fields.len())?;
+                    for (field, node) in fields {
+                        fields_ser.serialize_field(static_name(field.clone())?, node)?;
+                    }
+                    fields_ser.end()
+                }
+            },
+            Node::Variant {
+                name,
+                index,
+                variant,
+                fields,
+            } => match fields {
+                Fields::Unit => serializer.serialize_unit_variant(name, *index, variant),
+                Fields::Newtype(node) => {
+                    serializer.serialize_newtype_variant(name, *index, variant, node)
+                }
+                Fields::Tuple(nodes) => {
+                    let mut tuple =
+                        serializer.serialize_tuple_variant(name, *index, variant, nodes.len())?;
+                    for node in nodes {
+                        tuple.serialize_field(node)?;
+                    }
+                    tuple.end()
+                }
+                Fields::Named(fields) => {
+                    let mut fields_ser =
+                        serializer.serialize_struct_variant(name, *index, variant, fields.len())?;
+                    for (field, node) in fields {
+                        fields_ser.serialize_field(static_name(field.clone())?, node)?;
+                    }
+                    fields_ser.end()
+                }
+            },
+        }
+    }
+}
+
+fn static_name<E: ser::Error>(name: Cow<'static, str>) -> Result<&'static str, E> {
+    match name {
+        Cow::Borrowed(name) => Ok(name),
+        Cow::Owned(name) => Err(E::custom(format_args!(
+            "field {} was added by a diff and can't be serialized",
+            name
+        ))),
+    }
+}
+
+// Values read from a diff don't say which type they were serialized from, so they're kept whole
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(Node::Value)
+    }
+}
+
+/// The key of a map entry. Formats such as JSON read every integer key back as a u64 or i64, so
+/// keys are kept in the widest type of their kind to find the entries that diffs refer to. The
+/// original type still deserializes from them.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct MapKey(Value);
+
+impl MapKey {
+    fn new(key: Value) -> Self {
+        MapKey(match key {
+            Value::U8(n) => Value::U64(n.into()),
+            Value::U16(n) => Value::U64(n.into()),
+            Value::U32(n) => Value::U64(n.into()),
+            Value::I8(n) => signed(n.into()),
+            Value::I16(n) => signed(n.into()),
+            Value::I32(n) => signed(n.into()),
+            Value::I64(n) => signed(n),
+            Value::F32(n) => Value::F64(n.into()),
+            Value::Char(c) => Value::String(c.to_string()),
+            key => key,
+        })
+    }
+}
+
+// Non-negative integers are read back as unsigned
+fn signed(n: i64) -> Value {
+    if n < 0 {
+        Value::I64(n)
+    } else {
+        Value::U64(n as u64)
+    }
+}
+
+impl Serialize for MapKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MapKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(MapKey::new)
+    }
+}
+
+/// Serializes a value into a Node
+struct NodeSerializer;
+
+/// Collects the elements of a sequence, tuple or fields of a struct into a Node
+struct NodeCollector<T> {
+    name: &'static str,
+    nodes: Vec<T>,
+    finish: fn(&'static str, Vec<T>) -> Node,
+}
+
+impl<T> NodeCollector<T> {
+    fn new(
+        name: &'static str,
+        len: Option<usize>,
+        finish: fn(&'static str, Vec<T>) -> Node,
+    ) -> Self {
+        NodeCollector {
+            name,
+            nodes: Vec::with_capacity(len.unwrap_or(0)),
+            finish,
+        }
+    }
+}
+
+/// Collects the entries of a map into a Node
+struct MapCollector {
+    entries: BTreeMap<MapKey, Node>,
+    key: Option<MapKey>,
+}
+
+/// Collects the fields of a tuple or struct variant into a Node
+struct VariantCollector<T> {
+    name: &'static str,
+    index: u32,
+    variant: &'static str,
+    nodes: Vec<T>,
+}
+
+impl VariantCollector<()> {
+    fn new<T>(
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> VariantCollector<T> {
+        VariantCollector {
+            name,
+            index,
+            variant,
+            nodes: Vec::with_capacity(len),
+        }
+    }
+}
+
+impl Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = SerializerError;
+    type SerializeSeq = NodeCollector<Node>;
+    type SerializeTuple = NodeCollector<Node>;
+    type SerializeTupleStruct = NodeCollector<Node>;
+    type SerializeTupleVariant = VariantCollector<Node>;
+    type SerializeMap = MapCollector;
+    type SerializeStruct = NodeCollector<(Cow<'static, str>, Node)>;
+    type SerializeStructVariant = VariantCollector<(Cow<'static, str>, Node)>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::Bool(v)))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::I8(v)))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::I16(v)))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::I32(v)))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::I64(v)))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::U8(v)))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::U16(v)))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::U32(v)))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::U64(v)))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::F32(v)))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::F64(v)))
+    }
+    fn serialize_char(self, v: char) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::Char(v)))
+    }
+    fn serialize_str(self, v: &str) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::String(v.to_string())))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::Bytes(v.to_vec())))
+    }
+    fn serialize_none(self) -> Result<Node, SerializerError> {
+        Ok(Node::Option(None))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, SerializerError> {
+        Ok(Node::Option(Some(Box::new(Node::of(value)?))))
+    }
+    fn serialize_unit(self) -> Result<Node, SerializerError> {
+        Ok(Node::Value(Value::Unit))
+    }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Node, SerializerError> {
+        Ok(Node::Struct {
+            name,
+            fields: Fields::Unit,
+        })
+    }
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<Node, SerializerError> {
+        Ok(Node::Variant {
+            name,
+            index,
+            variant,
+            fields: Fields::Unit,
+        })
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Node, SerializerError> {
+        Ok(Node::Struct {
+            name,
+            fields: Fields::Newtype(Box::new(Node::of(value)?)),
+        })
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, SerializerError> {
+        Ok(Node::Variant {
+            name,
+            index,
+            variant,
+            fields: Fields::Newtype(Box::new(Node::of(value)?)),
+        })
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, SerializerError> {
+        Ok(NodeCollector::new("", len, |_, nodes| Node::Seq(nodes)))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, SerializerError> {
+        Ok(NodeCollector::new("", Some(len), |_, nodes| {
+            Node::Tuple(nodes)
+        }))
+    }
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerializerError> {
+        Ok(NodeCollector::new(name, Some(len), |name, nodes| {
+            Node::Struct {
+                name,
+                fields: Fields::Tuple(nodes),
+            }
+        }))
+    }
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerializerError> {
+        Ok(VariantCollector::new(name, index, variant, len))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapCollector, SerializerError> {
+        Ok(MapCollector {
+            entries: BTreeMap::new(),
+            key: None,
+        })
+    }
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, SerializerError> {
+        Ok(NodeCollector::new(name, Some(len), |name, fields| {
+            Node::Struct {
+                name,
+                fields: Fields::Named(fields),
+            }
+        }))
+    }
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerializerError> {
+        Ok(VariantCollector::new(name, index, variant, len))
+    }
+}
+
+impl ser::SerializeSeq for NodeCollector<Node> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializerError> {
+        self.nodes.push(Node::of(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        Ok((self.finish)(self.name, self.nodes))
+    }
+}
+
+impl ser::SerializeTuple for NodeCollector<Node> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializerError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for NodeCollector<Node> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializerError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeStruct for NodeCollector<(Cow<'static, str>, Node)> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializerError> {
+        self.nodes.push((Cow::Borrowed(key), Node::of(value)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        Ok((self.finish)(self.name, self.nodes))
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantCollector<Node> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializerError> {
+        self.nodes.push(Node::of(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        Ok(Node::Variant {
+            name: self.name,
+            index: self.index,
+            variant: self.variant,
+            fields: Fields::Tuple(self.nodes),
+        })
+    }
+}
+
+impl ser::SerializeStructVariant for VariantCollector<(Cow<'static, str>, Node)> {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializerError> {
+        self.nodes.push((Cow::Borrowed(key), Node::of(value)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        Ok(Node::Variant {
+            name: self.name,
+            index: self.index,
+            variant: self.variant,
+            fields: Fields::Named(self.nodes),
+        })
+    }
+}
+
+impl ser::SerializeMap for MapCollector {
+    type Ok = Node;
+    type Error = SerializerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerializerError> {
+        self.key = Some(MapKey::new(serde_value::to_value(key)?));
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializerError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <SerializerError as ser::Error>::custom("map value without a key"))?;
+        self.entries.insert(key, Node::of(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, SerializerError> {
+        Ok(Node::Map(self.entries))
+    }
+}
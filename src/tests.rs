@@ -1,9 +1,10 @@
 use crate as serde_diff;
-use crate::{Apply, Diff, SerdeDiff};
+use crate::{difference::DiffPathElementValue, Apply, Diff, DiffPatch, SerdeDiff};
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Copy, Clone, Default)]
 struct TestStruct {
     a: u32,
     b: f64,
@@ -23,7 +24,9 @@ fn roundtrip<T: SerdeDiff + Serialize + for<'a> Deserialize<'a> + PartialEq + De
     let bincode_diff = bincode::serialize(&diff).unwrap();
     let mut target = old;
 
-    bincode::config()
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
         .deserialize_seed(Apply::deserializable(&mut target), &bincode_diff)
         .unwrap();
     assert_eq!(target, new);
@@ -44,7 +47,9 @@ fn partial<T: SerdeDiff + Serialize + for<'a> Deserialize<'a> + PartialEq + Debu
 
     let bincode_diff = bincode::serialize(&diff).unwrap();
     let mut tmp_target = target;
-    bincode::config()
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
         .deserialize_seed(Apply::deserializable(&mut tmp_target), &bincode_diff)
         .unwrap();
     assert_eq!(tmp_target, expected);
@@ -97,6 +102,29 @@ fn test_array() {
             None,
         ],
     );
+
+    roundtrip([0u32; 0], []);
+
+    // Arrays of any length are diffed element by element, even those serde can't serialize
+    let old = [0f32; 64];
+    let mut new = old;
+    new[63] = 1.;
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    assert_eq!(patch.operations().unwrap().len(), 1);
+    let mut target = old;
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    let old = Box::new([[0u8; 128]; 128]);
+    let mut new = old.clone();
+    new[100][127] = 1;
+    new[127][0] = 2;
+    let mut target = old.clone();
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, new);
 }
 
 #[test]
@@ -133,6 +161,73 @@ fn test_tuple() {
             None,
         ),
     );
+
+    let old = (
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    );
+    let mut new = old;
+    new.1 = 100;
+    new.19 = 100;
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    assert_eq!(patch.operations().unwrap().len(), 2);
+    let mut target = old;
+    patch.apply(&mut target).unwrap();
+    assert_eq!(
+        (target.0, target.1, target.18, target.19),
+        (0, 100, 18, 100)
+    );
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum TestEnum {
+    Unit,
+    Struct { a: u32, b: f32 },
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct EnumFieldStruct {
+    before: u32,
+    value: TestEnum,
+    after: u32,
+}
+
+#[test]
+fn test_enum_field() {
+    // The fields following the enum must still be applied once its variant is exited
+    partial(
+        EnumFieldStruct {
+            before: 0,
+            value: TestEnum::Struct { a: 1, b: 1. },
+            after: 0,
+        },
+        EnumFieldStruct {
+            before: 1,
+            value: TestEnum::Struct { a: 2, b: 1. },
+            after: 1,
+        },
+        EnumFieldStruct {
+            before: 5,
+            value: TestEnum::Struct { a: 5, b: 5. },
+            after: 5,
+        },
+        EnumFieldStruct {
+            before: 1,
+            value: TestEnum::Struct { a: 2, b: 5. },
+            after: 1,
+        },
+    );
+    roundtrip(
+        EnumFieldStruct {
+            before: 0,
+            value: TestEnum::Unit,
+            after: 0,
+        },
+        EnumFieldStruct {
+            before: 0,
+            value: TestEnum::Struct { a: 2, b: 1. },
+            after: 1,
+        },
+    );
 }
 
 #[derive(SerdeDiff, Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -160,16 +255,17 @@ impl From<MySimpleStruct> for MyComplexStruct {
     }
 }
 
-impl Into<MySimpleStruct> for MyComplexStruct {
-    fn into(self) -> MySimpleStruct {
-        MySimpleStruct { a: self.a }
+impl From<MyComplexStruct> for MySimpleStruct {
+    fn from(my_complex_struct: MyComplexStruct) -> Self {
+        MySimpleStruct {
+            a: my_complex_struct.a,
+        }
     }
 }
 
-fn targeted_roundtrip<T, U>(old: T, new: T, expected: T)
+fn targeted_roundtrip<T>(old: T, new: T, expected: T)
 where
     T: SerdeDiff + Serialize + for<'a> Deserialize<'a> + PartialEq + Debug + Clone,
-    U: SerdeDiff + Serialize + for<'a> Deserialize<'a>,
 {
     let diff = Diff::serializable(&old, &new);
     let json_diff = serde_json::to_string(&diff).unwrap();
@@ -181,7 +277,9 @@ where
     let bincode_diff = bincode::serialize(&diff).unwrap();
     let mut applied = old;
 
-    bincode::config()
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
         .deserialize_seed(Apply::deserializable(&mut applied), &bincode_diff)
         .unwrap();
     assert_eq!(applied, expected);
@@ -189,14 +287,2714 @@ where
 
 #[test]
 fn test_targeted() {
-    targeted_roundtrip::<MyComplexStruct, MySimpleStruct>(
+    targeted_roundtrip(
         MyComplexStruct { a: 1, b: 777 },
         MyComplexStruct { a: 2, b: 999 },
         MyComplexStruct { a: 2, b: 0 },
     );
-    targeted_roundtrip::<Option<MyComplexStruct>, Option<MySimpleStruct>>(
+    targeted_roundtrip(
         Some(MyComplexStruct { a: 1, b: 777 }),
         Some(MyComplexStruct { a: 2, b: 999 }),
         Some(MyComplexStruct { a: 2, b: 0 }),
     );
 }
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+struct PatchStruct {
+    s: String,
+    list: Vec<TestStruct>,
+    map: std::collections::HashMap<String, u32>,
+    opt: Option<TestStruct>,
+}
+
+#[test]
+fn test_patch() {
+    let old = PatchStruct {
+        s: "old".to_string(),
+        list: vec![TestStruct { a: 1, b: 1. }, TestStruct { a: 2, b: 2. }],
+        map: vec![("a".to_string(), 1), ("b".to_string(), 2)]
+            .into_iter()
+            .collect(),
+        opt: None,
+    };
+    let new = PatchStruct {
+        s: "new".to_string(),
+        list: vec![TestStruct { a: 1, b: 3. }],
+        map: vec![("a".to_string(), 5), ("c".to_string(), 3)]
+            .into_iter()
+            .collect(),
+        opt: Some(TestStruct { a: 7, b: 7. }),
+    };
+
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    assert!(patch.has_changes());
+    assert!(!DiffPatch::new(&old, &old).unwrap().has_changes());
+
+    // the same patch can be applied to several targets
+    for _ in 0..2 {
+        let mut target = old.clone();
+        patch.apply(&mut target).unwrap();
+        assert_eq!(target, new);
+    }
+
+    // a serialized patch is interchangeable with a serialized diff
+    let old = TestStruct { a: 1, b: 2. };
+    let new = TestStruct { a: 3, b: 2. };
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let json_patch = serde_json::to_string(&patch).unwrap();
+    assert_eq!(
+        json_patch,
+        serde_json::to_string(&Diff::serializable(&old, &new)).unwrap()
+    );
+    let mut target = old;
+    let mut deserializer = serde_json::Deserializer::from_str(&json_patch);
+    Apply::apply(&mut deserializer, &mut target).unwrap();
+    assert_eq!(target, new);
+
+    let deserialized: DiffPatch = serde_json::from_str(&json_patch).unwrap();
+    assert_eq!(serde_json::to_string(&deserialized).unwrap(), json_patch);
+    let mut target = TestStruct { a: 0, b: 4. };
+    deserialized.apply(&mut target).unwrap();
+    assert_eq!(target, TestStruct { a: 3, b: 4. });
+}
+
+#[test]
+fn test_map_of_structs() {
+    let old: std::collections::HashMap<u32, TestStruct> = vec![
+        (1, TestStruct { a: 1, b: 1. }),
+        (2, TestStruct { a: 2, b: 2. }),
+    ]
+    .into_iter()
+    .collect();
+    let new: std::collections::HashMap<u32, TestStruct> = vec![
+        (1, TestStruct { a: 3, b: 1. }),
+        (2, TestStruct { a: 4, b: 2. }),
+    ]
+    .into_iter()
+    .collect();
+    roundtrip(old, new);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum MergeEnum {
+    A { x: u32, y: u32 },
+    B(String),
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct MergeStruct {
+    s: String,
+    inner: TestStruct,
+    list: Vec<u32>,
+    map: std::collections::HashMap<String, TestStruct>,
+    tree: std::collections::BTreeMap<u32, String>,
+    opt: Option<u32>,
+    e: MergeEnum,
+}
+
+#[test]
+fn test_merge() {
+    use crate::{Merge, PathElement};
+    use std::borrow::Cow;
+
+    let base = MergeStruct {
+        s: "base".to_string(),
+        inner: TestStruct { a: 1, b: 1. },
+        list: vec![1, 2, 3],
+        map: vec![
+            ("a".to_string(), TestStruct { a: 1, b: 1. }),
+            ("b".to_string(), TestStruct { a: 2, b: 2. }),
+        ]
+        .into_iter()
+        .collect(),
+        tree: vec![(1, "one".to_string())].into_iter().collect(),
+        opt: None,
+        e: MergeEnum::A { x: 1, y: 1 },
+    };
+
+    // Non-overlapping edits merge automatically
+    let mut ours = base.clone();
+    ours.inner.a = 10;
+    ours.list.push(4);
+    ours.map.get_mut("a").unwrap().a = 10;
+    ours.tree.insert(2, "two".to_string());
+    ours.e = MergeEnum::A { x: 10, y: 1 };
+    let mut theirs = base.clone();
+    theirs.s = "theirs".to_string();
+    theirs.inner.b = 20.;
+    theirs.list[0] = 20;
+    theirs.map.get_mut("a").unwrap().b = 20.;
+    theirs.map.remove("b");
+    theirs.opt = Some(20);
+    theirs.e = MergeEnum::A { x: 1, y: 20 };
+
+    let merge = Merge::new(&base, &ours, &theirs).unwrap();
+    assert!(!merge.has_conflicts());
+    let mut expected = ours.clone();
+    expected.s = "theirs".to_string();
+    expected.inner.b = 20.;
+    expected.list[0] = 20;
+    expected.map.get_mut("a").unwrap().b = 20.;
+    expected.map.remove("b");
+    expected.opt = Some(20);
+    expected.e = MergeEnum::A { x: 10, y: 20 };
+    assert_eq!(merge.merged, expected);
+
+    // Identical edits are not conflicts
+    let merge = Merge::new(&base, &ours, &ours).unwrap();
+    assert!(!merge.has_conflicts());
+    assert_eq!(merge.merged, ours);
+
+    // Overlapping edits are reported, keeping our side
+    let mut ours = base.clone();
+    ours.s = "ours".to_string();
+    ours.map.remove("a");
+    ours.e = MergeEnum::B("ours".to_string());
+    ours.list.truncate(1);
+    let mut theirs = base.clone();
+    theirs.s = "theirs".to_string();
+    theirs.map.get_mut("a").unwrap().a = 20;
+    theirs.map.get_mut("b").unwrap().a = 20;
+    theirs.e = MergeEnum::A { x: 20, y: 1 };
+    theirs.list[2] = 20;
+
+    let merge = Merge::new(&base, &ours, &theirs).unwrap();
+    let mut expected = ours.clone();
+    expected.map.get_mut("b").unwrap().a = 20;
+    assert_eq!(merge.merged, expected);
+
+    let field =
+        |name: &'static str| PathElement::Element(DiffPathElementValue::Field(Cow::Borrowed(name)));
+    let key = |name: &str| PathElement::Key(serde_value::Value::String(name.to_string()));
+    let paths: Vec<_> = merge.conflicts.iter().map(|c| c.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            vec![field("s")],
+            vec![field("list")],
+            vec![field("map"), key("a")],
+            vec![field("e")],
+        ]
+    );
+
+    // Conflicting changes can be resolved by applying their side on top
+    let mut resolved = merge.merged.clone();
+    merge.conflicts[0].theirs.apply(&mut resolved).unwrap();
+    assert_eq!(resolved.s, "theirs");
+}
+
+#[test]
+fn test_invert() {
+    use crate::Config;
+
+    let old = MergeStruct {
+        s: "old".to_string(),
+        inner: TestStruct { a: 1, b: 1. },
+        list: vec![1, 2, 3],
+        map: vec![
+            ("a".to_string(), TestStruct { a: 1, b: 1. }),
+            ("b".to_string(), TestStruct { a: 2, b: 2. }),
+        ]
+        .into_iter()
+        .collect(),
+        tree: vec![(1, "one".to_string())].into_iter().collect(),
+        opt: Some(1),
+        e: MergeEnum::A { x: 1, y: 1 },
+    };
+    let new = MergeStruct {
+        s: "new".to_string(),
+        inner: TestStruct { a: 1, b: 2. },
+        list: vec![5],
+        map: vec![
+            ("a".to_string(), TestStruct { a: 3, b: 1. }),
+            ("c".to_string(), TestStruct { a: 4, b: 4. }),
+        ]
+        .into_iter()
+        .collect(),
+        tree: vec![(1, "one".to_string()), (2, "two".to_string())]
+            .into_iter()
+            .collect(),
+        opt: None,
+        e: MergeEnum::B("new".to_string()),
+    };
+    let config = Config::new().with_invertible(true);
+
+    // An invertible diff can still be applied without going through a patch
+    let diff = config.serializable_diff(&old, &new);
+    let json_diff = serde_json::to_string(&diff).unwrap();
+    let mut target = old.clone();
+    let mut deserializer = serde_json::Deserializer::from_str(&json_diff);
+    Apply::apply(&mut deserializer, &mut target).unwrap();
+    assert_eq!(target, new);
+    let bincode_diff = bincode::serialize(&diff).unwrap();
+    let mut target = old.clone();
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize_seed(Apply::deserializable(&mut target), &bincode_diff)
+        .unwrap();
+    assert_eq!(target, new);
+
+    // Undo and redo
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let undo = patch.invert().unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+    undo.apply(&mut target).unwrap();
+    assert_eq!(target, old);
+    undo.invert().unwrap().apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Growing collections are inverted too
+    let undo = config.diff_patch(&new, &old).unwrap().invert().unwrap();
+    let mut target = old.clone();
+    undo.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Patches that don't record old values can't be inverted
+    assert!(DiffPatch::new(&old, &new).unwrap().invert().is_none());
+    assert!(DiffPatch::new(&old, &old).unwrap().invert().is_some());
+}
+
+fn malformed_struct_pair() -> (MergeStruct, MergeStruct) {
+    let old = MergeStruct {
+        s: "old".to_string(),
+        inner: TestStruct { a: 1, b: 1. },
+        list: vec![1, 2, 3],
+        map: vec![
+            ("a".to_string(), TestStruct { a: 1, b: 1. }),
+            ("b".to_string(), TestStruct { a: 2, b: 2. }),
+        ]
+        .into_iter()
+        .collect(),
+        tree: vec![(1, "one".to_string())].into_iter().collect(),
+        opt: Some(1),
+        e: MergeEnum::A { x: 1, y: 1 },
+    };
+    let new = MergeStruct {
+        s: "new".to_string(),
+        inner: TestStruct { a: 2, b: 2. },
+        list: vec![4, 2, 3, 4, 5],
+        map: vec![
+            ("a".to_string(), TestStruct { a: 3, b: 1. }),
+            ("c".to_string(), TestStruct { a: 4, b: 4. }),
+        ]
+        .into_iter()
+        .collect(),
+        tree: vec![(2, "two".to_string())].into_iter().collect(),
+        opt: None,
+        e: MergeEnum::B("new".to_string()),
+    };
+    (old, new)
+}
+
+// Applies a command stream and only checks that it doesn't panic
+fn apply_commands(commands: Vec<serde_json::Value>, target: &MergeStruct) {
+    let mut target = target.clone();
+    let _ = Apply::apply(serde_json::Value::Array(commands), &mut target);
+}
+
+#[test]
+fn test_malformed_commands() {
+    use crate::Config;
+
+    let (old, new) = malformed_struct_pair();
+    for invertible in &[false, true] {
+        let diff = Config::new()
+            .with_invertible(*invertible)
+            .serializable_diff(&old, &new);
+        let commands = match serde_json::to_value(&diff).unwrap() {
+            serde_json::Value::Array(commands) => commands,
+            _ => panic!("diff is not a sequence"),
+        };
+
+        for len in 0..commands.len() {
+            apply_commands(commands[..len].to_vec(), &old);
+        }
+        for idx in 0..commands.len() {
+            let mut removed = commands.clone();
+            removed.remove(idx);
+            apply_commands(removed, &old);
+
+            let mut duplicated = commands.clone();
+            duplicated.insert(idx, commands[idx].clone());
+            apply_commands(duplicated, &old);
+
+            for other in 0..commands.len() {
+                let mut swapped = commands.clone();
+                swapped.swap(idx, other);
+                apply_commands(swapped, &old);
+            }
+        }
+
+        // Shuffle with a fixed xorshift generator so failures are reproducible
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..500 {
+            let mut shuffled = commands.clone();
+            for idx in (1..shuffled.len()).rev() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                shuffled.swap(idx, (state % (idx as u64 + 1)) as usize);
+            }
+            apply_commands(shuffled, &old);
+        }
+
+        let bincode_diff = bincode::serialize(&diff).unwrap();
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes();
+        for len in 0..bincode_diff.len() {
+            let mut target = old.clone();
+            let _ =
+                options.deserialize_seed(Apply::deserializable(&mut target), &bincode_diff[..len]);
+        }
+        for idx in 0..bincode_diff.len() {
+            for byte in &[0u8, 1, 2, 7, 9, 0xff] {
+                let mut corrupted = bincode_diff.clone();
+                corrupted[idx] = *byte;
+                let mut target = old.clone();
+                let _ = options.deserialize_seed(Apply::deserializable(&mut target), &corrupted);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_apply_error() {
+    use crate::{ApplyError, ApplyFailure, Config};
+
+    let mut old = malformed_struct_pair().0;
+    let mut new = old.clone();
+    new.list.push(4);
+    let json_diff = serde_json::to_value(Diff::serializable(&old, &new)).unwrap();
+    let mut commands = match json_diff {
+        serde_json::Value::Array(commands) => commands,
+        _ => panic!("diff is not a sequence"),
+    };
+
+    // Cut off the value being appended
+    commands.truncate(2);
+    let err = Apply::apply(serde_json::Value::Array(commands.clone()), &mut old).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "diff commands ended unexpectedly at list[+]"
+    );
+
+    // Something other than a value where one is required
+    commands.push(serde_json::json!("Exit"));
+    let err = Apply::apply(serde_json::Value::Array(commands), &mut old).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        ApplyError::ExpectedValue {
+            after: "AddToCollection",
+            path: vec![
+                crate::PathElement::Element(DiffPathElementValue::Field("list".into())),
+                crate::PathElement::Element(DiffPathElementValue::AddToCollection),
+            ],
+        }
+        .to_string()
+    );
+
+    // Commands that are not valid inside a struct
+    let commands = serde_json::json!([{ "Remove": 1 }]);
+    let err = Apply::apply(commands.clone(), &mut old).unwrap_err();
+    assert_eq!(err.to_string(), "unexpected diff command Remove at <root>");
+
+    // try_apply returns the ApplyError itself, and keeps errors of the format apart
+    match Config::new().try_apply(commands, &mut old) {
+        Err(ApplyFailure::Apply(ApplyError::UnexpectedCommand { command, path })) => {
+            assert_eq!(command, "Remove");
+            assert!(path.is_empty());
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    let strict = Config::new().with_strict_apply(true);
+    let commands = serde_json::json!([{ "Enter": { "Field": "missing" } }, { "Value": 1 }]);
+    match strict.try_apply(commands, &mut old) {
+        Err(ApplyFailure::Apply(error @ ApplyError::UnmatchedPath { .. })) => assert_eq!(
+            error.path(),
+            &[crate::PathElement::Element(DiffPathElementValue::Field(
+                "missing".into()
+            ))]
+        ),
+        result => panic!("unexpected result {:?}", result),
+    }
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[{ "Enter": "#);
+    assert!(matches!(
+        Config::new().try_apply(&mut deserializer, &mut old),
+        Err(ApplyFailure::Format(_))
+    ));
+}
+
+#[test]
+fn test_strict_apply() {
+    use crate::{ApplyError, Config, PathElement};
+
+    let strict = Config::new().with_strict_apply(true);
+    let apply_strict = |old: &MergeStruct, new: &MergeStruct, target: &mut MergeStruct| {
+        strict
+            .apply_patch(&DiffPatch::new(old, new).unwrap(), target)
+            .map_err(|e| e.to_string())
+    };
+    let field = |name: &'static str| PathElement::Element(DiffPathElementValue::Field(name.into()));
+    let unmatched = |path: Vec<PathElement>| ApplyError::UnmatchedPath { path }.to_string();
+
+    let (old, new) = malformed_struct_pair();
+
+    // A target that matches the old value applies cleanly
+    let mut target = old.clone();
+    apply_strict(&old, &new, &mut target).unwrap();
+    assert_eq!(target, new);
+    let json_diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(&json_diff);
+    let mut target = old.clone();
+    strict.apply(&mut deserializer, &mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Index past the end of a Vec
+    let mut changed = old.clone();
+    changed.list[2] = 10;
+    let mut target = old.clone();
+    target.list.truncate(1);
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![
+            field("list"),
+            PathElement::Element(DiffPathElementValue::CollectionIndex(2)),
+        ]))
+    );
+    // By default the change is skipped
+    let mut lenient_target = target.clone();
+    Config::new()
+        .apply_patch(
+            &DiffPatch::new(&old, &changed).unwrap(),
+            &mut lenient_target,
+        )
+        .unwrap();
+    assert_eq!(lenient_target, target);
+
+    // Removing more elements than the Vec has
+    let mut changed = old.clone();
+    changed.list.truncate(1);
+    let mut target = old.clone();
+    target.list.clear();
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![field("list")]))
+    );
+
+    // Changing, removing and adding map keys that don't match the target
+    let key = |name: &str| PathElement::Key(serde_value::Value::String(name.to_string()));
+    let mut changed = old.clone();
+    changed.map.get_mut("a").unwrap().a = 10;
+    let mut target = old.clone();
+    target.map.remove("a");
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![field("map"), key("a")]))
+    );
+    let mut changed = old.clone();
+    changed.map.remove("a");
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![field("map"), key("a")]))
+    );
+    let mut changed = old.clone();
+    changed
+        .map
+        .insert("c".to_string(), TestStruct { a: 3, b: 3. });
+    let mut target = changed.clone();
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![field("map"), key("c")]))
+    );
+
+    // Removing from an Option that is already None
+    let mut changed = old.clone();
+    changed.opt = None;
+    let mut target = old.clone();
+    target.opt = None;
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![field("opt")]))
+    );
+
+    // Changing a field of an enum variant the target doesn't have
+    let mut changed = old.clone();
+    changed.e = MergeEnum::A { x: 2, y: 1 };
+    let mut target = old.clone();
+    target.e = MergeEnum::B("b".to_string());
+    assert_eq!(
+        apply_strict(&old, &changed, &mut target),
+        Err(unmatched(vec![
+            field("e"),
+            PathElement::Element(DiffPathElementValue::EnumVariant("A".into())),
+        ]))
+    );
+}
+
+#[test]
+fn test_apply_report() {
+    use crate::{ChangeKind, Config, PathElement};
+
+    let (old, new) = malformed_struct_pair();
+    let field = |name: &'static str| PathElement::Element(DiffPathElementValue::Field(name.into()));
+    let index = |idx| PathElement::Element(DiffPathElementValue::CollectionIndex(idx));
+    let key = |name: &str| PathElement::Key(serde_value::Value::String(name.to_string()));
+    let tree_key = |k| PathElement::Key(serde_value::Value::U32(k));
+
+    let json_diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(&json_diff);
+    let mut target = old.clone();
+    let report = Config::new()
+        .apply_with_report(&mut deserializer, &mut target)
+        .unwrap();
+    assert_eq!(target, new);
+    assert!(report.unmatched.is_empty());
+
+    let mut changes: Vec<_> = report
+        .changes
+        .iter()
+        .map(|change| (change.path.clone(), change.kind))
+        .collect();
+    // map iteration order is not deterministic
+    changes.sort_by_key(|(path, _)| format!("{:?}", path));
+    let mut expected = vec![
+        (vec![field("s")], ChangeKind::Modified),
+        (vec![field("inner"), field("a")], ChangeKind::Modified),
+        (vec![field("inner"), field("b")], ChangeKind::Modified),
+        (vec![field("list"), index(0)], ChangeKind::Modified),
+        (vec![field("list"), index(3)], ChangeKind::Added),
+        (vec![field("list"), index(4)], ChangeKind::Added),
+        (
+            vec![field("map"), key("a"), field("a")],
+            ChangeKind::Modified,
+        ),
+        (vec![field("map"), key("b")], ChangeKind::Removed),
+        (vec![field("map"), key("c")], ChangeKind::Added),
+        (vec![field("tree"), tree_key(1)], ChangeKind::Removed),
+        (vec![field("tree"), tree_key(2)], ChangeKind::Added),
+        (vec![field("opt")], ChangeKind::Removed),
+        (vec![field("e")], ChangeKind::Modified),
+    ];
+    expected.sort_by_key(|(path, _)| format!("{:?}", path));
+    assert_eq!(changes, expected);
+
+    assert!(report.touches(&[field("inner")]));
+    assert!(report.touches(&[field("map"), key("a"), field("a")]));
+    assert!(!report.touches(&[field("map"), key("a"), field("b")]));
+
+    // Removals from the end of a collection and paths the target doesn't have
+    let mut changed = old.clone();
+    changed.list = vec![1];
+    changed.map.get_mut("b").unwrap().a = 5;
+    let mut target = old.clone();
+    target.map.remove("b");
+    let report = Config::new()
+        .apply_patch_with_report(&DiffPatch::new(&old, &changed).unwrap(), &mut target)
+        .unwrap();
+    let changes: Vec<_> = report
+        .changes
+        .iter()
+        .map(|change| (change.path.clone(), change.kind))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (vec![field("list"), index(1)], ChangeKind::Removed),
+            (vec![field("list"), index(2)], ChangeKind::Removed),
+        ]
+    );
+    assert_eq!(report.unmatched, vec![vec![field("map"), key("b")]]);
+
+    // Nothing to apply
+    let report = Config::new()
+        .apply_patch_with_report(&DiffPatch::new(&old, &old).unwrap(), &mut target)
+        .unwrap();
+    assert!(!report.has_changes());
+}
+
+#[test]
+fn test_render() {
+    use crate::{Config, RenderStyle};
+
+    let (old, mut new) = malformed_struct_pair();
+    new.map.clear();
+    new.tree.clear();
+    let text = Config::new()
+        .render_diff(&old, &new, RenderStyle::Plain)
+        .unwrap();
+    let mut lines: Vec<&str> = text.lines().collect();
+    // map iteration order is not deterministic
+    lines.sort_unstable();
+    assert_eq!(
+        lines,
+        vec![
+            "+ list[+] = 4",
+            "+ list[+] = 5",
+            "- 1 item removed from opt: [1]",
+            "- map[\"a\"] = {\"a\": 1, \"b\": 1}",
+            "- map[\"b\"] = {\"a\": 2, \"b\": 2}",
+            "- tree[1] = \"one\"",
+            "e: {\"A\": {\"x\": 1, \"y\": 1}} -> {\"B\": \"new\"}",
+            "inner.a: 1 -> 2",
+            "inner.b: 1 -> 2",
+            "list[0]: 1 -> 4",
+            "s: \"old\" -> \"new\"",
+        ]
+    );
+
+    // Without old values, only the new ones can be shown
+    let mut changed = old.clone();
+    changed.inner.a = 7;
+    changed.list.truncate(1);
+    changed
+        .map
+        .insert("c".to_string(), TestStruct { a: 3, b: 0.5 });
+    assert_eq!(
+        DiffPatch::new(&old, &changed).unwrap().to_string(),
+        "inner.a: 7\n- 2 items removed from list\n+ map[\"c\"] = {\"a\": 3, \"b\": 0.5}\n"
+    );
+
+    let ansi = Config::new()
+        .render_diff(&old.inner, &changed.inner, RenderStyle::Ansi)
+        .unwrap();
+    assert_eq!(ansi, "a: \x1b[31m1\x1b[0m -> \x1b[32m7\x1b[0m\n");
+}
+
+#[test]
+fn test_json_patch() {
+    use crate::{JsonPatch, JsonPatchOperation};
+    use serde_value::Value;
+
+    let (old, new) = malformed_struct_pair();
+    let patch = JsonPatch::new(&old, &new).unwrap();
+    let mut operations = patch.0.clone();
+    // map iteration order is not deterministic
+    operations.sort_by_key(|op| format!("{:?}", op));
+    let replace = |path: &str, value| JsonPatchOperation::Replace {
+        path: path.to_string(),
+        value,
+    };
+    let add = |path: &str, value| JsonPatchOperation::Add {
+        path: path.to_string(),
+        value,
+    };
+    let remove = |path: &str| JsonPatchOperation::Remove {
+        path: path.to_string(),
+    };
+    let test_struct = |a, b| serde_value::to_value(TestStruct { a, b }).unwrap();
+    let mut expected = vec![
+        replace("/s", Value::String("new".to_string())),
+        replace("/inner/a", Value::U32(2)),
+        replace("/inner/b", Value::F64(2.)),
+        replace("/list/0", Value::U32(4)),
+        add("/list/-", Value::U32(4)),
+        add("/list/-", Value::U32(5)),
+        replace("/map/a/a", Value::U32(3)),
+        remove("/map/b"),
+        add("/map/c", test_struct(4, 4.)),
+        remove("/tree/1"),
+        add("/tree/2", Value::String("two".to_string())),
+        replace("/opt", Value::Unit),
+        replace("/e", serde_value::to_value(&new.e).unwrap()),
+    ];
+    expected.sort_by_key(|op| format!("{:?}", op));
+    assert_eq!(operations, expected);
+
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Removals from the end of a collection, and an Option that gains a value
+    let mut changed = old.clone();
+    changed.list.truncate(1);
+    changed.opt = Some(2);
+    let patch = JsonPatch::new(&old, &changed).unwrap();
+    assert_eq!(
+        patch.0,
+        vec![
+            remove("/list/2"),
+            remove("/list/1"),
+            replace("/opt", Value::U32(2)),
+        ]
+    );
+    let mut target = changed.clone();
+    JsonPatch::new(&changed, &old)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, old);
+
+    // Documents written by other tools
+    let json = r#"[
+        { "op": "replace", "path": "/inner/a", "value": 9 },
+        { "op": "add", "path": "/list/1", "value": 7 },
+        { "op": "remove", "path": "/list/0" },
+        { "op": "add", "path": "/map/a~1b", "value": { "a": 1, "b": 1.5 } },
+        { "op": "add", "path": "/tree/5", "value": "five" },
+        { "op": "replace", "path": "/e/A/y", "value": 3 },
+        { "op": "replace", "path": "/opt", "value": null }
+    ]"#;
+    let patch: JsonPatch = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        serde_json::from_str::<JsonPatch>(&serde_json::to_string(&patch).unwrap()).unwrap(),
+        patch
+    );
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    let mut expected = old.clone();
+    expected.inner.a = 9;
+    expected.list = vec![7, 2, 3];
+    expected
+        .map
+        .insert("a/b".to_string(), TestStruct { a: 1, b: 1.5 });
+    expected.tree.insert(5, "five".to_string());
+    expected.e = MergeEnum::A { x: 1, y: 3 };
+    expected.opt = None;
+    assert_eq!(target, expected);
+
+    // Paths that don't exist are rejected
+    for json in &[
+        r#"[{ "op": "replace", "path": "/missing", "value": 1 }]"#,
+        r#"[{ "op": "remove", "path": "/list/3" }]"#,
+        r#"[{ "op": "add", "path": "/list/4", "value": 1 }]"#,
+        r#"[{ "op": "replace", "path": "inner", "value": 1 }]"#,
+    ] {
+        let patch: JsonPatch = serde_json::from_str(json).unwrap();
+        let mut target = old.clone();
+        assert!(patch.apply(&mut target).is_err());
+        assert_eq!(target, old);
+    }
+}
+
+#[test]
+fn test_json_merge_patch() {
+    use crate::{Config, JsonMergePatch};
+
+    let (old, new) = malformed_struct_pair();
+    let patch = JsonMergePatch::new(&old, &new).unwrap();
+    assert_eq!(
+        serde_json::to_value(&patch).unwrap(),
+        serde_json::json!({
+            "s": "new",
+            "inner": { "a": 2, "b": 2.0 },
+            "list": [4, 2, 3, 4, 5],
+            "map": { "a": { "a": 3 }, "b": null, "c": { "a": 4, "b": 4.0 } },
+            "tree": { "1": null, "2": "two" },
+            "opt": null,
+            "e": { "A": null, "B": "new" },
+        })
+    );
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // The patch round-trips through JSON, where map keys become strings
+    let json = serde_json::to_string(&patch).unwrap();
+    let from_json: JsonMergePatch = serde_json::from_str(&json).unwrap();
+    let mut target = old.clone();
+    from_json.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // An Option that gains a value, and the reverse patch
+    let mut target = new.clone();
+    let patch = Config::new().json_merge_patch(&new, &old).unwrap();
+    assert_eq!(
+        serde_json::to_value(&patch).unwrap()["opt"],
+        serde_json::json!(1)
+    );
+    Config::new()
+        .apply_json_merge_patch(&patch, &mut target)
+        .unwrap();
+    assert_eq!(target, old);
+
+    assert!(JsonMergePatch::new(&old, &old).unwrap().is_empty());
+
+    // Documents written by other tools
+    let json = r#"{
+        "inner": { "a": 9 },
+        "map": { "a/b": { "a": 1, "b": 1.5 }, "missing": null },
+        "tree": { "5": "five" },
+        "e": { "A": { "y": 3 } },
+        "opt": null
+    }"#;
+    let patch: JsonMergePatch = serde_json::from_str(json).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    let mut expected = old.clone();
+    expected.inner.a = 9;
+    expected
+        .map
+        .insert("a/b".to_string(), TestStruct { a: 1, b: 1.5 });
+    expected.tree.insert(5, "five".to_string());
+    expected.e = MergeEnum::A { x: 1, y: 3 };
+    expected.opt = None;
+    assert_eq!(target, expected);
+
+    // Members that don't deserialize are rejected
+    let patch: JsonMergePatch = serde_json::from_str(r#"{ "inner": { "a": "text" } }"#).unwrap();
+    let mut target = old.clone();
+    assert!(patch.apply(&mut target).is_err());
+    assert_eq!(target, old);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RenamedStruct {
+    field_one: u32,
+    #[serde(rename = "second")]
+    field_two: u32,
+    #[serde(alias = "legacyThree")]
+    field_three: u32,
+    #[serde_diff(rename = "four")]
+    field_four: u32,
+    r#type: u32,
+    nested: RenamedEnum,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+enum RenamedEnum {
+    #[serde_diff(rename = "first")]
+    FirstVariant { inner_value: u32 },
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE", alias = "second")]
+    SecondVariant { inner_value: u32 },
+}
+
+#[test]
+fn test_serde_rename() {
+    use crate::{Config, JsonMergePatch, JsonPatch, JsonPatchOperation, RenderStyle};
+    use serde_value::Value;
+
+    let old = RenamedStruct {
+        field_one: 1,
+        field_two: 1,
+        field_three: 1,
+        field_four: 1,
+        r#type: 1,
+        nested: RenamedEnum::SecondVariant { inner_value: 1 },
+    };
+    let new = RenamedStruct {
+        field_one: 2,
+        field_two: 2,
+        field_three: 2,
+        field_four: 2,
+        r#type: 2,
+        nested: RenamedEnum::SecondVariant { inner_value: 2 },
+    };
+    assert_eq!(
+        Config::new()
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "fieldOne: 1 -> 2\nsecond: 1 -> 2\nfieldThree: 1 -> 2\nfour: 1 -> 2\ntype: 1 -> 2\n\
+         nested.second_variant.INNER_VALUE: 1 -> 2\n"
+    );
+
+    // Paths match the serialized names, so JSON Patches apply to the serialized value. They
+    // address fields by their serialized name, even if serde_diff renames them.
+    let patch = JsonPatch::new(&old, &new).unwrap();
+    assert!(patch.0.contains(&JsonPatchOperation::Replace {
+        path: "/fieldFour".to_owned(),
+        value: Value::U32(2),
+    }));
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+    let merge_patch = JsonMergePatch::new(&old, &new).unwrap();
+    let mut target = old.clone();
+    merge_patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Aliases, and the Rust identifiers used by diffs from before serde's names were honored
+    let commands = serde_json::json!([
+        { "Enter": { "Field": "legacyThree" } },
+        { "Value": 3 },
+        { "Enter": { "Field": "field_two" } },
+        { "Value": 3 },
+        { "Enter": { "Field": "nested" } },
+        { "Enter": { "EnumVariant": "second" } },
+        { "Enter": { "Field": "inner_value" } },
+        { "Value": 3 },
+        "Exit",
+        "Exit",
+    ]);
+    let mut target = old.clone();
+    Apply::apply(commands, &mut target).unwrap();
+    let mut expected = old.clone();
+    expected.field_three = 3;
+    expected.field_two = 3;
+    expected.nested = RenamedEnum::SecondVariant { inner_value: 3 };
+    assert_eq!(target, expected);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct EditScriptStruct {
+    #[serde_diff(edit_script)]
+    edited: Vec<TestStruct>,
+    by_index: Vec<u32>,
+}
+
+#[test]
+fn test_vec_edit_script() {
+    use crate::{
+        edit_script::{edit_script, Edit, MAX_COMPARISONS},
+        ApplyReport, ChangeKind, Config, JsonPatch, PathElement, RenderStyle, VecDiffMode,
+    };
+
+    let config = Config::new().with_vec_diff_mode(VecDiffMode::EditScript);
+
+    // Edit scripts are as short as the longest common subsequence allows, and turn the old
+    // sequence into the new one
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut random = |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    for _ in 0..300 {
+        let old: Vec<u32> = (0..random(12)).map(|_| random(4) as u32).collect();
+        let new: Vec<u32> = (0..random(12)).map(|_| random(4) as u32).collect();
+        let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+                };
+            }
+        }
+        let edits = edit_script(old.len(), new.len(), |i, j| old[i] == new[j]).unwrap();
+        let kept = edits.iter().filter(|edit| **edit == Edit::Keep).count();
+        assert_eq!(kept, lcs[0][0], "{:?} -> {:?}", old, new);
+        assert_eq!(edits.len(), old.len() + new.len() - kept);
+
+        for invertible in &[false, true] {
+            let config = config.with_invertible(*invertible);
+            let patch = config.diff_patch(&old, &new).unwrap();
+            let mut target = old.clone();
+            config.apply_patch(&patch, &mut target).unwrap();
+            assert_eq!(target, new, "{:?} -> {:?}", old, new);
+            if *invertible {
+                patch.invert().unwrap().apply(&mut target).unwrap();
+                assert_eq!(target, old);
+            }
+            // The serialized commands apply the same way
+            let json = serde_json::to_string(&config.serializable_diff(&old, &new)).unwrap();
+            let mut target = old.clone();
+            Apply::apply(&mut serde_json::Deserializer::from_str(&json), &mut target).unwrap();
+            assert_eq!(target, new);
+        }
+    }
+
+    // Inserting at the front of a long list only sends the new element
+    let old: Vec<u32> = (0..10_000).collect();
+    let mut new = old.clone();
+    new.insert(0, 10_000);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    assert_eq!(patch.commands.len(), 3);
+    let mut target = old.clone();
+    let mut bincode_target = old.clone();
+    config.apply_patch(&patch, &mut target).unwrap();
+    assert_eq!(target, new);
+    let bincode_diff = bincode::serialize(&config.serializable_diff(&old, &new)).unwrap();
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .deserialize_seed(Apply::deserializable(&mut bincode_target), &bincode_diff)
+        .unwrap();
+    assert_eq!(bincode_target, new);
+    assert!(Config::new().diff_patch(&old, &new).unwrap().commands.len() > 10_000);
+
+    // Long lists that differ in many places are compared by index, without comparing every pair
+    // of elements
+    for ones in &[2, 16] {
+        let old: Vec<u32> = (0..20_000).map(|_| (random(*ones) == 0) as u32).collect();
+        let new: Vec<u32> = (0..20_000).map(|_| (random(*ones) == 0) as u32).collect();
+        let comparisons = std::cell::Cell::new(0);
+        let edits = edit_script(old.len(), new.len(), |i, j| {
+            comparisons.set(comparisons.get() + 1);
+            old[i] == new[j]
+        });
+        assert_eq!(edits, None);
+        assert!(comparisons.get() <= MAX_COMPARISONS);
+        assert_eq!(
+            config.diff_patch(&old, &new).unwrap(),
+            Config::new().diff_patch(&old, &new).unwrap()
+        );
+    }
+    // So are lists too long to compare within the budget, even when they barely differ
+    let old: Vec<u32> = (0..MAX_COMPARISONS as u32).collect();
+    let mut new = old.clone();
+    new.insert(0, 0);
+    assert_eq!(
+        edit_script(old.len(), new.len(), |i, j| old[i] == new[j]),
+        None
+    );
+
+    // Elements that take the place of removed ones are diffed in place
+    let old = EditScriptStruct {
+        edited: vec![
+            TestStruct { a: 1, b: 1. },
+            TestStruct { a: 2, b: 2. },
+            TestStruct { a: 3, b: 3. },
+        ],
+        by_index: vec![1, 2],
+    };
+    let new = EditScriptStruct {
+        edited: vec![
+            TestStruct { a: 0, b: 0. },
+            TestStruct { a: 1, b: 1. },
+            TestStruct { a: 3, b: 4. },
+        ],
+        by_index: vec![0, 1, 2],
+    };
+    let text = Config::new()
+        .render_diff(&old, &new, RenderStyle::Plain)
+        .unwrap();
+    assert_eq!(
+        text,
+        "+ edited[0] = {\"a\": 0, \"b\": 0}\n\
+         edited[2].a: 2 -> 3\n\
+         edited[2].b: 2 -> 4\n\
+         - edited[3] = {\"a\": 3, \"b\": 3}\n\
+         by_index[0]: 1 -> 0\n\
+         by_index[1]: 2 -> 1\n\
+         + by_index[+] = 2\n"
+    );
+    let mut target = old.clone();
+    let report: ApplyReport = Config::new()
+        .apply_patch_with_report(&Config::new().diff_patch(&old, &new).unwrap(), &mut target)
+        .unwrap();
+    assert_eq!(target, new);
+    let index = |idx| PathElement::Element(DiffPathElementValue::CollectionIndex(idx));
+    let field = |name: &'static str| PathElement::Element(DiffPathElementValue::Field(name.into()));
+    let edited = field("edited");
+    let changes: Vec<_> = report
+        .changes
+        .iter()
+        .filter(|change| change.path[0] == edited)
+        .map(|change| (change.path.clone(), change.kind))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (vec![edited.clone(), index(0)], ChangeKind::Added),
+            (
+                vec![edited.clone(), index(2), field("a")],
+                ChangeKind::Modified
+            ),
+            (
+                vec![edited.clone(), index(2), field("b")],
+                ChangeKind::Modified
+            ),
+            (vec![edited.clone(), index(3)], ChangeKind::Removed),
+        ]
+    );
+
+    // JSON Patches insert and remove at the same indices
+    let mut target = old.clone();
+    JsonPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, new);
+
+    // Removing an index past the end is reported in strict mode
+    let commands = serde_json::json!([
+        { "Enter": { "Field": "edited" } },
+        { "RemoveAt": 5 },
+        "Exit",
+    ]);
+    let mut target = old.clone();
+    assert!(Config::new()
+        .with_strict_apply(true)
+        .apply(commands.clone(), &mut target)
+        .is_err());
+    let mut target = old.clone();
+    Config::new().apply(commands, &mut target).unwrap();
+    assert_eq!(target, old);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct KeyedEntity {
+    id: u32,
+    value: i32,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct KeyedListStruct {
+    #[serde_diff(key = "id")]
+    entities: Vec<KeyedEntity>,
+}
+
+#[test]
+fn test_keyed_list() {
+    use crate::{Config, JsonPatch, PathElement, RenderStyle};
+
+    let entities = |items: &[(u32, i32)]| KeyedListStruct {
+        entities: items
+            .iter()
+            .map(|(id, value)| KeyedEntity {
+                id: *id,
+                value: *value,
+            })
+            .collect(),
+    };
+
+    // Elements are matched by id, wherever they moved to
+    let old = entities(&[(1, 10), (2, 20), (3, 30), (4, 40)]);
+    let new = entities(&[(4, 41), (1, 10), (5, 50), (3, 30)]);
+    roundtrip(old.clone(), new.clone());
+    let text = Config::new()
+        .render_diff(&old, &new, RenderStyle::Plain)
+        .unwrap();
+    assert_eq!(
+        text,
+        "- entities[1] = {\"id\": 2, \"value\": 20}\n\
+         entities[4].value: 40 -> 41\n\
+         entities reordered: [2, 0, 1]\n\
+         + entities[2] = {\"id\": 5, \"value\": 50}\n"
+    );
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let report = Config::new()
+        .apply_patch_with_report(&patch, &mut old.clone())
+        .unwrap();
+    assert_eq!(
+        report.changes[0].path,
+        vec![
+            PathElement::Element(DiffPathElementValue::Field("entities".into())),
+            PathElement::Key(serde_value::Value::U32(2)),
+        ]
+    );
+
+    // Changes apply to the element with the same id, even if the target's elements have moved
+    partial(
+        old.clone(),
+        entities(&[(1, 11), (2, 20), (3, 30), (4, 40)]),
+        entities(&[(0, 0), (4, 40), (1, 10)]),
+        entities(&[(0, 0), (4, 40), (1, 11)]),
+    );
+
+    // Invertible patches restore the old order
+    let config = Config::new().with_invertible(true);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    config.apply_patch(&patch, &mut target).unwrap();
+    assert_eq!(target, new);
+    patch.invert().unwrap().apply(&mut target).unwrap();
+    assert_eq!(target, old);
+
+    // Elements sharing an id are removed and inserted instead
+    roundtrip(
+        entities(&[(1, 1), (1, 2), (2, 3)]),
+        entities(&[(2, 4), (1, 2), (3, 3)]),
+    );
+    roundtrip(entities(&[(1, 1), (2, 2)]), entities(&[(2, 2), (2, 3)]));
+
+    // Random edits to the list round-trip, and invert
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut random = |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    for _ in 0..200 {
+        let mut old_ids: Vec<(u32, i32)> = Vec::new();
+        let mut new_ids: Vec<(u32, i32)> = Vec::new();
+        for id in 0..random(10) as u32 {
+            old_ids.insert(random(old_ids.len() as u64 + 1) as usize, (id, id as i32));
+            if random(4) != 0 {
+                let value = id as i32 + random(2) as i32;
+                new_ids.insert(random(new_ids.len() as u64 + 1) as usize, (id, value));
+            }
+        }
+        let (old, new) = (entities(&old_ids), entities(&new_ids));
+        for invertible in &[false, true] {
+            let config = Config::new().with_invertible(*invertible);
+            let patch = config.diff_patch(&old, &new).unwrap();
+            let mut target = old.clone();
+            config.apply_patch(&patch, &mut target).unwrap();
+            assert_eq!(target, new, "{:?} -> {:?}", old_ids, new_ids);
+            if *invertible {
+                patch.invert().unwrap().apply(&mut target).unwrap();
+                assert_eq!(target, old);
+            }
+        }
+        let mut target = old.clone();
+        JsonPatch::new(&old, &new)
+            .unwrap()
+            .apply(&mut target)
+            .unwrap();
+        assert_eq!(target, new);
+    }
+
+    // A reorder that doesn't fit the target is reported in strict mode
+    let commands = serde_json::json!([
+        { "Enter": { "Field": "entities" } },
+        { "Reorder": [1, 0] },
+        "Exit",
+    ]);
+    assert!(Config::new()
+        .with_strict_apply(true)
+        .apply(commands.clone(), &mut old.clone())
+        .is_err());
+    let mut target = old.clone();
+    Config::new().apply(commands, &mut target).unwrap();
+    assert_eq!(target, old);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+struct SetStruct {
+    tags: std::collections::HashSet<String>,
+    ids: std::collections::BTreeSet<u32>,
+}
+
+#[test]
+fn test_sets() {
+    use crate::{ChangeKind, Config, JsonMergePatch, JsonPatch, PathElement};
+    use serde_value::Value;
+
+    let set = |tags: &[&str], ids: &[u32]| SetStruct {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ids: ids.iter().copied().collect(),
+    };
+    let old = set(&["red", "green"], &[1, 2, 3]);
+    let new = set(&["green", "blue"], &[2, 3, 4, 5]);
+    roundtrip(old.clone(), new.clone());
+    roundtrip(SetStruct::default(), new.clone());
+    roundtrip(new.clone(), SetStruct::default());
+
+    // Only the elements added and removed are written
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let text = Config::new()
+        .render_diff(&old, &new, crate::RenderStyle::Plain)
+        .unwrap();
+    assert_eq!(
+        text,
+        "- tags[\"red\"] = ()\n\
+         + tags[\"blue\"] = ()\n\
+         - ids[1] = ()\n\
+         + ids[4] = ()\n\
+         + ids[5] = ()\n"
+    );
+
+    // Additions and removals apply to sets that have changed in other ways
+    partial(
+        old.clone(),
+        new.clone(),
+        set(&["yellow"], &[1, 9]),
+        set(&["yellow", "blue"], &[4, 5, 9]),
+    );
+
+    let mut target = old.clone();
+    let report = Config::new()
+        .apply_patch_with_report(&patch, &mut target)
+        .unwrap();
+    assert_eq!(target, new);
+    assert_eq!(
+        report.changes[0].path,
+        vec![
+            PathElement::Element(DiffPathElementValue::Field("tags".into())),
+            PathElement::Key(Value::String("red".to_string())),
+        ]
+    );
+    assert_eq!(report.changes[0].kind, ChangeKind::Removed);
+
+    // Adding an element that's already there or removing one that isn't is unmatched
+    let mut target = set(&["blue"], &[]);
+    let report = Config::new()
+        .apply_patch_with_report(&patch, &mut target)
+        .unwrap();
+    assert_eq!(target, set(&["blue"], &[4, 5]));
+    assert_eq!(report.unmatched.len(), 3);
+    assert!(Config::new()
+        .with_strict_apply(true)
+        .apply_patch(&patch, &mut set(&["blue"], &[]))
+        .is_err());
+
+    let config = Config::new().with_invertible(true);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    config.apply_patch(&patch, &mut target).unwrap();
+    assert_eq!(target, new);
+    patch.invert().unwrap().apply(&mut target).unwrap();
+    assert_eq!(target, old);
+
+    // Sets are serialized as arrays, which JSON Patches and Merge Patches replace
+    let mut target = old.clone();
+    JsonPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, new);
+    let mut target = old.clone();
+    JsonMergePatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, new);
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_index_set() {
+    use indexmap::IndexSet;
+
+    let old: IndexSet<u32> = vec![1, 2, 3, 4].into_iter().collect();
+    let new: IndexSet<u32> = vec![1, 3, 4, 5].into_iter().collect();
+    roundtrip(old.clone(), new.clone());
+    // Removing an element keeps the order of the others
+    let mut target = old.clone();
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target.iter().copied().collect::<Vec<_>>(), vec![1, 3, 4, 5]);
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_index_map() {
+    use indexmap::IndexMap;
+
+    let old: IndexMap<String, TestStruct> = (0..4)
+        .map(|a| (a.to_string(), TestStruct { a, b: 0. }))
+        .collect();
+    let mut new = old.clone();
+    new.shift_remove("1");
+    new["2"].b = 1.;
+    new.insert("4".to_string(), TestStruct { a: 4, b: 0. });
+    roundtrip(old.clone(), new.clone());
+    let mut target = old.clone();
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target.keys().collect::<Vec<_>>(), vec!["0", "2", "3", "4"]);
+}
+
+#[test]
+fn test_sequences() {
+    use crate::Config;
+    use std::collections::{BinaryHeap, LinkedList, VecDeque};
+
+    // A ring buffer that pops at the front and pushes at the back only sends those changes
+    let old: VecDeque<u32> = (0..1000).collect();
+    let mut new = old.clone();
+    new.pop_front();
+    new.pop_front();
+    new.push_back(1000);
+    roundtrip(old.clone(), new.clone());
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    assert_eq!(patch.commands.len(), 5);
+    let mut front = old.clone();
+    front.push_front(7);
+    front.pop_back();
+    roundtrip(old.clone(), front.clone());
+    roundtrip(VecDeque::new(), front.clone());
+    roundtrip(front, VecDeque::new());
+
+    let config = Config::new().with_invertible(true);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    config.apply_patch(&patch, &mut target).unwrap();
+    assert_eq!(target, new);
+    patch.invert().unwrap().apply(&mut target).unwrap();
+    assert_eq!(target, old);
+
+    let old: LinkedList<TestStruct> = (0..10).map(|a| TestStruct { a, b: 0. }).collect();
+    let mut new = old.clone();
+    new.pop_front();
+    new.push_front(TestStruct { a: 100, b: 1. });
+    new.push_front(TestStruct { a: 101, b: 1. });
+    if let Some(last) = new.back_mut() {
+        last.b = 2.;
+    }
+    roundtrip(old.clone(), new.clone());
+    roundtrip(LinkedList::new(), new);
+
+    let boxed = |values: &[u32]| values.to_vec().into_boxed_slice();
+    roundtrip(boxed(&[1, 2, 3]), boxed(&[1, 5, 3, 4]));
+    roundtrip(boxed(&[1, 2, 3]), boxed(&[]));
+    partial(
+        boxed(&[1, 2]),
+        boxed(&[1, 3]),
+        boxed(&[4, 2, 6]),
+        boxed(&[4, 3, 6]),
+    );
+
+    // Heaps are compared by their contents, regardless of the order the elements were pushed in
+    let heap = |values: &[u32]| values.iter().copied().collect::<BinaryHeap<u32>>();
+    let unchanged = DiffPatch::new(&heap(&[5, 1, 3]), &heap(&[3, 5, 1])).unwrap();
+    assert!(!unchanged.has_changes());
+    let old = heap(&[5, 1, 3, 8]);
+    let mut new = old.clone();
+    new.pop();
+    new.push(2);
+    new.push(4);
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target.into_sorted_vec(), vec![1, 2, 3, 4, 5]);
+    let diff = bincode::serialize(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .deserialize_seed(Apply::deserializable(&mut target), &diff)
+        .unwrap();
+    assert_eq!(target.peek(), Some(&5));
+    assert_eq!(target.into_sorted_vec(), vec![1, 2, 3, 4, 5]);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct PointerStruct {
+    boxed: Box<TestStruct>,
+    shared: std::rc::Rc<TestStruct>,
+    atomic: std::sync::Arc<TestStruct>,
+    cell: std::cell::Cell<TestStruct>,
+    ref_cell: std::cell::RefCell<TestStruct>,
+    wrapping: std::num::Wrapping<u32>,
+    reverse: std::cmp::Reverse<TestStruct>,
+    cow: std::borrow::Cow<'static, TestStruct>,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, Debug, Default)]
+struct LockStruct {
+    mutex: std::sync::Mutex<TestStruct>,
+    rw_lock: std::sync::RwLock<Vec<u32>>,
+}
+
+#[test]
+fn test_pointers() {
+    use crate::{Config, RenderStyle};
+    use std::{borrow::Cow, rc::Rc};
+
+    let value = |a| TestStruct { a, b: 0. };
+    let old = PointerStruct {
+        boxed: Box::new(value(1)),
+        shared: Rc::new(value(1)),
+        atomic: std::sync::Arc::new(value(1)),
+        cell: value(1).into(),
+        ref_cell: value(1).into(),
+        wrapping: std::num::Wrapping(1),
+        reverse: std::cmp::Reverse(value(1)),
+        cow: Cow::Owned(value(1)),
+    };
+    let new = PointerStruct {
+        boxed: Box::new(value(2)),
+        shared: Rc::new(value(2)),
+        atomic: std::sync::Arc::new(value(2)),
+        cell: value(2).into(),
+        ref_cell: value(2).into(),
+        wrapping: std::num::Wrapping(2),
+        reverse: std::cmp::Reverse(value(2)),
+        cow: Cow::Owned(value(2)),
+    };
+    roundtrip(old.clone(), new.clone());
+
+    // Pointers are transparent, so only the fields behind them that changed are in the diff
+    assert_eq!(
+        Config::new()
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "boxed.a: 1 -> 2\nshared.a: 1 -> 2\natomic.a: 1 -> 2\ncell.a: 1 -> 2\n\
+         ref_cell.a: 1 -> 2\nwrapping: 1 -> 2\nreverse.a: 1 -> 2\ncow.a: 1 -> 2\n"
+    );
+    partial(
+        old.clone(),
+        new.clone(),
+        PointerStruct {
+            shared: Rc::new(TestStruct { a: 1, b: 3. }),
+            ..old.clone()
+        },
+        PointerStruct {
+            shared: Rc::new(TestStruct { a: 2, b: 3. }),
+            ..new.clone()
+        },
+    );
+
+    // Applying to a shared value clones it, and leaves the other owners unchanged
+    let mut target = old.clone();
+    let owner = target.shared.clone();
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, new);
+    assert_eq!(owner.a, 1);
+    assert!(!Rc::ptr_eq(&owner, &target.shared));
+    let unchanged = old.clone();
+    assert!(!DiffPatch::new(&old, &unchanged).unwrap().has_changes());
+
+    let old = LockStruct::default();
+    let new = LockStruct::default();
+    new.mutex.lock().unwrap().b = 2.;
+    new.rw_lock.write().unwrap().push(3);
+    assert!(!DiffPatch::new(&old, &old).unwrap().has_changes());
+    let json_diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = LockStruct::default();
+    Apply::apply(
+        &mut serde_json::Deserializer::from_str(&json_diff),
+        &mut target,
+    )
+    .unwrap();
+    assert_eq!(*target.mutex.lock().unwrap(), TestStruct { a: 0, b: 2. });
+    assert_eq!(*target.rw_lock.read().unwrap(), vec![3]);
+}
+
+#[cfg(all(feature = "smallvec", feature = "arrayvec"))]
+#[test]
+fn test_inline_vecs() {
+    use arrayvec::ArrayVec;
+    use smallvec::{smallvec, SmallVec};
+
+    let old: SmallVec<[TestStruct; 2]> = smallvec![TestStruct { a: 1, b: 1. }];
+    let mut new = old.clone();
+    new[0].b = 2.;
+    new.push(TestStruct { a: 2, b: 2. });
+    new.push(TestStruct { a: 3, b: 3. });
+    roundtrip(old.clone(), new.clone());
+    roundtrip(new, old);
+
+    let old: ArrayVec<u32, 3> = vec![1, 2].into_iter().collect();
+    let new: ArrayVec<u32, 3> = vec![1, 3, 4].into_iter().collect();
+    roundtrip(old.clone(), new.clone());
+    roundtrip(new.clone(), old.clone());
+
+    // Elements beyond the capacity can't be added
+    let mut target: ArrayVec<u32, 3> = vec![5, 6, 7].into_iter().collect();
+    assert!(DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .is_err());
+    assert_eq!(target.as_slice(), &[5, 3, 7]);
+}
+
+#[cfg(all(
+    feature = "uuid",
+    feature = "chrono",
+    feature = "bytes",
+    feature = "url"
+))]
+#[test]
+fn test_opaque_dependencies() {
+    roundtrip(uuid::Uuid::nil(), uuid::Uuid::from_u128(42));
+    let date = chrono::NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+    roundtrip(date, date.succ_opt().unwrap());
+    let time = date.and_hms_opt(12, 0, 0).unwrap().and_utc();
+    roundtrip(time, time + chrono::Duration::seconds(1));
+    roundtrip(
+        bytes::Bytes::from_static(b"old"),
+        bytes::Bytes::from_static(b"new"),
+    );
+    roundtrip(
+        url::Url::parse("https://example.com/old").unwrap(),
+        url::Url::parse("https://example.com/new").unwrap(),
+    );
+}
+
+#[cfg(all(feature = "glam", feature = "nalgebra"))]
+#[test]
+fn test_math_vectors() {
+    use crate::{Config, RenderStyle};
+
+    #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Transform {
+        position: glam::Vec3,
+        rotation: glam::Quat,
+        scale: nalgebra::Vector3<f32>,
+        matrix: nalgebra::Matrix2<f64>,
+    }
+
+    let old = Transform {
+        position: glam::Vec3::ZERO,
+        rotation: glam::Quat::IDENTITY,
+        scale: nalgebra::Vector3::new(1., 1., 1.),
+        matrix: nalgebra::Matrix2::identity(),
+    };
+    let mut new = old.clone();
+    new.position.y = 2.;
+    new.scale.z = 3.;
+    new.matrix[(0, 1)] = 4.;
+    roundtrip(old.clone(), new.clone());
+    // Vectors are diffed component-wise, so only the changed components are sent
+    assert_eq!(
+        Config::new()
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "position.y: 0 -> 2\nscale[0][2]: 1 -> 3\nmatrix[1][0]: 0 -> 4\n"
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_json_value() {
+    use serde_json::json;
+
+    let old = json!({
+        "name": "config",
+        "limits": { "cpu": 1, "memory": 512 },
+        "hosts": ["a", "b", "c"],
+        "debug": false,
+    });
+    let new = json!({
+        "name": "config",
+        "limits": { "cpu": 2, "memory": 512, "disk": 10 },
+        "hosts": ["a", "c", "d"],
+        "debug": { "level": 3 },
+    });
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Only the changed values are in the diff, not the whole document
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    assert!(!diff.contains("config") && !diff.contains("512"));
+    let mut target = old.clone();
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target, new);
+    let diff = rmp_serde::to_vec(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut rmp_serde::Deserializer::new(&diff[..]), &mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Changes apply to documents that differ elsewhere, and are skipped where the type or keys
+    // changed
+    let mut target = json!({
+        "name": "other",
+        "limits": { "cpu": 1, "memory": 1024 },
+        "hosts": "all",
+    });
+    patch.apply(&mut target).unwrap();
+    assert_eq!(
+        target,
+        json!({
+            "name": "other",
+            "limits": { "cpu": 2, "memory": 1024, "disk": 10 },
+            "hosts": "all",
+        })
+    );
+
+    let config = crate::Config::new().with_invertible(true);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    config.apply_patch(&patch, &mut target).unwrap();
+    patch.invert().unwrap().apply(&mut target).unwrap();
+    assert_eq!(target, old);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_toml_value() {
+    use crate::{Config, RenderStyle};
+
+    let old: toml::Value = toml::from_str(
+        r#"
+        [package]
+        name = "crate"
+        version = "0.1.0"
+        authors = ["a"]
+
+        [dependencies]
+        serde = "1"
+        "#,
+    )
+    .unwrap();
+    let new: toml::Value = toml::from_str(
+        r#"
+        [package]
+        name = "crate"
+        version = "0.2.0"
+        authors = ["a", "b"]
+
+        [dependencies]
+        serde = { version = "1", features = ["derive"] }
+        "#,
+    )
+    .unwrap();
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Tables are entered by key and arrays by index, while the dependency changed type
+    assert_eq!(
+        Config::new()
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "[\"dependencies\"][\"serde\"]: \"1\" -> {\"features\": [\"derive\"], \"version\": \"1\"}\n\
+         + [\"package\"][\"authors\"][+] = \"b\"\n\
+         [\"package\"][\"version\"]: \"0.1.0\" -> \"0.2.0\"\n"
+    );
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct SerializedStruct {
+    name: String,
+    values: Vec<TestStruct>,
+    lookup: std::collections::BTreeMap<String, u32>,
+    optional: Option<TestStruct>,
+    shape: SerializedEnum,
+    pair: [String; 2],
+    wrapped: Wrapped,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum SerializedEnum {
+    Empty,
+    Circle(f64),
+    Rect { w: f64, h: f64 },
+    Pair(u32, u32),
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct Wrapped(u32, u32);
+
+#[test]
+fn test_serialized() {
+    use crate::Config;
+
+    use crate::Serialized;
+
+    let old = SerializedStruct {
+        name: "old".to_string(),
+        values: vec![TestStruct { a: 1, b: 1. }, TestStruct { a: 2, b: 2. }],
+        lookup: vec![("a".to_string(), 1), ("b".to_string(), 2)]
+            .into_iter()
+            .collect(),
+        optional: None,
+        shape: SerializedEnum::Rect { w: 1., h: 2. },
+        pair: ["one".to_string(), "one".to_string()],
+        wrapped: Wrapped(1, 2),
+    };
+    let changes = [
+        SerializedStruct {
+            name: "new".to_string(),
+            values: vec![TestStruct { a: 1, b: 3. }],
+            lookup: vec![("a".to_string(), 3), ("c".to_string(), 2)]
+                .into_iter()
+                .collect(),
+            optional: Some(TestStruct { a: 1, b: 1. }),
+            shape: SerializedEnum::Rect { w: 1., h: 3. },
+            pair: ["one".to_string(), "two".to_string()],
+            wrapped: Wrapped(1, 3),
+        },
+        SerializedStruct {
+            values: vec![
+                TestStruct { a: 1, b: 1. },
+                TestStruct { a: 2, b: 2. },
+                TestStruct { a: 3, b: 3. },
+            ],
+            shape: SerializedEnum::Circle(1.),
+            ..old.clone()
+        },
+        SerializedStruct {
+            shape: SerializedEnum::Empty,
+            ..old.clone()
+        },
+        SerializedStruct {
+            shape: SerializedEnum::Pair(1, 2),
+            ..old.clone()
+        },
+    ];
+
+    for new in changes.iter() {
+        // The diff is the same as the derived implementation's
+        let derived = serde_json::to_string(&Diff::serializable(&old, new)).unwrap();
+        let (old, new) = (Serialized(old.clone()), Serialized(new.clone()));
+        let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+        assert_eq!(diff, derived);
+
+        let mut target = old.clone();
+        Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+        assert_eq!(target, new);
+        let diff = rmp_serde::to_vec(&Diff::serializable(&old, &new)).unwrap();
+        let mut target = old.clone();
+        Apply::apply(&mut rmp_serde::Deserializer::new(&diff[..]), &mut target).unwrap();
+        assert_eq!(target, new);
+        let mut target = old.clone();
+        DiffPatch::new(&old, &new)
+            .unwrap()
+            .apply(&mut target)
+            .unwrap();
+        assert_eq!(target, new);
+    }
+
+    // Fields that are skipped when serializing are added by the diff
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Skipping {
+        a: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        b: Option<u32>,
+    }
+    let old = Serialized(Skipping { a: 1, b: None });
+    let new = Serialized(Skipping { a: 1, b: Some(2) });
+    let mut target = Serialized(Skipping { a: 3, b: None });
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target.0, Skipping { a: 3, b: Some(2) });
+
+    // And removed when the new value skips them, so the target gets their default
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Outer {
+        inner: Skipping,
+        c: u32,
+    }
+    let old = Serialized(Outer {
+        inner: Skipping { a: 1, b: Some(2) },
+        c: 1,
+    });
+    let new = Serialized(Outer {
+        inner: Skipping { a: 1, b: None },
+        c: 2,
+    });
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target, new);
+    let diff = rmp_serde::to_vec(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut rmp_serde::Deserializer::new(&diff[..]), &mut target).unwrap();
+    assert_eq!(target, new);
+    for invertible in &[false, true] {
+        let config = Config::new().with_invertible(*invertible);
+        let patch = config.diff_patch(&old, &new).unwrap();
+        let mut target = old.clone();
+        config.apply_patch(&patch, &mut target).unwrap();
+        assert_eq!(target, new);
+        if *invertible {
+            patch.invert().unwrap().apply(&mut target).unwrap();
+            assert_eq!(target, old);
+        }
+    }
+    let patch = crate::JsonPatch::new(&old, &new).unwrap();
+    assert!(patch.0.contains(&crate::JsonPatchOperation::Remove {
+        path: "/inner/b".to_string()
+    }));
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Map keys are found whatever integer type the format reads them back as
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Keyed {
+        names: std::collections::BTreeMap<u32, String>,
+        offsets: std::collections::BTreeMap<i16, f32>,
+        letters: std::collections::BTreeMap<char, u8>,
+    }
+    let old = Serialized(Keyed {
+        names: vec![(1, "one".to_string()), (2, "two".to_string())]
+            .into_iter()
+            .collect(),
+        offsets: vec![(-1, 0.5), (1, 1.5)].into_iter().collect(),
+        letters: vec![('a', 1), ('b', 2)].into_iter().collect(),
+    });
+    let new = Serialized(Keyed {
+        names: vec![(1, "uno".to_string()), (3, "three".to_string())]
+            .into_iter()
+            .collect(),
+        offsets: vec![(-1, 2.5), (1, 1.5)].into_iter().collect(),
+        letters: vec![('a', 3), ('b', 2)].into_iter().collect(),
+    });
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target, new);
+    let diff = rmp_serde::to_vec(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = old.clone();
+    Apply::apply(&mut rmp_serde::Deserializer::new(&diff[..]), &mut target).unwrap();
+    assert_eq!(target, new);
+    // Diffs of the derived implementation find the same entries
+    let (old, new) = (old.0.names, new.0.names);
+    let diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut target = Serialized(old);
+    Apply::apply(&mut serde_json::Deserializer::from_str(&diff), &mut target).unwrap();
+    assert_eq!(target.0, new);
+
+    // Diffs of tuples from derived implementations are applied by field name
+    let old = ((1, "one".to_string()), 1);
+    let new = ((1, "two".to_string()), 1);
+    let mut target = Serialized(old.clone());
+    DiffPatch::new(&old, &new)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target.0, new);
+
+    // A value that fails to serialize fails the diff, including the pass that counts commands
+    #[derive(Deserialize, PartialEq, Debug, Clone)]
+    struct Failing(u32);
+    impl Serialize for Failing {
+        fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("can't serialize"))
+        }
+    }
+    let old = Serialized(Failing(1));
+    let new = Serialized(Failing(2));
+    let error = bincode::serialize(&Diff::serializable(&old, &new)).unwrap_err();
+    assert!(error.to_string().contains("can't serialize"));
+    assert!(serde_json::to_string(&Diff::serializable(&old, &new)).is_err());
+}
+
+/// Stands in for a crate whose types don't implement SerdeDiff
+mod foreign {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    pub struct Transform {
+        pub position: (f32, f32),
+        pub scale: f32,
+        pub cache: Vec<u8>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    pub enum Shape {
+        Circle { radius: f32 },
+        Rect(f32, f32),
+        Empty,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    pub struct Pair<T>(pub T, pub T);
+}
+
+// The cache is left out of the mirror, so it is never diffed
+#[allow(dead_code)]
+#[derive(SerdeDiff)]
+#[serde_diff(remote = "foreign::Transform")]
+struct TransformDef {
+    position: (f32, f32),
+    scale: f32,
+}
+
+#[allow(dead_code)]
+#[derive(SerdeDiff)]
+#[serde_diff(remote = "foreign::Shape")]
+enum ShapeDef {
+    Circle { radius: f32 },
+    Rect(f32, f32),
+    Empty,
+}
+
+#[derive(SerdeDiff)]
+#[serde_diff(remote = "foreign::Pair<T>")]
+struct PairDef<T>(T, T)
+where
+    T: SerdeDiff;
+
+/// Diffs a string as a whole, even where only part of it changed
+mod whole_string {
+    use crate::{ApplyContext, DiffContext};
+    use serde::{de::SeqAccess, ser::SerializeSeq};
+
+    pub fn diff<'a, S: SerializeSeq>(
+        this: &String,
+        ctx: &mut DiffContext<'a, S>,
+        other: &String,
+    ) -> Result<bool, S::Error> {
+        if this != other {
+            ctx.save_change(this, other)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub fn apply<'de, A: SeqAccess<'de>>(
+        this: &mut String,
+        seq: &mut A,
+        ctx: &mut ApplyContext,
+    ) -> Result<bool, A::Error> {
+        ctx.read_value(seq, this)
+    }
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct RemoteStruct {
+    #[serde_diff(with = "TransformDef")]
+    transform: foreign::Transform,
+    #[serde_diff(with = "ShapeDef")]
+    shape: foreign::Shape,
+    #[serde_diff(with = "PairDef")]
+    pair: foreign::Pair<u32>,
+    #[serde_diff(with = "whole_string")]
+    name: String,
+}
+
+#[test]
+fn test_remote() {
+    use crate::{Config, RenderStyle};
+
+    let old = RemoteStruct {
+        transform: foreign::Transform {
+            position: (0., 0.),
+            scale: 1.,
+            cache: vec![1, 2, 3],
+        },
+        shape: foreign::Shape::Circle { radius: 1. },
+        pair: foreign::Pair(1, 2),
+        name: "old".to_owned(),
+    };
+    let new = RemoteStruct {
+        transform: foreign::Transform {
+            position: (0., 2.),
+            scale: 1.,
+            cache: vec![1, 2, 3],
+        },
+        shape: foreign::Shape::Circle { radius: 2. },
+        pair: foreign::Pair(1, 3),
+        name: "new".to_owned(),
+    };
+    roundtrip(old.clone(), new.clone());
+    roundtrip(
+        old.clone(),
+        RemoteStruct {
+            shape: foreign::Shape::Rect(1., 2.),
+            ..old.clone()
+        },
+    );
+    roundtrip(
+        RemoteStruct {
+            shape: foreign::Shape::Empty,
+            ..old.clone()
+        },
+        old.clone(),
+    );
+
+    // Remote types are diffed field by field like derived ones
+    assert_eq!(
+        Config::new()
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "transform.position.1: 0 -> 2\nshape.Circle.radius: 1 -> 2\npair.1: 2 -> 3\n\
+         name: \"old\" -> \"new\"\n"
+    );
+
+    // Fields left out of the mirror are neither diffed nor applied
+    let mut cached = old.clone();
+    cached.transform.cache.clear();
+    partial(old.clone(), cached, old.clone(), old.clone());
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum IndexedEnum {
+    Unit,
+    Struct { x: u32, y: (u32, String) },
+    Tuple(u32, u32),
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct IndexedStruct {
+    #[serde_diff(skip)]
+    skipped: u32,
+    value: u32,
+    variant: IndexedEnum,
+}
+
+#[test]
+fn test_field_path_mode() {
+    use crate::{Config, FieldPathMode, RenderStyle};
+
+    let old = IndexedStruct {
+        skipped: 0,
+        value: 1,
+        variant: IndexedEnum::Struct {
+            x: 1,
+            y: (1, "a".to_owned()),
+        },
+    };
+    let new = IndexedStruct {
+        skipped: 0,
+        value: 2,
+        variant: IndexedEnum::Struct {
+            x: 2,
+            y: (1, "b".to_owned()),
+        },
+    };
+    let config = || Config::new().with_field_path_mode(FieldPathMode::Index);
+    let render = |config: Config, old, new| config.render_diff(old, new, RenderStyle::Plain);
+    assert_eq!(
+        render(config(), &old, &new).unwrap(),
+        "1: 1 -> 2\n2.<variant 1>.0: 1 -> 2\n2.<variant 1>.1.1: \"a\" -> \"b\"\n"
+    );
+    assert_eq!(
+        render(Config::new(), &old, &new).unwrap(),
+        "value: 1 -> 2\nvariant.Struct.x: 1 -> 2\nvariant.Struct.y.1: \"a\" -> \"b\"\n"
+    );
+
+    // Diffs written in either mode apply to the same type
+    for mode in &[FieldPathMode::Index, FieldPathMode::Name] {
+        let config = Config::new().with_field_path_mode(*mode);
+        let diff = serde_json::to_string(&config.serializable_diff(&old, &new)).unwrap();
+        let mut target = old.clone();
+        let mut deserializer = serde_json::Deserializer::from_str(&diff);
+        Config::new().apply(&mut deserializer, &mut target).unwrap();
+        assert_eq!(target, new);
+    }
+    let patch = config().diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    // Index paths don't match other variants
+    let tuple = IndexedStruct {
+        variant: IndexedEnum::Tuple(1, 2),
+        ..old.clone()
+    };
+    let mut target = tuple.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, IndexedStruct { value: 2, ..tuple });
+
+    // Changing the variant replaces it in either mode
+    let unit = IndexedStruct {
+        variant: IndexedEnum::Unit,
+        ..old.clone()
+    };
+    let mut target = old.clone();
+    config()
+        .diff_patch(&old, &unit)
+        .unwrap()
+        .apply(&mut target)
+        .unwrap();
+    assert_eq!(target, unit);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct ItemV1 {
+    #[serde_diff(id = 10)]
+    name: String,
+    #[serde_diff(id = 11)]
+    count: u32,
+    kind: KindV1,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum KindV1 {
+    #[serde_diff(id = 10)]
+    Tool { durability: u32 },
+    #[serde_diff(id = 11)]
+    Food(u32),
+}
+
+// Fields and variants reordered, added and renamed since ItemV1
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct ItemV2 {
+    #[serde_diff(id = 12)]
+    weight: u32,
+    #[serde_diff(id = 11)]
+    amount: u32,
+    #[serde_diff(id = 10)]
+    name: String,
+    kind: KindV2,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum KindV2 {
+    #[serde_diff(id = 12)]
+    Potion,
+    #[serde_diff(id = 11)]
+    Food(u32),
+    #[serde_diff(id = 10)]
+    Tool { durability: u32 },
+}
+
+#[test]
+fn test_field_ids() {
+    use crate::{Config, FieldPathMode, RenderStyle};
+
+    let old = ItemV1 {
+        name: "axe".to_owned(),
+        count: 1,
+        kind: KindV1::Tool { durability: 10 },
+    };
+    let new = ItemV1 {
+        name: "pick".to_owned(),
+        count: 2,
+        kind: KindV1::Tool { durability: 9 },
+    };
+    roundtrip(old.clone(), new.clone());
+    let config = |mode| Config::new().with_field_path_mode(mode);
+    assert_eq!(
+        config(FieldPathMode::Tag)
+            .render_diff(&old, &new, RenderStyle::Plain)
+            .unwrap(),
+        "10: \"axe\" -> \"pick\"\n11: 1 -> 2\nkind.<variant 10>.durability: 10 -> 9\n"
+    );
+
+    let target = ItemV2 {
+        weight: 5,
+        amount: 1,
+        name: "axe".to_owned(),
+        kind: KindV2::Tool { durability: 10 },
+    };
+    let apply = |mode, target: &ItemV2| {
+        let mut target = target.clone();
+        let patch = config(mode).diff_patch(&old, &new).unwrap();
+        patch.apply(&mut target).unwrap();
+        target
+    };
+    // Tagged fields and variants apply to the new layout, and untagged ones by name
+    assert_eq!(
+        apply(FieldPathMode::Tag, &target),
+        ItemV2 {
+            weight: 5,
+            amount: 2,
+            name: "pick".to_owned(),
+            kind: KindV2::Tool { durability: 9 },
+        }
+    );
+    // The untagged field's index changed, so its change is lost
+    assert_eq!(
+        apply(FieldPathMode::Index, &target),
+        ItemV2 {
+            weight: 5,
+            amount: 2,
+            name: "pick".to_owned(),
+            kind: KindV2::Tool { durability: 10 },
+        }
+    );
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct SaveV1 {
+    level: u32,
+    name: String,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct SaveV2 {
+    level: u32,
+    name: Option<String>,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct TreeNode {
+    value: u32,
+    children: Vec<TreeNode>,
+    parent: Option<Box<TreeNode>>,
+}
+
+// The same save type in two modules, as when a type is moved to another module
+mod saves {
+    use crate as serde_diff;
+    use crate::SerdeDiff;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+    pub struct Save {
+        pub level: u32,
+        #[serde_diff(opaque)]
+        pub seed: (u64, u64),
+        pub scores: HashMap<String, u32>,
+    }
+
+    pub mod moved {
+        use crate as serde_diff;
+        use crate::SerdeDiff;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+        pub struct Save {
+            pub level: u32,
+            #[serde_diff(opaque)]
+            pub seed: (u64, u64),
+            pub scores: std::collections::HashMap<std::string::String, u32>,
+        }
+    }
+}
+
+// A local type with the layout of the remote type `TransformDef` mirrors
+mod local {
+    use crate as serde_diff;
+    use crate::SerdeDiff;
+
+    #[allow(dead_code)]
+    #[derive(SerdeDiff)]
+    pub struct Transform {
+        position: (f32, f32),
+        scale: f32,
+    }
+}
+
+#[test]
+fn test_fingerprint() {
+    use crate::{ApplyError, ApplyFailure, Config, Fingerprint};
+    use std::collections::HashMap;
+
+    // Structurally different types have different fingerprints, and the fingerprint of a type
+    // that contains itself is stable
+    assert_ne!(SaveV1::fingerprint(), SaveV2::fingerprint());
+    assert_ne!(Vec::<u32>::fingerprint(), Vec::<i32>::fingerprint());
+    assert_eq!(TreeNode::fingerprint(), TreeNode::fingerprint());
+    assert_ne!(TreeNode::fingerprint(), Vec::<TreeNode>::fingerprint());
+    // Fingerprints only depend on names and layout, not on module paths
+    assert_eq!(
+        saves::Save::fingerprint(),
+        saves::moved::Save::fingerprint()
+    );
+    assert_eq!(TransformDef::fingerprint(), local::Transform::fingerprint());
+    assert_ne!(
+        HashMap::<String, u32>::fingerprint(),
+        HashMap::<u32, u32>::fingerprint()
+    );
+    assert_ne!(<(u64, u32)>::fingerprint(), <(u64, u64)>::fingerprint());
+    assert_ne!(
+        Fingerprint::deserialized::<(u64, u64)>(),
+        Fingerprint::deserialized::<(u32, u64)>()
+    );
+    assert_eq!(
+        Fingerprint::deserialized::<Vec<SaveV1>>(),
+        Fingerprint::deserialized::<Vec<SaveV1>>()
+    );
+    assert_ne!(
+        Fingerprint::deserialized::<Vec<SaveV1>>(),
+        Fingerprint::deserialized::<Vec<SaveV2>>()
+    );
+
+    let config = Config::new().with_fingerprint(true);
+    let old = SaveV1 {
+        level: 1,
+        name: "start".to_owned(),
+    };
+    let new = SaveV1 {
+        level: 2,
+        name: "forest".to_owned(),
+    };
+
+    // A fingerprinted diff applies to the type it was created for
+    let diff = config.serializable_diff(&old, &new);
+    let bincode_diff = bincode::serialize(&diff).unwrap();
+    let mut target = old.clone();
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize_seed(Apply::deserializable(&mut target), &bincode_diff)
+        .unwrap();
+    assert_eq!(target, new);
+    let patch = config.diff_patch(&old, &new).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+    let inverted = config
+        .with_invertible(true)
+        .diff_patch(&old, &new)
+        .unwrap()
+        .invert()
+        .unwrap();
+    let mut target = new.clone();
+    inverted.apply(&mut target).unwrap();
+    assert_eq!(target, old);
+    assert!(!config.diff_patch(&old, &old).unwrap().has_changes());
+
+    // Applying it to a different type fails before anything is changed
+    let mut target = SaveV2 {
+        level: 1,
+        name: None,
+    };
+    assert_eq!(
+        patch.apply(&mut target).unwrap_err().to_string(),
+        ApplyError::FingerprintMismatch {
+            expected: SaveV2::fingerprint(),
+            found: SaveV1::fingerprint(),
+        }
+        .to_string()
+    );
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes();
+    let mut deserializer = bincode::Deserializer::from_slice(&bincode_diff, options);
+    match Config::new().try_apply(&mut deserializer, &mut target) {
+        Err(ApplyFailure::Apply(ApplyError::FingerprintMismatch { expected, found })) => {
+            assert_eq!(expected, SaveV2::fingerprint());
+            assert_eq!(found, SaveV1::fingerprint());
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert_eq!(
+        target,
+        SaveV2 {
+            level: 1,
+            name: None,
+        }
+    );
+
+    // Diffs without a fingerprint still apply
+    let patch = DiffPatch::new(&old, &new).unwrap();
+    let mut target = old.clone();
+    patch.apply(&mut target).unwrap();
+    assert_eq!(target, new);
+
+    let old = TreeNode {
+        value: 1,
+        children: vec![],
+        parent: None,
+    };
+    let mut new = old.clone();
+    new.children.push(old.clone());
+    let mut target = old.clone();
+    config
+        .apply_patch(&config.diff_patch(&old, &new).unwrap(), &mut target)
+        .unwrap();
+    assert_eq!(target, new);
+}
+
+thread_local! {
+    /// Number of times `DiffCounted::diff` was called on this thread
+    static DIFF_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Counts how often it is diffed
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct DiffCounted(u32);
+
+impl SerdeDiff for DiffCounted {
+    fn diff<'a, S: serde::ser::SerializeSeq>(
+        &self,
+        ctx: &mut crate::DiffContext<'a, S>,
+        other: &Self,
+    ) -> Result<bool, S::Error> {
+        DIFF_CALLS.with(|calls| calls.set(calls.get() + 1));
+        self.0.diff(ctx, &other.0)
+    }
+
+    fn apply<'de, A>(
+        &mut self,
+        seq: &mut A,
+        ctx: &mut crate::ApplyContext,
+    ) -> Result<bool, <A as serde::de::SeqAccess<'de>>::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        self.0.apply(seq, ctx)
+    }
+}
+
+#[test]
+fn test_buffered_serialization() {
+    use crate::{Config, VecDiffMode};
+
+    let (old, new) = malformed_struct_pair();
+    let configs = [
+        Config::new(),
+        Config::new()
+            .with_invertible(true)
+            .with_fingerprint(true)
+            .with_vec_diff_mode(VecDiffMode::EditScript),
+    ];
+    for config in &configs {
+        let buffered = config.with_buffered_serialization(true);
+
+        // Buffering writes the same commands as diffing twice
+        let diff = buffered.serializable_diff(&old, &new);
+        let bincode_diff = bincode::serialize(&diff).unwrap();
+        assert!(diff.has_changes());
+        assert_eq!(
+            bincode_diff,
+            bincode::serialize(&config.serializable_diff(&old, &new)).unwrap()
+        );
+        let rmp_diff = rmp_serde::to_vec(&diff).unwrap();
+        assert_eq!(
+            rmp_diff,
+            rmp_serde::to_vec(&config.serializable_diff(&old, &new)).unwrap()
+        );
+
+        let mut target = old.clone();
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_seed(Apply::deserializable(&mut target), &bincode_diff)
+            .unwrap();
+        assert_eq!(target, new);
+        let mut target = old.clone();
+        Apply::apply(
+            &mut rmp_serde::Deserializer::new(&rmp_diff[..]),
+            &mut target,
+        )
+        .unwrap();
+        assert_eq!(target, new);
+
+        // Human-readable formats are unaffected
+        assert_eq!(
+            serde_json::to_string(&buffered.serializable_diff(&old, &new)).unwrap(),
+            serde_json::to_string(&config.serializable_diff(&old, &new)).unwrap()
+        );
+
+        let diff = buffered.serializable_diff(&old, &old);
+        bincode::serialize(&diff).unwrap();
+        assert!(!diff.has_changes());
+    }
+
+    // Buffering diffs the values once, where formats that need the length up front otherwise
+    // diff them twice
+    let diff_calls = |config: Config, serialize: fn(&Diff<DiffCounted>)| {
+        DIFF_CALLS.with(|calls| calls.set(0));
+        serialize(&config.serializable_diff(&DiffCounted(1), &DiffCounted(2)));
+        DIFF_CALLS.with(|calls| calls.get())
+    };
+    // bincode::serialize serializes once more to size its buffer, which serialize_into doesn't
+    let to_bincode: fn(&Diff<DiffCounted>) = |diff| {
+        bincode::serialize_into(Vec::new(), diff).unwrap();
+    };
+    let to_json: fn(&Diff<DiffCounted>) = |diff| {
+        serde_json::to_string(diff).unwrap();
+    };
+    let buffered = Config::new().with_buffered_serialization(true);
+    assert_eq!(diff_calls(buffered, to_bincode), 1);
+    assert_eq!(diff_calls(Config::new(), to_bincode), 2);
+    assert_eq!(diff_calls(buffered, to_json), 1);
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct ProfileV1 {
+    name: String,
+    stats: std::collections::BTreeMap<String, TestStruct>,
+    tags: Vec<String>,
+    shape: SerializedEnum,
+    level: u32,
+}
+
+#[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct ProfileV2 {
+    name: String,
+    level: u32,
+}
+
+#[test]
+fn test_framed_values() {
+    use crate::Config;
+
+    let old = ProfileV1 {
+        name: "old".to_owned(),
+        stats: vec![
+            ("a".to_owned(), TestStruct { a: 1, b: 1. }),
+            ("b".to_owned(), TestStruct { a: 2, b: 2. }),
+        ]
+        .into_iter()
+        .collect(),
+        tags: vec!["x".to_owned(), "y".to_owned(), "z".to_owned()],
+        shape: SerializedEnum::Circle(1.),
+        level: 1,
+    };
+    let new = ProfileV1 {
+        name: "new".to_owned(),
+        stats: vec![
+            ("a".to_owned(), TestStruct { a: 3, b: 1. }),
+            ("c".to_owned(), TestStruct { a: 4, b: 4. }),
+        ]
+        .into_iter()
+        .collect(),
+        tags: vec!["x".to_owned()],
+        shape: SerializedEnum::Rect { w: 1., h: 2. },
+        level: 2,
+    };
+    let apply_bincode = |bincode_diff: &[u8], target: &mut ProfileV2| {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_seed(Apply::deserializable(target), bincode_diff)
+    };
+    let target = ProfileV2 {
+        name: "old".to_owned(),
+        level: 1,
+    };
+
+    let expected = ProfileV2 {
+        name: "new".to_owned(),
+        level: 2,
+    };
+
+    // Self-describing formats skip the fields the receiver doesn't have, including maps
+    let json_diff = serde_json::to_string(&Diff::serializable(&old, &new)).unwrap();
+    let mut partial_target = target.clone();
+    Apply::apply(
+        &mut serde_json::Deserializer::from_str(&json_diff),
+        &mut partial_target,
+    )
+    .unwrap();
+    assert_eq!(partial_target, expected);
+
+    // Without framing, bincode can't skip the fields the receiver doesn't have
+    let bincode_diff = bincode::serialize(&Diff::serializable(&old, &new)).unwrap();
+    assert!(apply_bincode(&bincode_diff, &mut target.clone()).is_err());
+
+    for config in &[Config::new(), Config::new().with_invertible(true)] {
+        let framed = config.with_framed_values(true);
+
+        // Fields the receiver doesn't have are skipped in every format
+        let bincode_diff = bincode::serialize(&framed.serializable_diff(&old, &new)).unwrap();
+        let mut partial_target = target.clone();
+        apply_bincode(&bincode_diff, &mut partial_target).unwrap();
+        assert_eq!(partial_target, expected);
+
+        // Receivers that know every field apply all of the changes
+        let mut full_target = old.clone();
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_seed(Apply::deserializable(&mut full_target), &bincode_diff)
+            .unwrap();
+        assert_eq!(full_target, new);
+        let rmp_diff = rmp_serde::to_vec(&framed.serializable_diff(&old, &new)).unwrap();
+        let mut full_target = old.clone();
+        Apply::apply(
+            &mut rmp_serde::Deserializer::new(&rmp_diff[..]),
+            &mut full_target,
+        )
+        .unwrap();
+        assert_eq!(full_target, new);
+        let json_diff = serde_json::to_string(&framed.serializable_diff(&old, &new)).unwrap();
+        let mut full_target = old.clone();
+        let mut deserializer = serde_json::Deserializer::from_str(&json_diff);
+        Apply::apply(&mut deserializer, &mut full_target).unwrap();
+        assert_eq!(full_target, new);
+
+        // Framing combines with buffering
+        let buffered = framed.with_buffered_serialization(true);
+        assert_eq!(
+            bincode::serialize(&buffered.serializable_diff(&old, &new)).unwrap(),
+            bincode_diff
+        );
+
+        // Patches hold values unframed
+        assert!(serde_json::from_str::<DiffPatch>(&json_diff).is_err());
+    }
+
+    // Values that serialize differently in human-readable formats are framed in that form, and
+    // read back the same way
+    #[derive(SerdeDiff, Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Endpoint {
+        addr: std::net::IpAddr,
+        port: u16,
+    }
+    let old = Endpoint {
+        addr: [127, 0, 0, 1].into(),
+        port: 80,
+    };
+    let new = Endpoint {
+        addr: [10, 0, 0, 2].into(),
+        port: 8080,
+    };
+    let framed = Config::new().with_framed_values(true);
+    let bincode_diff = bincode::serialize(&framed.serializable_diff(&old, &new)).unwrap();
+    let mut target = old.clone();
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize_seed(Apply::deserializable(&mut target), &bincode_diff)
+        .unwrap();
+    assert_eq!(target, new);
+    assert!(bincode_diff.windows(8).any(|bytes| bytes == b"10.0.0.2"));
+}